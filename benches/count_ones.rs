@@ -0,0 +1,63 @@
+//! Compare `Bitset::count_ones` with a plain word-by-word sum on a 1024-leaf bitset,
+//! and on shorter ones around the length where the `popcnt` path starts.
+//!
+//! Run with `cargo bench --bench count_ones`. Uses only `std`, so no benchmark
+//! framework is needed; the two variants are timed in alternating rounds and the
+//! fastest round of each is reported, which keeps scheduler noise out of the ratio.
+
+use rust_python_tree_distances::bitset::Bitset;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const CALLS: usize = 20_000_000;
+const ROUNDS: usize = 7;
+
+fn naive_count_ones(bs: &Bitset) -> usize {
+    bs.0.iter().map(|w| w.count_ones() as usize).sum()
+}
+
+/// Total time of `CALLS` calls of `count`, with its sum checked against `expected`.
+fn time(bs: &Bitset, expected: usize, count: impl Fn(&Bitset) -> usize) -> Duration {
+    let start = Instant::now();
+    let mut total = 0;
+    for _ in 0..CALLS {
+        total += count(black_box(bs));
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(total, expected * CALLS);
+    elapsed
+}
+
+/// Print the best-of-`ROUNDS` times of both variants on `bs`.
+fn compare(label: &str, bs: &Bitset) {
+    let expected = naive_count_ones(bs);
+    let (mut optimized, mut naive) = (Duration::MAX, Duration::MAX);
+    for _ in 0..ROUNDS {
+        optimized = optimized.min(time(bs, expected, Bitset::count_ones));
+        naive = naive.min(time(bs, expected, naive_count_ones));
+    }
+    println!("count_ones, {label}, {CALLS} calls, best of {ROUNDS} rounds");
+    println!("  Bitset::count_ones:     {optimized:?}");
+    println!("  naive word-by-word sum: {naive:?}");
+    println!(
+        "  optimized / naive: {:.2}",
+        optimized.as_secs_f64() / naive.as_secs_f64()
+    );
+}
+
+fn main() {
+    // Deterministic pseudo-random words (xorshift64)
+    let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+    let words: Vec<u64> = (0..1024 / 64)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        })
+        .collect();
+
+    compare("1024 leaves", &Bitset(words.clone()));
+    compare("256 leaves", &Bitset(words[..4].to_vec()));
+    compare("64 leaves", &Bitset(words[..1].to_vec()));
+}
@@ -4,72 +4,444 @@
 //! from BEAST/NEXUS tree files.
 
 use phylotree::tree::Tree as PhyloTree;
-use pyo3::exceptions::PyValueError;
+use pyo3::exceptions::{PyIndexError, PyRuntimeError, PyUserWarning, PyValueError};
 use pyo3::prelude::*;
-use rayon::prelude::*;
 use std::collections::HashSet;
+use std::ffi::CString;
+use std::sync::Mutex;
 
-use crate::distances::{kf_from_snapshots, rf_from_snapshots, weighted_rf_from_snapshots};
-use crate::io::read_beast_trees;
+use crate::bitset::Bitset;
+use crate::consensus;
+use crate::distances::{
+    DistanceError, DistanceValue, KuhnerFelsenstein, KuhnerFelsensteinSquared, MatchingSplit,
+    NormalizedRobinsonFoulds, NormalizedWeightedRobinsonFoulds, RfMode, RobinsonFoulds,
+    RobinsonFouldsWithMode, TreeDistance, WeightedRobinsonFoulds, check_subset, compute_sym_matrix,
+    cross_matrix, max_rf, medoid_index, pairwise_matrix, rf_detailed_from_snapshots,
+    weighted_rf_components_from_snapshots,
+};
+use crate::io::{
+    DEFAULT_NAME_TEMPLATE, ReadError, read_beast_tree_files_with, read_tree_states,
+    strip_beast_annotations,
+};
+use crate::monitor::BatchMonitor;
 use crate::snapshot::TreeSnapshot;
+use crate::stats::tree_diameter;
+use crate::utils::{self, exclude_taxa, prune_to_common_taxa};
 
-/// Compute pairwise Robinson-Foulds distances from multiple tree files.
+/// Tree names plus the square distance matrix between them.
+type NamedMatrix<T> = (Vec<String>, Vec<Vec<T>>);
+
+/// Row tree names, column tree names and the rectangular distance matrix between them.
+type CrossMatrix = (Vec<String>, Vec<String>, Vec<Vec<f64>>);
+
+/// Splits, each given as the taxon names on one side.
+type Splits = Vec<Vec<String>>;
+
+/// Metric names accepted by the functions taking a `metric` string.
+const METRICS: &str = "rf, weighted, wrf_norm, kf, kf2, norm_rf, matching_split";
+
+/// Evaluate `$body` with `$metric` bound to the metric named `$name`, or return a
+/// `ValueError` listing the valid names. RF uses the rooted handling `$mode`
+/// (`RfMode::Auto` if omitted).
+///
+/// Each metric is a different type, so this dispatches through a macro instead of a closure.
+macro_rules! with_metric {
+    ($name:expr, |$metric:ident| $body:expr) => {
+        with_metric!($name, RfMode::Auto, |$metric| $body)
+    };
+    ($name:expr, $mode:expr, |$metric:ident| $body:expr) => {
+        match $name {
+            "rf" => {
+                let $metric = &RobinsonFouldsWithMode { mode: $mode };
+                $body
+            }
+            "weighted" => {
+                let $metric = &WeightedRobinsonFoulds;
+                $body
+            }
+            "wrf_norm" => {
+                let $metric = &NormalizedWeightedRobinsonFoulds;
+                $body
+            }
+            "kf" => {
+                let $metric = &KuhnerFelsenstein;
+                $body
+            }
+            "kf2" => {
+                let $metric = &KuhnerFelsensteinSquared;
+                $body
+            }
+            "norm_rf" => {
+                let $metric = &NormalizedRobinsonFoulds;
+                $body
+            }
+            "matching_split" => {
+                let $metric = &MatchingSplit;
+                $body
+            }
+            other => Err(PyValueError::new_err(format!(
+                "Unknown metric '{}', expected one of: {}",
+                other, METRICS
+            ))),
+        }
+    };
+}
+
+/// Compute pairwise distances from multiple tree files with a metric chosen by name.
 ///
 /// Args:
 ///     paths: List of file paths to BEAST/NEXUS tree files
+///     metric: One of "rf", "weighted", "wrf_norm", "kf", "kf2", "norm_rf", "matching_split"
+///         (default: "rf")
 ///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
 ///     burnin_states: Minimum STATE value to keep trees (default: 0)
 ///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///     prune_to_common: Prune all trees to their shared taxa instead of requiring identical leaf sets (default: False)
+///     exclude_taxa: Optional list of tip names removed from every tree before comparing;
+///         names found in no tree raise a UserWarning (default: None)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///     threads: Number of worker threads, 0 uses all cores (default: 0)
+///     progress_callback: Optional callable called as progress_callback(done, total) with
+///         the number of tree pairs compared so far, from worker threads (default: None)
+///     subset: Optional list of distinct tree indices; only these trees are compared, in
+///         the given order (default: None)
+///     mode: How RF treats rooted trees: "auto" adds 2 for different root splits only
+///         when both trees are rooted, "rooted" whenever both roots are binary,
+///         "unrooted" never; only "auto" is valid for other metrics (default: "auto")
 ///
 /// Returns:
 ///     A tuple of (tree_names, distance_matrix) where:
 ///     - tree_names is a list of tree identifiers
-///     - distance_matrix is a 2D list of RF distances
+///     - distance_matrix is a 2D list of floats (RF counts are cast to float)
 ///
 /// Raises:
-///     ValueError: If no trees are found, trees have different leaf sets, or sanity checks fail
+///     ValueError: If the metric or mode is unknown, no trees are found, a subset index is
+///     out of range or repeated, or sanity checks fail
 #[pyfunction]
-#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true))]
-fn pairwise_rf(
+#[pyo3(signature = (paths, metric="rf", burnin_trees=0, burnin_states=0, use_real_taxa=true, prune_to_common=false, threads=0, progress_callback=None, subset=None, mode="auto", exclude_taxa=None, name_template=None))]
+#[allow(clippy::too_many_arguments)] // one per Python keyword argument
+fn pairwise_distances(
     paths: Vec<String>,
+    metric: &str,
     burnin_trees: usize,
     burnin_states: usize,
     use_real_taxa: bool,
-) -> PyResult<(Vec<String>, Vec<Vec<usize>>)> {
-    // Read all trees from all files
-    let (tree_names, trees) = read_all_trees(&paths, burnin_trees, burnin_states, use_real_taxa)?;
+    prune_to_common: bool,
+    threads: usize,
+    progress_callback: Option<Py<PyAny>>,
+    subset: Option<Vec<usize>>,
+    mode: &str,
+    exclude_taxa: Option<Vec<String>>,
+    name_template: Option<String>,
+) -> PyResult<NamedMatrix<f64>> {
+    let run = RunOptions {
+        threads,
+        progress_callback,
+        subset,
+    };
+    let mode = rf_mode(mode, metric)?;
+    with_metric!(metric, mode, |metric| {
+        let (tree_names, matrix) = compute_pairwise(
+            &paths,
+            &ReadOptions {
+                burnin_trees,
+                burnin_states,
+                use_real_taxa,
+                name_template,
+            },
+            &TaxaFilter {
+                prune_to_common,
+                exclude_taxa,
+            },
+            &run,
+            metric,
+        )?;
+        Ok((tree_names, widen(matrix)))
+    })
+}
 
-    // Perform sanity checks
-    sanity_check_trees(&trees)?;
+/// Compute the distances between the trees of two sets of files, e.g. two independent
+/// MCMC chains, to assess whether they converged.
+///
+/// Args:
+///     paths_a: Tree files of the first set; its trees are the rows
+///     paths_b: Tree files of the second set; its trees are the columns
+///     metric: One of "rf", "weighted", "wrf_norm", "kf", "kf2", "norm_rf", "matching_split"
+///         (default: "rf")
+///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
+///     burnin_states: Minimum STATE value to keep trees (default: 0)
+///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///     threads: Number of worker threads, 0 uses all cores (default: 0)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///
+/// Returns:
+///     A tuple of (names_a, names_b, distance_matrix) where distance_matrix[i][j] is the
+///     distance between tree names_a[i] and tree names_b[j] (a rectangular matrix)
+///
+/// Raises:
+///     ValueError: If the metric is unknown, a set has no trees, or the trees do not all
+///     share the same leaf set (trees are numbered through set A, then set B)
+#[pyfunction]
+#[pyo3(signature = (paths_a, paths_b, metric="rf", burnin_trees=0, burnin_states=0, use_real_taxa=true, threads=0, name_template=None))]
+#[allow(clippy::too_many_arguments)] // one per Python keyword argument
+fn cross_distances(
+    py: Python<'_>,
+    paths_a: Vec<String>,
+    paths_b: Vec<String>,
+    metric: &str,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+    threads: usize,
+    name_template: Option<String>,
+) -> PyResult<CrossMatrix> {
+    let read = ReadOptions {
+        burnin_trees,
+        burnin_states,
+        use_real_taxa,
+        name_template,
+    };
+    let (names_a, mut trees) = read_all_trees(&paths_a, &read)?;
+    let (names_b, trees_b) = read_all_trees(&paths_b, &read)?;
+    // Checking both sets as one ensures the rows and columns share a leaf set
+    trees.extend(trees_b);
+
+    with_metric!(metric, |metric| {
+        let matrix = py.detach(|| {
+            in_thread_pool(threads, || {
+                let mut snapshots = checked_snapshots(trees, metric.include_trivial())?;
+                let snapshots_b = snapshots.split_off(names_a.len());
+                Ok(cross_matrix(&snapshots, &snapshots_b, metric))
+            })
+        })?;
+        Ok((names_a, names_b, matrix))
+    })
+}
 
-    // Build snapshots
-    let snapshots: Vec<TreeSnapshot> = trees
+/// Compute pairwise distances between trees given as Newick strings.
+///
+/// Args:
+///     newicks: List of Newick strings (BEAST [&...] annotations are ignored)
+///     metric: One of "rf", "weighted", "wrf_norm", "kf", "kf2", "norm_rf", "matching_split"
+///         (default: "rf")
+///     threads: Number of worker threads, 0 uses all cores (default: 0)
+///     progress_callback: Optional callable called as progress_callback(done, total) with
+///         the number of tree pairs compared so far, from worker threads (default: None)
+///     subset: Optional list of distinct tree indices; only these trees are compared, in
+///         the given order (default: None)
+///     mode: How RF treats rooted trees, see pairwise_distances (default: "auto")
+///
+/// Returns:
+///     A 2D list of floats where matrix[i][j] is the distance between newicks[i] and newicks[j]
+///
+/// Raises:
+///     ValueError: If a string cannot be parsed (the message names its index), the metric
+///     or mode is unknown, a subset index is out of range or repeated, or sanity checks fail
+#[pyfunction]
+#[pyo3(signature = (newicks, metric="rf", threads=0, progress_callback=None, subset=None, mode="auto"))]
+fn pairwise_rf_from_newick(
+    newicks: Vec<String>,
+    metric: &str,
+    threads: usize,
+    progress_callback: Option<Py<PyAny>>,
+    subset: Option<Vec<usize>>,
+    mode: &str,
+) -> PyResult<Vec<Vec<f64>>> {
+    let trees = newicks
         .iter()
-        .map(TreeSnapshot::from_tree)
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| PyValueError::new_err(format!("Failed to create tree snapshot: {}", e)))?;
-
-    // Compute pairwise distances
-    let n = snapshots.len();
-    let mut matrix = vec![vec![0usize; n]; n];
-
-    // Parallel computation across all pairs
-    let pairs: Vec<(usize, usize, usize)> = (0..n)
-        .into_par_iter()
-        .flat_map_iter(|i| (i + 1..n).map(move |j| (i, j)))
-        .map(|(i, j)| {
-            let dist = rf_from_snapshots(&snapshots[i], &snapshots[j]);
-            (i, j, dist)
+        .enumerate()
+        .map(|(idx, newick)| {
+            PhyloTree::from_newick(&strip_beast_annotations(newick)).map_err(|e| {
+                PyValueError::new_err(format!("Failed to parse tree at index {}: {}", idx, e))
+            })
         })
-        .collect();
+        .collect::<PyResult<Vec<_>>>()?;
 
-    // Fill matrix (symmetric)
-    for (i, j, dist) in pairs {
-        matrix[i][j] = dist;
-        matrix[j][i] = dist;
-    }
+    let run = RunOptions {
+        threads,
+        progress_callback,
+        subset,
+    };
+    let mode = rf_mode(mode, metric)?;
+    with_metric!(metric, mode, |metric| {
+        matrix_for_trees(trees, metric, &run).map(widen)
+    })
+}
 
-    Ok((tree_names, matrix))
+/// Compute the Robinson-Foulds distance between two Newick trees and list the splits
+/// found in only one of them.
+///
+/// Each split is given as the taxa on its side without the alphabetically first taxon,
+/// so the same split is written the same way for both trees.
+///
+/// Args:
+///     newick_a: First tree as a Newick string (BEAST [&...] annotations are ignored)
+///     newick_b: Second tree, on the same taxa
+///
+/// Returns:
+///     A tuple of (distance, only_in_a, only_in_b) where only_in_a lists the splits of
+///     tree A missing from tree B as sorted lists of taxon names, and vice versa
+///
+/// Raises:
+///     ValueError: If a string cannot be parsed or the trees have different taxa
+#[pyfunction]
+fn rf_detailed(newick_a: &str, newick_b: &str) -> PyResult<(usize, Splits, Splits)> {
+    let trees = [newick_a, newick_b]
+        .iter()
+        .enumerate()
+        .map(|(idx, newick)| {
+            PhyloTree::from_newick(&strip_beast_annotations(newick)).map_err(|e| {
+                PyValueError::new_err(format!("Failed to parse tree at index {}: {}", idx, e))
+            })
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let snapshots = checked_snapshots(trees, false)?;
+    let (a, b) = (&snapshots[0], &snapshots[1]);
+
+    let detail = rf_detailed_from_snapshots(a, b);
+    let names = |snap: &TreeSnapshot, parts: &[Bitset]| -> Splits {
+        parts
+            .iter()
+            .map(|part| snap.taxa(part).into_iter().map(String::from).collect())
+            .collect()
+    };
+    Ok((
+        detail.distance,
+        names(a, &detail.only_in_a),
+        names(b, &detail.only_in_b),
+    ))
+}
+
+/// Compute pairwise Robinson-Foulds distances from multiple tree files.
+///
+/// Args:
+///     paths: List of file paths to BEAST/NEXUS tree files
+///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
+///     burnin_states: Minimum STATE value to keep trees (default: 0)
+///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///     prune_to_common: Prune all trees to their shared taxa instead of requiring identical leaf sets (default: False)
+///     exclude_taxa: Optional list of tip names removed from every tree before comparing;
+///         names found in no tree raise a UserWarning (default: None)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///     threads: Number of worker threads, 0 uses all cores (default: 0)
+///     subset: Optional list of distinct tree indices; only these trees are compared, in
+///         the given order (default: None)
+///     mode: How rooted trees are treated: "auto" adds 2 for different root splits only
+///         when both trees are rooted, "rooted" whenever both roots are binary,
+///         "unrooted" never (default: "auto")
+///
+/// Returns:
+///     A tuple of (tree_names, distance_matrix) where:
+///     - tree_names is a list of tree identifiers
+///     - distance_matrix is a 2D list of RF distances
+///
+/// Raises:
+///     ValueError: If the mode is unknown, no trees are found, a subset index is out of
+///     range or repeated, trees have different leaf sets, or sanity checks fail
+#[pyfunction]
+#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true, prune_to_common=false, threads=0, subset=None, mode="auto", exclude_taxa=None, name_template=None))]
+#[allow(clippy::too_many_arguments)] // one per Python keyword argument
+fn pairwise_rf(
+    paths: Vec<String>,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+    prune_to_common: bool,
+    threads: usize,
+    subset: Option<Vec<usize>>,
+    mode: &str,
+    exclude_taxa: Option<Vec<String>>,
+    name_template: Option<String>,
+) -> PyResult<(Vec<String>, Vec<Vec<usize>>)> {
+    let mode = rf_mode(mode, "rf")?;
+    compute_pairwise(
+        &paths,
+        &ReadOptions {
+            burnin_trees,
+            burnin_states,
+            use_real_taxa,
+            name_template,
+        },
+        &TaxaFilter {
+            prune_to_common,
+            exclude_taxa,
+        },
+        &RunOptions {
+            threads,
+            subset,
+            ..Default::default()
+        },
+        &RobinsonFouldsWithMode { mode },
+    )
+}
+
+/// Compute pairwise normalized Robinson-Foulds distances from multiple tree files.
+///
+/// Each RF distance is divided by its maximum possible value (2n - 6 for unrooted,
+/// 2n - 4 for rooted binary trees), so trees with different taxon counts are comparable.
+///
+/// Args:
+///     paths: List of file paths to BEAST/NEXUS tree files
+///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
+///     burnin_states: Minimum STATE value to keep trees (default: 0)
+///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///     prune_to_common: Prune all trees to their shared taxa instead of requiring identical leaf sets (default: False)
+///     exclude_taxa: Optional list of tip names removed from every tree before comparing;
+///         names found in no tree raise a UserWarning (default: None)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///     threads: Number of worker threads, 0 uses all cores (default: 0)
+///     subset: Optional list of distinct tree indices; only these trees are compared, in
+///         the given order (default: None)
+///
+/// Returns:
+///     A tuple of (tree_names, distance_matrix) where:
+///     - tree_names is a list of tree identifiers
+///     - distance_matrix is a 2D list of normalized RF distances in [0, 1]
+///
+/// Raises:
+///     ValueError: If no trees are found, a subset index is out of range or repeated, trees
+///     have different leaf sets, or sanity checks fail
+#[pyfunction]
+#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true, prune_to_common=false, threads=0, subset=None, exclude_taxa=None, name_template=None))]
+#[allow(clippy::too_many_arguments)] // one per Python keyword argument
+fn pairwise_norm_rf(
+    paths: Vec<String>,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+    prune_to_common: bool,
+    threads: usize,
+    subset: Option<Vec<usize>>,
+    exclude_taxa: Option<Vec<String>>,
+    name_template: Option<String>,
+) -> PyResult<(Vec<String>, Vec<Vec<f64>>)> {
+    compute_pairwise(
+        &paths,
+        &ReadOptions {
+            burnin_trees,
+            burnin_states,
+            use_real_taxa,
+            name_template,
+        },
+        &TaxaFilter {
+            prune_to_common,
+            exclude_taxa,
+        },
+        &RunOptions {
+            threads,
+            subset,
+            ..Default::default()
+        },
+        &NormalizedRobinsonFoulds,
+    )
 }
 
 /// Compute pairwise Weighted Robinson-Foulds distances from multiple tree files.
@@ -81,6 +453,15 @@ fn pairwise_rf(
 ///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
 ///     burnin_states: Minimum STATE value to keep trees (default: 0)
 ///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///     prune_to_common: Prune all trees to their shared taxa instead of requiring identical leaf sets (default: False)
+///     exclude_taxa: Optional list of tip names removed from every tree before comparing;
+///         names found in no tree raise a UserWarning (default: None)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///     threads: Number of worker threads, 0 uses all cores (default: 0)
+///     subset: Optional list of distinct tree indices; only these trees are compared, in
+///         the given order (default: None)
 ///
 /// Returns:
 ///     A tuple of (tree_names, distance_matrix) where:
@@ -88,42 +469,104 @@ fn pairwise_rf(
 ///     - distance_matrix is a 2D list of weighted RF distances
 ///
 /// Raises:
-///     ValueError: If no trees are found, trees have different leaf sets, or sanity checks fail
+///     ValueError: If no trees are found, a subset index is out of range or repeated, trees
+///     have different leaf sets, or sanity checks fail
 #[pyfunction]
-#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true))]
+#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true, prune_to_common=false, threads=0, subset=None, exclude_taxa=None, name_template=None))]
+#[allow(clippy::too_many_arguments)] // one per Python keyword argument
 fn pairwise_weighted_rf(
     paths: Vec<String>,
     burnin_trees: usize,
     burnin_states: usize,
     use_real_taxa: bool,
+    prune_to_common: bool,
+    threads: usize,
+    subset: Option<Vec<usize>>,
+    exclude_taxa: Option<Vec<String>>,
+    name_template: Option<String>,
 ) -> PyResult<(Vec<String>, Vec<Vec<f64>>)> {
-    let (tree_names, trees) = read_all_trees(&paths, burnin_trees, burnin_states, use_real_taxa)?;
-    sanity_check_trees(&trees)?;
-
-    let snapshots: Vec<TreeSnapshot> = trees
-        .iter()
-        .map(TreeSnapshot::from_tree)
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| PyValueError::new_err(format!("Failed to create tree snapshot: {}", e)))?;
-
-    let n = snapshots.len();
-    let mut matrix = vec![vec![0.0f64; n]; n];
-
-    let pairs: Vec<(usize, usize, f64)> = (0..n)
-        .into_par_iter()
-        .flat_map_iter(|i| (i + 1..n).map(move |j| (i, j)))
-        .map(|(i, j)| {
-            let dist = weighted_rf_from_snapshots(&snapshots[i], &snapshots[j]);
-            (i, j, dist)
-        })
-        .collect();
-
-    for (i, j, dist) in pairs {
-        matrix[i][j] = dist;
-        matrix[j][i] = dist;
-    }
+    compute_pairwise(
+        &paths,
+        &ReadOptions {
+            burnin_trees,
+            burnin_states,
+            use_real_taxa,
+            name_template,
+        },
+        &TaxaFilter {
+            prune_to_common,
+            exclude_taxa,
+        },
+        &RunOptions {
+            threads,
+            subset,
+            ..Default::default()
+        },
+        &WeightedRobinsonFoulds,
+    )
+}
 
-    Ok((tree_names, matrix))
+/// Compute pairwise weighted Robinson-Foulds distances on normalized branch lengths.
+///
+/// Each tree's branch lengths are divided by its total branch length first, so trees on
+/// different absolute time scales are comparable.
+///
+/// Args:
+///     paths: List of file paths to BEAST/NEXUS tree files
+///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
+///     burnin_states: Minimum STATE value to keep trees (default: 0)
+///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///     prune_to_common: Prune all trees to their shared taxa instead of requiring identical leaf sets (default: False)
+///     exclude_taxa: Optional list of tip names removed from every tree before comparing;
+///         names found in no tree raise a UserWarning (default: None)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///     threads: Number of worker threads, 0 uses all cores (default: 0)
+///     subset: Optional list of distinct tree indices; only these trees are compared, in
+///         the given order (default: None)
+///
+/// Returns:
+///     A tuple of (tree_names, distance_matrix) where:
+///     - tree_names is a list of tree identifiers
+///     - distance_matrix is a 2D list of normalized weighted RF distances in [0, 2]
+///
+/// Raises:
+///     ValueError: If no trees are found, a subset index is out of range or repeated, trees
+///     have different leaf sets, or sanity checks fail
+#[pyfunction]
+#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true, prune_to_common=false, threads=0, subset=None, exclude_taxa=None, name_template=None))]
+#[allow(clippy::too_many_arguments)] // one per Python keyword argument
+fn pairwise_weighted_rf_norm(
+    paths: Vec<String>,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+    prune_to_common: bool,
+    threads: usize,
+    subset: Option<Vec<usize>>,
+    exclude_taxa: Option<Vec<String>>,
+    name_template: Option<String>,
+) -> PyResult<(Vec<String>, Vec<Vec<f64>>)> {
+    compute_pairwise(
+        &paths,
+        &ReadOptions {
+            burnin_trees,
+            burnin_states,
+            use_real_taxa,
+            name_template,
+        },
+        &TaxaFilter {
+            prune_to_common,
+            exclude_taxa,
+        },
+        &RunOptions {
+            threads,
+            subset,
+            ..Default::default()
+        },
+        &NormalizedWeightedRobinsonFoulds,
+    )
 }
 
 /// Compute pairwise Kuhner-Felsenstein (Branch Score) distances from multiple tree files.
@@ -135,6 +578,15 @@ fn pairwise_weighted_rf(
 ///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
 ///     burnin_states: Minimum STATE value to keep trees (default: 0)
 ///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///     prune_to_common: Prune all trees to their shared taxa instead of requiring identical leaf sets (default: False)
+///     exclude_taxa: Optional list of tip names removed from every tree before comparing;
+///         names found in no tree raise a UserWarning (default: None)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///     threads: Number of worker threads, 0 uses all cores (default: 0)
+///     subset: Optional list of distinct tree indices; only these trees are compared, in
+///         the given order (default: None)
 ///
 /// Returns:
 ///     A tuple of (tree_names, distance_matrix) where:
@@ -142,84 +594,828 @@ fn pairwise_weighted_rf(
 ///     - distance_matrix is a 2D list of KF distances
 ///
 /// Raises:
-///     ValueError: If no trees are found, trees have different leaf sets, or sanity checks fail
+///     ValueError: If no trees are found, a subset index is out of range or repeated, trees
+///     have different leaf sets, or sanity checks fail
 #[pyfunction]
-#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true))]
+#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true, prune_to_common=false, threads=0, subset=None, exclude_taxa=None, name_template=None))]
+#[allow(clippy::too_many_arguments)] // one per Python keyword argument
 fn pairwise_kf(
     paths: Vec<String>,
     burnin_trees: usize,
     burnin_states: usize,
     use_real_taxa: bool,
+    prune_to_common: bool,
+    threads: usize,
+    subset: Option<Vec<usize>>,
+    exclude_taxa: Option<Vec<String>>,
+    name_template: Option<String>,
 ) -> PyResult<(Vec<String>, Vec<Vec<f64>>)> {
-    let (tree_names, trees) = read_all_trees(&paths, burnin_trees, burnin_states, use_real_taxa)?;
-    sanity_check_trees(&trees)?;
+    compute_pairwise(
+        &paths,
+        &ReadOptions {
+            burnin_trees,
+            burnin_states,
+            use_real_taxa,
+            name_template,
+        },
+        &TaxaFilter {
+            prune_to_common,
+            exclude_taxa,
+        },
+        &RunOptions {
+            threads,
+            subset,
+            ..Default::default()
+        },
+        &KuhnerFelsenstein,
+    )
+}
+
+/// Compute pairwise squared Kuhner-Felsenstein distances from multiple tree files.
+///
+/// This is Σ(length_a - length_b)² without the square root of `pairwise_kf`, which is
+/// additive over branches.
+///
+/// Args:
+///     paths: List of file paths to BEAST/NEXUS tree files
+///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
+///     burnin_states: Minimum STATE value to keep trees (default: 0)
+///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///     prune_to_common: Prune all trees to their shared taxa instead of requiring identical leaf sets (default: False)
+///     exclude_taxa: Optional list of tip names removed from every tree before comparing;
+///         names found in no tree raise a UserWarning (default: None)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///     threads: Number of worker threads, 0 uses all cores (default: 0)
+///     subset: Optional list of distinct tree indices; only these trees are compared, in
+///         the given order (default: None)
+///
+/// Returns:
+///     A tuple of (tree_names, distance_matrix) where:
+///     - tree_names is a list of tree identifiers
+///     - distance_matrix is a 2D list of squared KF distances
+///
+/// Raises:
+///     ValueError: If no trees are found, a subset index is out of range or repeated, trees
+///     have different leaf sets, or sanity checks fail
+#[pyfunction]
+#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true, prune_to_common=false, threads=0, subset=None, exclude_taxa=None, name_template=None))]
+#[allow(clippy::too_many_arguments)] // one per Python keyword argument
+fn pairwise_kf2(
+    paths: Vec<String>,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+    prune_to_common: bool,
+    threads: usize,
+    subset: Option<Vec<usize>>,
+    exclude_taxa: Option<Vec<String>>,
+    name_template: Option<String>,
+) -> PyResult<(Vec<String>, Vec<Vec<f64>>)> {
+    compute_pairwise(
+        &paths,
+        &ReadOptions {
+            burnin_trees,
+            burnin_states,
+            use_real_taxa,
+            name_template,
+        },
+        &TaxaFilter {
+            prune_to_common,
+            exclude_taxa,
+        },
+        &RunOptions {
+            threads,
+            subset,
+            ..Default::default()
+        },
+        &KuhnerFelsensteinSquared,
+    )
+}
+
+/// Read the taxon labels and the MCMC state of every tree, without computing distances.
+///
+/// Tree names and burn-in match the `pairwise_*` functions, so `states[i]` is the
+/// `STATE_` number of row `i` of a distance matrix computed from the same arguments.
+///
+/// Args:
+///     paths: List of file paths to BEAST/NEXUS tree files
+///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
+///     burnin_states: Minimum STATE value to keep trees (default: 0)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///
+/// Returns:
+///     A tuple of (taxon_labels, tree_names, states) where:
+///     - taxon_labels is the sorted list of TRANSLATE labels (empty without a TRANSLATE block)
+///     - tree_names is a list of tree identifiers
+///     - states[i] is the STATE number of tree_names[i]
+///
+/// Raises:
+///     ValueError: If a file cannot be read or has no trees after burn-in
+#[pyfunction]
+#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, name_template=None))]
+fn read_trees_info(
+    paths: Vec<String>,
+    burnin_trees: usize,
+    burnin_states: usize,
+    name_template: Option<String>,
+) -> PyResult<(Vec<String>, Vec<String>, Vec<usize>)> {
+    let name_template = name_template.as_deref().unwrap_or(DEFAULT_NAME_TEMPLATE);
+    let (labels, named_states) =
+        read_tree_states(&paths, burnin_trees, burnin_states, name_template)
+            .map_err(|(file_idx, e)| file_read_error(&paths, file_idx, e))?;
+    let (names, states) = named_states.into_iter().unzip();
+    Ok((labels, names, states))
+}
 
-    let snapshots: Vec<TreeSnapshot> = trees
+/// Compute the diameter of every tree: the longest leaf-to-leaf path in branch length units.
+///
+/// Args:
+///     paths: List of file paths to BEAST/NEXUS tree files
+///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
+///     burnin_states: Minimum STATE value to keep trees (default: 0)
+///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///     name_template: Tree names with {base} (file name), {index} (position in the file)
+///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+///         "file<N>_{base}_tree_STATE{state}" (default: None)
+///
+/// Returns:
+///     A tuple of (tree_names, diameters) where diameters[i] belongs to tree_names[i]
+///
+/// Raises:
+///     ValueError: If no trees are found or a tree is malformed
+#[pyfunction]
+#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true, name_template=None))]
+fn tree_diameters(
+    paths: Vec<String>,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+    name_template: Option<String>,
+) -> PyResult<(Vec<String>, Vec<f64>)> {
+    let read = ReadOptions {
+        burnin_trees,
+        burnin_states,
+        use_real_taxa,
+        name_template,
+    };
+    let (tree_names, trees) = read_all_trees(&paths, &read)?;
+
+    let diameters = trees
         .iter()
-        .map(TreeSnapshot::from_tree)
+        .map(tree_diameter)
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| PyValueError::new_err(format!("Failed to create tree snapshot: {}", e)))?;
+        .map_err(|e| PyValueError::new_err(format!("Failed to compute tree diameter: {}", e)))?;
 
-    let n = snapshots.len();
-    let mut matrix = vec![vec![0.0f64; n]; n];
+    Ok((tree_names, diameters))
+}
+
+/// Fraction of trees containing each bipartition, most frequent first.
+///
+/// Args:
+///     paths: List of file paths to BEAST/NEXUS tree files
+///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
+///     burnin_states: Minimum STATE value to keep trees (default: 0)
+///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+///
+/// Returns:
+///     A list of (taxa_in_split, frequency) tuples, where taxa_in_split lists the taxa on
+///     the side of the split without the alphabetically first taxon
+///
+/// Raises:
+///     ValueError: If no trees are found or trees have different leaf sets
+#[pyfunction]
+#[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true))]
+fn bipartition_frequencies(
+    paths: Vec<String>,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+) -> PyResult<Vec<(Vec<String>, f64)>> {
+    let read = ReadOptions {
+        burnin_trees,
+        burnin_states,
+        use_real_taxa,
+        ..Default::default()
+    };
+    let (_, trees) = read_all_trees(&paths, &read)?;
+    // A single tree has nothing to compare, but its table is still well defined
+    let snapshots = if trees.len() == 1 {
+        TreeSnapshot::from_trees(trees, false)
+            .map_err(|e| PyValueError::new_err(format!("Failed to create tree snapshot: {}", e)))?
+    } else {
+        checked_snapshots(trees, false)?
+    };
 
-    let pairs: Vec<(usize, usize, f64)> = (0..n)
-        .into_par_iter()
-        .flat_map_iter(|i| (i + 1..n).map(move |j| (i, j)))
-        .map(|(i, j)| {
-            let dist = kf_from_snapshots(&snapshots[i], &snapshots[j]);
-            (i, j, dist)
+    Ok(consensus::bipartition_frequencies(&snapshots)
+        .into_iter()
+        .map(|(part, freq)| {
+            let taxa = snapshots[0].taxa(&part).into_iter().map(String::from);
+            (taxa.collect(), freq)
         })
-        .collect();
+        .collect())
+}
 
-    for (i, j, dist) in pairs {
-        matrix[i][j] = dist;
-        matrix[j][i] = dist;
+/// Online convergence monitor for trees arriving in batches.
+///
+/// Keeps a count of every partition seen so far; each batch is scored against the
+/// majority-rule consensus of all earlier trees before being added to the history.
+///
+/// Example:
+///     monitor = BatchMonitor()
+///     mean_within, mean_to_consensus, new_partitions = monitor.process_batch(newicks)
+#[pyclass(name = "BatchMonitor")]
+#[derive(Default)]
+struct PyBatchMonitor {
+    inner: BatchMonitor,
+}
+
+#[pymethods]
+impl PyBatchMonitor {
+    #[new]
+    fn new() -> Self {
+        Self::default()
     }
 
-    Ok((tree_names, matrix))
+    /// Score a batch of Newick strings, then add it to the history.
+    ///
+    /// Returns:
+    ///     A tuple of (mean_within, mean_to_consensus, new_partitions) where
+    ///     mean_to_consensus is None for the first batch
+    ///
+    /// Raises:
+    ///     ValueError: If a Newick string cannot be parsed, or if a tree's taxa differ
+    ///         from those of the first tree processed
+    fn process_batch(&mut self, newicks: Vec<String>) -> PyResult<(f64, Option<f64>, usize)> {
+        let trees = newicks
+            .iter()
+            .enumerate()
+            .map(|(idx, newick)| {
+                PhyloTree::from_newick(&strip_beast_annotations(newick)).map_err(|e| {
+                    PyValueError::new_err(format!("Failed to parse tree at index {}: {}", idx, e))
+                })
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let snapshots = TreeSnapshot::from_trees(trees, false)
+            .map_err(|e| PyValueError::new_err(format!("Failed to create tree snapshot: {}", e)))?;
+
+        let report = self.inner.process_batch(&snapshots).map_err(|e| match e {
+            DistanceError::LeafSetMismatch { index } => {
+                let reference: HashSet<String> = self
+                    .inner
+                    .leaf_names()
+                    .unwrap_or(&snapshots[0].leaf_names)
+                    .iter()
+                    .cloned()
+                    .collect();
+                let leaves: HashSet<String> = snapshots[index].leaf_names.iter().cloned().collect();
+                let diff = utils::describe_leaf_set_difference(&reference, &leaves, index)
+                    .unwrap_or_default();
+                PyValueError::new_err(format!(
+                    "Tree {} of the batch has a different leaf set than the monitored trees ({}). All trees must have the same taxa.",
+                    index, diff
+                ))
+            }
+            e => PyValueError::new_err(e.to_string()),
+        })?;
+        Ok((
+            report.mean_within,
+            report.mean_to_consensus,
+            report.new_partitions,
+        ))
+    }
+
+    /// Total number of trees processed so far.
+    #[getter]
+    fn trees_seen(&self) -> usize {
+        self.inner.trees_seen()
+    }
 }
 
-/// Helper function to read trees from multiple files
-fn read_all_trees(
+/// Trees read and snapshotted once, for computing several metrics without re-parsing.
+///
+/// Snapshots keep the trivial (single-leaf) splits so the same set serves every metric;
+/// they are shared by all trees and do not change RF.
+///
+/// Example:
+///     trees = TreeSet(["file1.trees"], burnin_trees=10)
+///     rf = trees.rf()
+///     wrf = trees.weighted_rf()
+#[pyclass(name = "TreeSet")]
+struct PyTreeSet {
+    names: Vec<String>,
+    snapshots: Vec<TreeSnapshot>,
+    threads: usize,
+}
+
+#[pymethods]
+impl PyTreeSet {
+    /// Read, check and snapshot the trees of all files.
+    ///
+    /// Args:
+    ///     paths: List of file paths to BEAST/NEXUS tree files
+    ///     burnin_trees: Number of trees to skip at the beginning of each file (default: 0)
+    ///     burnin_states: Minimum STATE value to keep trees (default: 0)
+    ///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+    ///     prune_to_common: Prune all trees to their shared taxa instead of requiring identical leaf sets (default: False)
+    ///     exclude_taxa: Optional list of tip names removed from every tree before comparing;
+    ///         names found in no tree raise a UserWarning (default: None)
+    ///     name_template: Tree names with {base} (file name), {index} (position in the file)
+    ///         and {state} placeholders, e.g. "{base}.{index}"; None gives
+    ///         "file<N>_{base}_tree_STATE{state}" (default: None)
+    ///     threads: Number of worker threads for snapshots and matrices, 0 uses all cores (default: 0)
+    ///
+    /// Raises:
+    ///     ValueError: If no trees are found, trees have different leaf sets, or sanity checks fail
+    #[new]
+    #[pyo3(signature = (paths, burnin_trees=0, burnin_states=0, use_real_taxa=true, prune_to_common=false, threads=0, exclude_taxa=None, name_template=None))]
+    #[allow(clippy::too_many_arguments)] // one per Python keyword argument
+    fn new(
+        py: Python<'_>,
+        paths: Vec<String>,
+        burnin_trees: usize,
+        burnin_states: usize,
+        use_real_taxa: bool,
+        prune_to_common: bool,
+        threads: usize,
+        exclude_taxa: Option<Vec<String>>,
+        name_template: Option<String>,
+    ) -> PyResult<Self> {
+        let read = ReadOptions {
+            burnin_trees,
+            burnin_states,
+            use_real_taxa,
+            name_template,
+        };
+        let (names, mut trees) = read_all_trees(&paths, &read)?;
+        TaxaFilter {
+            prune_to_common,
+            exclude_taxa,
+        }
+        .apply(py, &mut trees)?;
+        let snapshots = py.detach(|| in_thread_pool(threads, || checked_snapshots(trees, true)))?;
+
+        Ok(Self {
+            names,
+            snapshots,
+            threads,
+        })
+    }
+
+    /// Tree identifiers, in matrix order.
+    #[getter]
+    fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    /// Largest possible RF distance for these trees: 2n - 6 for unrooted, 2n - 4 for
+    /// rooted binary trees with n taxa, and 0 with fewer than 4 taxa.
+    #[getter]
+    fn max_rf(&self) -> usize {
+        self.snapshots.first().map_or(0, max_rf)
+    }
+
+    fn __len__(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Pairwise Robinson-Foulds distances as a 2D list.
+    fn rf(&self, py: Python<'_>) -> PyResult<Vec<Vec<usize>>> {
+        self.matrix(py, &RobinsonFoulds)
+    }
+
+    /// Pairwise weighted Robinson-Foulds distances as a 2D list.
+    fn weighted_rf(&self, py: Python<'_>) -> PyResult<Vec<Vec<f64>>> {
+        self.matrix(py, &WeightedRobinsonFoulds)
+    }
+
+    /// Pairwise Kuhner-Felsenstein distances as a 2D list.
+    fn kf(&self, py: Python<'_>) -> PyResult<Vec<Vec<f64>>> {
+        self.matrix(py, &KuhnerFelsenstein)
+    }
+
+    /// The medoid tree: the one with the smallest summed distance to all others.
+    ///
+    /// Args:
+    ///     metric: One of "rf", "weighted", "wrf_norm", "kf", "kf2", "norm_rf", "matching_split"
+    ///         (default: "rf")
+    ///
+    /// Returns:
+    ///     A tuple of (tree_name, index); ties resolve to the lowest index
+    ///
+    /// Raises:
+    ///     ValueError: If the metric is unknown
+    #[pyo3(signature = (metric="rf"))]
+    fn medoid(&self, py: Python<'_>, metric: &str) -> PyResult<(String, usize)> {
+        let index = with_metric!(metric, |metric| {
+            py.detach(|| {
+                in_thread_pool(self.threads, || {
+                    Ok(medoid_index(&compute_sym_matrix(
+                        &self.snapshots,
+                        metric,
+                        None,
+                    )))
+                })
+            })
+        })?;
+        Ok((self.names[index].clone(), index))
+    }
+}
+
+impl PyTreeSet {
+    /// Compare the stored snapshots under `metric` with the GIL released.
+    fn matrix<D: TreeDistance>(&self, py: Python<'_>, metric: &D) -> PyResult<Vec<Vec<D::Output>>> {
+        py.detach(|| {
+            in_thread_pool(self.threads, || {
+                Ok(pairwise_matrix(&self.snapshots, metric))
+            })
+        })
+    }
+}
+
+/// A single tree's partitions, for computing individual distances.
+///
+/// Snapshots keep the trivial (single-leaf) splits so every metric can use them.
+///
+/// Example:
+///     a = TreeSnapshot.from_newick("((A,B),(C,D));")
+///     b = TreeSnapshot.from_newick("((A,C),(B,D));")
+///     distance = rf(a, b)
+#[pyclass(name = "TreeSnapshot", frozen)]
+struct PyTreeSnapshot {
+    inner: TreeSnapshot,
+}
+
+#[pymethods]
+impl PyTreeSnapshot {
+    /// Snapshot a tree given as a Newick string (BEAST [&...] annotations are ignored).
+    ///
+    /// Raises:
+    ///     ValueError: If the string cannot be parsed or the tree is malformed
+    #[staticmethod]
+    fn from_newick(newick: &str) -> PyResult<Self> {
+        let tree = PhyloTree::from_newick(&strip_beast_annotations(newick))
+            .map_err(|e| PyValueError::new_err(format!("Failed to parse tree: {}", e)))?;
+        Self::from_tree(&tree)
+    }
+
+    /// Snapshot the tree at `index` of a BEAST/NEXUS tree file.
+    ///
+    /// Args:
+    ///     path: Path to the tree file
+    ///     index: Position of the tree in the file, counting from 0
+    ///     use_real_taxa: Use TRANSLATE block for taxon names when available (default: True)
+    ///
+    /// Raises:
+    ///     IndexError: If the file has no tree at `index`
+    ///     ValueError: If the file cannot be read or the tree is malformed
+    #[staticmethod]
+    #[pyo3(signature = (path, index, use_real_taxa=true))]
+    fn from_file(path: String, index: usize, use_real_taxa: bool) -> PyResult<Self> {
+        let read = ReadOptions {
+            use_real_taxa,
+            ..Default::default()
+        };
+        let (_, trees) = read_all_trees(&[path], &read)?;
+        let tree = trees.get(index).ok_or_else(|| {
+            PyIndexError::new_err(format!(
+                "Tree index {} is out of range for {} trees",
+                index,
+                trees.len()
+            ))
+        })?;
+        Self::from_tree(tree)
+    }
+
+    /// Number of taxa.
+    #[getter]
+    fn num_leaves(&self) -> usize {
+        self.inner.num_leaves
+    }
+
+    /// Whether the tree is rooted.
+    #[getter]
+    fn rooted(&self) -> bool {
+        self.inner.rooted
+    }
+
+    /// Number of non-trivial partitions (splits with at least two taxa on each side).
+    #[getter]
+    fn num_partitions(&self) -> usize {
+        let n = self.inner.num_leaves;
+        self.inner
+            .parts
+            .iter()
+            .filter(|part| (2..n.saturating_sub(1)).contains(&part.count_ones()))
+            .count()
+    }
+
+    /// Taxon names, sorted alphabetically.
+    #[getter]
+    fn leaf_names(&self) -> Vec<String> {
+        self.inner.leaf_names.clone()
+    }
+}
+
+impl PyTreeSnapshot {
+    fn from_tree(tree: &PhyloTree) -> PyResult<Self> {
+        let inner = TreeSnapshot::from_tree(tree, true)
+            .map_err(|e| PyValueError::new_err(format!("Failed to create tree snapshot: {}", e)))?;
+        Ok(Self { inner })
+    }
+}
+
+/// Robinson-Foulds distance between two snapshots.
+///
+/// Raises:
+///     ValueError: If the snapshots have different taxa
+#[pyfunction(name = "rf")]
+fn snapshot_rf(a: &PyTreeSnapshot, b: &PyTreeSnapshot) -> PyResult<usize> {
+    snapshot_distance(a, b, &RobinsonFoulds)
+}
+
+/// Weighted Robinson-Foulds distance between two snapshots.
+///
+/// Raises:
+///     ValueError: If the snapshots have different taxa
+#[pyfunction(name = "weighted_rf")]
+fn snapshot_weighted_rf(a: &PyTreeSnapshot, b: &PyTreeSnapshot) -> PyResult<f64> {
+    snapshot_distance(a, b, &WeightedRobinsonFoulds)
+}
+
+/// Kuhner-Felsenstein (branch score) distance between two snapshots.
+///
+/// Raises:
+///     ValueError: If the snapshots have different taxa
+#[pyfunction(name = "kf")]
+fn snapshot_kf(a: &PyTreeSnapshot, b: &PyTreeSnapshot) -> PyResult<f64> {
+    snapshot_distance(a, b, &KuhnerFelsenstein)
+}
+
+/// Weighted Robinson-Foulds distance between two snapshots, split by where it comes from.
+///
+/// Returns:
+///     A tuple of (shared_diff, unique_a, unique_b): the summed length differences of
+///     splits in both trees and the summed lengths of splits only in a or only in b;
+///     together they add up to weighted_rf(a, b)
+///
+/// Raises:
+///     ValueError: If the snapshots have different taxa
+#[pyfunction]
+fn weighted_rf_components(a: &PyTreeSnapshot, b: &PyTreeSnapshot) -> PyResult<(f64, f64, f64)> {
+    check_same_taxa(a, b)?;
+    let components = weighted_rf_components_from_snapshots(&a.inner, &b.inner);
+    Ok((
+        components.shared_diff,
+        components.unique_a,
+        components.unique_b,
+    ))
+}
+
+/// Compare two snapshots under `metric` after checking they share their taxa.
+fn snapshot_distance<D: TreeDistance>(
+    a: &PyTreeSnapshot,
+    b: &PyTreeSnapshot,
+    metric: &D,
+) -> PyResult<D::Output> {
+    check_same_taxa(a, b)?;
+    Ok(metric.distance(&a.inner, &b.inner))
+}
+
+/// Raise a `ValueError` unless both snapshots have the same taxa.
+fn check_same_taxa(a: &PyTreeSnapshot, b: &PyTreeSnapshot) -> PyResult<()> {
+    if a.inner.leaf_names != b.inner.leaf_names {
+        return Err(PyValueError::new_err(
+            "Snapshots have different leaf sets, cannot compare",
+        ));
+    }
+    Ok(())
+}
+
+/// Read, check and snapshot trees from multiple files, then build the matrix for `metric`.
+fn compute_pairwise<D: TreeDistance>(
     paths: &[String],
+    read: &ReadOptions,
+    taxa: &TaxaFilter,
+    run: &RunOptions,
+    metric: &D,
+) -> PyResult<NamedMatrix<D::Output>> {
+    let (tree_names, mut trees) = read_all_trees(paths, read)?;
+    Python::attach(|py| taxa.apply(py, &mut trees))?;
+    let tree_names = select_subset(tree_names, run.subset.as_deref())?;
+    let matrix = matrix_for_trees(trees, metric, run)?;
+
+    Ok((tree_names, matrix))
+}
+
+/// Burn-in and naming of the trees read from the files.
+#[derive(Default)]
+struct ReadOptions {
+    /// Number of trees to skip at the beginning of each file
     burnin_trees: usize,
+    /// Minimum STATE value to keep trees
     burnin_states: usize,
+    /// Use the TRANSLATE block for taxon names
     use_real_taxa: bool,
-) -> PyResult<(Vec<String>, Vec<PhyloTree>)> {
-    let mut all_tree_names = Vec::new();
-    let mut all_trees = Vec::new();
+    /// Tree name template, `None` for [`DEFAULT_NAME_TEMPLATE`]
+    name_template: Option<String>,
+}
 
-    for (file_idx, path) in paths.iter().enumerate() {
-        let (_taxons, named_trees) = read_beast_trees(
-            std::path::PathBuf::from(path),
-            burnin_trees,
-            burnin_states,
-            use_real_taxa,
-        );
+impl ReadOptions {
+    fn name_template(&self) -> &str {
+        self.name_template
+            .as_deref()
+            .unwrap_or(DEFAULT_NAME_TEMPLATE)
+    }
+}
 
-        if named_trees.is_empty() {
-            return Err(PyValueError::new_err(format!(
-                "No trees found in file '{}' after burnin removal",
-                path
-            )));
+/// Which tips are removed from the trees after reading them.
+struct TaxaFilter {
+    /// Prune all trees to the taxa they share
+    prune_to_common: bool,
+    /// Tips removed from every tree first; names found in no tree raise a `UserWarning`
+    exclude_taxa: Option<Vec<String>>,
+}
+
+impl TaxaFilter {
+    /// Remove the excluded tips, then prune to the common taxa if requested.
+    fn apply(&self, py: Python<'_>, trees: &mut [PhyloTree]) -> PyResult<()> {
+        if let Some(exclude) = self
+            .exclude_taxa
+            .as_deref()
+            .filter(|names| !names.is_empty())
+        {
+            let missing = exclude_taxa(trees, exclude)
+                .map_err(|e| PyValueError::new_err(format!("Failed to exclude taxa: {}", e)))?;
+            if !missing.is_empty() {
+                let message = format!("taxa not found in any tree: {}", missing.join(", "));
+                let message = CString::new(message)?;
+                PyErr::warn(py, &py.get_type::<PyUserWarning>(), &message, 1)?;
+            }
         }
+        if self.prune_to_common {
+            prune_to_common_taxa(trees).map_err(|e| {
+                PyValueError::new_err(format!("Failed to prune trees to common taxa: {}", e))
+            })?;
+        }
+        Ok(())
+    }
+}
 
-        // Add trees with file prefix in name
-        for (name, tree) in named_trees {
-            let full_name = format!("file{}_{}", file_idx, name);
-            all_tree_names.push(full_name);
-            all_trees.push(tree);
+/// Which trees a pairwise computation compares and how its parallel part runs.
+#[derive(Default)]
+struct RunOptions {
+    /// Number of worker threads, 0 uses all cores
+    threads: usize,
+    /// Python callable receiving `(pairs_done, pairs_total)` as rows of the matrix complete
+    progress_callback: Option<Py<PyAny>>,
+    /// Indices of the trees to compare, in matrix order; `None` compares all of them
+    subset: Option<Vec<usize>>,
+}
+
+/// Check, snapshot and compare `trees` (only the `run.subset` ones, if given) under
+/// `metric` with the GIL released.
+///
+/// The progress callback is called from the rayon workers, which must be able to take
+/// the GIL; the first exception it raises is returned once the matrix is done.
+fn matrix_for_trees<D: TreeDistance>(
+    trees: Vec<PhyloTree>,
+    metric: &D,
+    run: &RunOptions,
+) -> PyResult<Vec<Vec<D::Output>>> {
+    // Trees outside the subset are dropped before snapshotting, not just skipped in the matrix
+    let trees = select_subset(trees, run.subset.as_deref())?;
+    Python::attach(|py| {
+        py.detach(|| {
+            in_thread_pool(run.threads, || {
+                let snapshots = checked_snapshots(trees, metric.include_trivial())?;
+                // Python receives nested lists, so the triangle is expanded at the boundary
+                let Some(callback) = &run.progress_callback else {
+                    return Ok(compute_sym_matrix(&snapshots, metric, None).to_dense());
+                };
+
+                let failed: Mutex<Option<PyErr>> = Mutex::new(None);
+                let report = |done: usize, total: usize| {
+                    Python::attach(|py| {
+                        if let Err(e) = callback.bind(py).call1((done, total)) {
+                            failed
+                                .lock()
+                                .expect("progress lock poisoned")
+                                .get_or_insert(e);
+                        }
+                    })
+                };
+                let matrix = compute_sym_matrix(&snapshots, metric, Some(&report));
+
+                match failed.into_inner().expect("progress lock poisoned") {
+                    Some(e) => Err(e),
+                    None => Ok(matrix.to_dense()),
+                }
+            })
+        })
+    })
+}
+
+/// Parse the RF `mode` keyword; modes other than "auto" are only valid for RF.
+fn rf_mode(mode: &str, metric: &str) -> PyResult<RfMode> {
+    let mode = match mode {
+        "auto" => RfMode::Auto,
+        "rooted" => RfMode::ForceRooted,
+        "unrooted" => RfMode::ForceUnrooted,
+        other => {
+            return Err(PyValueError::new_err(format!(
+                "Unknown mode '{}', expected one of: auto, rooted, unrooted",
+                other
+            )));
         }
+    };
+    if mode != RfMode::Auto && metric != "rf" {
+        return Err(PyValueError::new_err(format!(
+            "mode only applies to the 'rf' metric, not '{}'",
+            metric
+        )));
     }
+    Ok(mode)
+}
+
+/// Keep the `subset` items of `items`, in the order given; `None` keeps all of them.
+fn select_subset<T>(items: Vec<T>, subset: Option<&[usize]>) -> PyResult<Vec<T>> {
+    let Some(indices) = subset else {
+        return Ok(items);
+    };
+    check_subset(indices, items.len())
+        .map_err(|e| PyValueError::new_err(format!("Invalid subset: {}", e)))?;
 
-    if all_trees.is_empty() {
+    let mut slots: Vec<Option<T>> = items.into_iter().map(Some).collect();
+    Ok(indices
+        .iter()
+        .map(|&idx| slots[idx].take().expect("subset indices are distinct"))
+        .collect())
+}
+
+/// Run `op` inside a dedicated rayon pool of `threads` workers (0 = all cores), so the
+/// parallel snapshot and matrix loops do not use the global pool.
+fn in_thread_pool<T, F>(threads: usize, op: F) -> PyResult<T>
+where
+    T: Send,
+    F: FnOnce() -> PyResult<T> + Send,
+{
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| PyRuntimeError::new_err(format!("Failed to build thread pool: {}", e)))?
+        .install(op)
+}
+
+/// Run the sanity checks and build one snapshot per tree, in parallel.
+fn checked_snapshots(trees: Vec<PhyloTree>, include_trivial: bool) -> PyResult<Vec<TreeSnapshot>> {
+    sanity_check_trees(&trees)?;
+
+    TreeSnapshot::from_trees(trees, include_trivial)
+        .map_err(|e| PyValueError::new_err(format!("Failed to create tree snapshot: {}", e)))
+}
+
+/// Widen every distance of a matrix to `f64`.
+fn widen<T: DistanceValue>(matrix: Vec<Vec<T>>) -> Vec<Vec<f64>> {
+    matrix
+        .into_iter()
+        .map(|row| row.into_iter().map(DistanceValue::to_f64).collect())
+        .collect()
+}
+
+/// Helper function to read trees from multiple files
+fn read_all_trees(paths: &[String], read: &ReadOptions) -> PyResult<(Vec<String>, Vec<PhyloTree>)> {
+    let (_taxons, named_trees, _) = read_beast_tree_files_with(
+        paths,
+        read.burnin_trees,
+        read.burnin_states,
+        read.use_real_taxa,
+        false,
+        read.name_template(),
+    )
+    .map_err(|(file_idx, e)| file_read_error(paths, file_idx, e))?;
+
+    if named_trees.is_empty() {
         return Err(PyValueError::new_err(
             "No trees found in any of the provided files",
         ));
     }
 
-    Ok((all_tree_names, all_trees))
+    Ok(named_trees.into_iter().unzip())
+}
+
+/// Turn the error of the file at `file_idx` into a Python `ValueError`.
+fn file_read_error(paths: &[String], file_idx: usize, e: ReadError) -> PyErr {
+    match e {
+        ReadError::NoTrees => PyValueError::new_err(format!(
+            "No trees found in file '{}' after burnin removal",
+            paths[file_idx]
+        )),
+        e => PyValueError::new_err(format!("Failed to read file '{}': {}", paths[file_idx], e)),
+    }
 }
 
 /// Perform sanity checks on trees
@@ -235,34 +1431,14 @@ fn sanity_check_trees(trees: &[PhyloTree]) -> PyResult<()> {
     }
 
     // Check that all trees have the same leaf set
-    let first_leaves: HashSet<String> = trees[0]
-        .get_leaves()
-        .iter()
-        .filter_map(|&id| trees[0].get(&id).ok()?.name.clone())
-        .collect();
-
-    let first_leaf_count = first_leaves.len();
+    let first_leaves = utils::leaf_names(&trees[0]);
 
     for (idx, tree) in trees.iter().enumerate().skip(1) {
-        let leaves: HashSet<String> = tree
-            .get_leaves()
-            .iter()
-            .filter_map(|&id| tree.get(&id).ok()?.name.clone())
-            .collect();
-
-        if leaves.len() != first_leaf_count {
-            return Err(PyValueError::new_err(format!(
-                "Tree {} has {} leaves, but tree 0 has {} leaves. All trees must have the same number of leaves.",
-                idx,
-                leaves.len(),
-                first_leaf_count
-            )));
-        }
-
-        if leaves != first_leaves {
+        let leaves = utils::leaf_names(tree);
+        if let Some(diff) = utils::describe_leaf_set_difference(&first_leaves, &leaves, idx) {
             return Err(PyValueError::new_err(format!(
-                "Tree {} has different leaf set than tree 0. All trees must have the same taxa.",
-                idx
+                "Tree {} has a different leaf set than tree 0 ({}). All trees must have the same taxa.",
+                idx, diff
             )));
         }
     }
@@ -273,8 +1449,25 @@ fn sanity_check_trees(trees: &[PhyloTree]) -> PyResult<()> {
 /// Python module definition
 #[pymodule]
 fn rust_python_tree_distances(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(pairwise_distances, m)?)?;
+    m.add_function(wrap_pyfunction!(pairwise_rf_from_newick, m)?)?;
+    m.add_function(wrap_pyfunction!(cross_distances, m)?)?;
     m.add_function(wrap_pyfunction!(pairwise_rf, m)?)?;
+    m.add_function(wrap_pyfunction!(rf_detailed, m)?)?;
     m.add_function(wrap_pyfunction!(pairwise_weighted_rf, m)?)?;
+    m.add_function(wrap_pyfunction!(pairwise_weighted_rf_norm, m)?)?;
     m.add_function(wrap_pyfunction!(pairwise_kf, m)?)?;
+    m.add_function(wrap_pyfunction!(pairwise_kf2, m)?)?;
+    m.add_function(wrap_pyfunction!(pairwise_norm_rf, m)?)?;
+    m.add_function(wrap_pyfunction!(read_trees_info, m)?)?;
+    m.add_function(wrap_pyfunction!(tree_diameters, m)?)?;
+    m.add_function(wrap_pyfunction!(bipartition_frequencies, m)?)?;
+    m.add_class::<PyBatchMonitor>()?;
+    m.add_class::<PyTreeSet>()?;
+    m.add_class::<PyTreeSnapshot>()?;
+    m.add_function(wrap_pyfunction!(snapshot_rf, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot_weighted_rf, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot_kf, m)?)?;
+    m.add_function(wrap_pyfunction!(weighted_rf_components, m)?)?;
     Ok(())
 }
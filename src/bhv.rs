@@ -0,0 +1,288 @@
+//! Billera–Holmes–Vogtmann (BHV) geodesic distance between weighted trees.
+//!
+//! # Overview
+//! In BHV tree space every topology is an orthant whose coordinates are the lengths of
+//! its splits, and orthants of trees sharing splits are glued along their common
+//! faces. The BHV distance is the length of the shortest path between two trees
+//! through this space. Unlike [`kf_from_snapshots`](crate::distances::kf_from_snapshots),
+//! which compares branch lengths split by split, the path has to pass through trees
+//! where incompatible splits shrink to zero before the other tree's splits grow.
+//!
+//! # Algorithm (GTP, Owen & Provan 2011)
+//! ```text
+//! 1. Splits in both trees             → (length_a - length_b)² each
+//!    Splits compatible with every     → length² each (the other tree has them
+//!    split of the other tree            at length 0)
+//! 2. Remaining splits A, B            → support sequence (A_1, B_1), ..., (A_k, B_k):
+//!    start with (A, B) and split a pair while a minimum weight vertex cover
+//!    C_1 ∪ C_2 of its incompatibility graph (weights |e|²/‖A_i‖², |f|²/‖B_i‖²)
+//!    weighs less than 1: (A_i, B_i) → (C_1, B_i \ C_2), (A_i \ C_1, C_2)
+//! 3. distance = √(step 1 + Σ (‖A_i‖ + ‖B_i‖)²)
+//! ```
+//!
+//! Along leg `i` the path drops the splits of `A_i` and then adds those of `B_i`;
+//! with a single leg it runs straight through the star tree (the cone path). Each
+//! vertex cover is a max-flow problem, so this suits trees of up to a few hundred
+//! taxa rather than large posterior samples.
+
+use crate::bitset::{Bitset, are_compatible};
+use crate::snapshot::TreeSnapshot;
+use std::collections::VecDeque;
+
+/// A split and its positive length.
+type Edge<'a> = (&'a Bitset, f64);
+
+/// Tolerance for the vertex cover weight and for residual flow capacities.
+const EPS: f64 = 1e-12;
+
+/// Compute the BHV geodesic distance from two pre-computed snapshots.
+///
+/// Both snapshots must share the same leaf set and leaf order, as for every metric in
+/// [`crate::distances`]. Trees are compared as unrooted, split by split; pendant edges
+/// only count if the snapshots include the trivial splits. Splits of length 0 (or
+/// without a length) are treated as absent, since they do not move the tree away from
+/// the tree without them.
+///
+/// Trees that share all their splits are in the same orthant, so the distance reduces
+/// to the Euclidean distance of the branch lengths, which is
+/// [`kf_from_snapshots`](crate::distances::kf_from_snapshots).
+pub fn bhv_distance_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    let edges_a = positive_edges(a);
+    let edges_b = positive_edges(b);
+
+    let mut squared = 0.0;
+    let mut only_a = Vec::new();
+    for &(split, length_a) in &edges_a {
+        match edges_b.iter().find(|(other, _)| *other == split) {
+            Some((_, length_b)) => squared += (length_a - length_b).powi(2),
+            None if compatible_with_all(split, &edges_b, a.num_leaves) => {
+                squared += length_a * length_a
+            }
+            None => only_a.push((split, length_a)),
+        }
+    }
+    let mut only_b = Vec::new();
+    for &(split, length_b) in &edges_b {
+        if edges_a.iter().any(|(other, _)| *other == split) {
+            continue;
+        }
+        if compatible_with_all(split, &edges_a, a.num_leaves) {
+            squared += length_b * length_b;
+        } else {
+            only_b.push((split, length_b));
+        }
+    }
+
+    let legs: f64 = support_sequence(only_a, only_b, a.num_leaves)
+        .iter()
+        .map(|(legs_a, legs_b)| (norm(legs_a) + norm(legs_b)).powi(2))
+        .sum();
+    (squared + legs).sqrt()
+}
+
+/// The splits of `snap` with a positive length.
+fn positive_edges(snap: &TreeSnapshot) -> Vec<Edge<'_>> {
+    snap.parts
+        .iter()
+        .filter_map(|part| {
+            let length = *snap.lengths.get(part)?;
+            (length > 0.0).then_some((part, length))
+        })
+        .collect()
+}
+
+fn compatible_with_all(split: &Bitset, edges: &[Edge<'_>], num_leaves: usize) -> bool {
+    edges
+        .iter()
+        .all(|(other, _)| are_compatible(split, other, num_leaves))
+}
+
+/// Euclidean norm of the lengths of `edges`.
+fn norm(edges: &[Edge<'_>]) -> f64 {
+    edges
+        .iter()
+        .map(|(_, length)| length * length)
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Refine `(a, b)` into the support sequence of the geodesic (step 2 of the
+/// [module docs](crate::bhv)). Every split of `a` must be incompatible with at least
+/// one split of `b` and vice versa.
+fn support_sequence<'a>(
+    a: Vec<Edge<'a>>,
+    b: Vec<Edge<'a>>,
+    num_leaves: usize,
+) -> Vec<(Vec<Edge<'a>>, Vec<Edge<'a>>)> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut legs = vec![(a, b)];
+    let mut i = 0;
+    while i < legs.len() {
+        let (legs_a, legs_b) = &legs[i];
+        let (cover_a, cover_b, weight) = min_vertex_cover(legs_a, legs_b, num_leaves);
+        if weight >= 1.0 - EPS {
+            i += 1;
+            continue;
+        }
+        // Drop the covered splits of A first, together with the B splits they block
+        let (legs_a, legs_b) = legs.remove(i);
+        let (first_a, second_a) = partition(legs_a, &cover_a);
+        let (second_b, first_b) = partition(legs_b, &cover_b);
+        legs.insert(i, (second_a, second_b));
+        legs.insert(i, (first_a, first_b));
+    }
+    legs
+}
+
+/// Split `edges` into those flagged in `keep` and the rest.
+fn partition<'a>(edges: Vec<Edge<'a>>, keep: &[bool]) -> (Vec<Edge<'a>>, Vec<Edge<'a>>) {
+    let (kept, rest): (Vec<_>, Vec<_>) = edges.into_iter().zip(keep).partition(|(_, keep)| **keep);
+    (
+        kept.into_iter().map(|(edge, _)| edge).collect(),
+        rest.into_iter().map(|(edge, _)| edge).collect(),
+    )
+}
+
+/// Minimum weight vertex cover of the bipartite graph joining incompatible splits of
+/// `a` and `b`, with weights `|e|²/‖a‖²` and `|f|²/‖b‖²`.
+///
+/// Returns the cover flags for `a` and `b` and the cover weight. The cover is read off
+/// a minimum cut: source → split of `a` (its weight) → incompatible split of `b`
+/// (unbounded) → sink (its weight).
+fn min_vertex_cover(
+    a: &[Edge<'_>],
+    b: &[Edge<'_>],
+    num_leaves: usize,
+) -> (Vec<bool>, Vec<bool>, f64) {
+    let (norm_a, norm_b) = (norm(a).powi(2), norm(b).powi(2));
+    let nodes = a.len() + b.len() + 2;
+    let (source, sink) = (0, nodes - 1);
+    let node_b = |j: usize| 1 + a.len() + j;
+
+    let mut capacity = vec![vec![0.0; nodes]; nodes];
+    for (i, (split_a, length_a)) in a.iter().enumerate() {
+        capacity[source][1 + i] = length_a * length_a / norm_a;
+        for (j, (split_b, _)) in b.iter().enumerate() {
+            if !are_compatible(split_a, split_b, num_leaves) {
+                capacity[1 + i][node_b(j)] = f64::INFINITY;
+            }
+        }
+    }
+    for (j, (_, length_b)) in b.iter().enumerate() {
+        capacity[node_b(j)][sink] = length_b * length_b / norm_b;
+    }
+
+    // Edmonds-Karp: augment along shortest paths until the sink is unreachable
+    let mut flow = 0.0;
+    loop {
+        let parent = residual_bfs(&capacity, source);
+        if parent[sink].is_none() {
+            let reachable: Vec<bool> = parent.iter().map(Option::is_some).collect();
+            let cover_a = (0..a.len()).map(|i| !reachable[1 + i]).collect();
+            let cover_b = (0..b.len()).map(|j| reachable[node_b(j)]).collect();
+            return (cover_a, cover_b, flow);
+        }
+        let mut bottleneck = f64::INFINITY;
+        let mut node = sink;
+        while let Some(prev) = parent[node].filter(|_| node != source) {
+            bottleneck = bottleneck.min(capacity[prev][node]);
+            node = prev;
+        }
+        let mut node = sink;
+        while let Some(prev) = parent[node].filter(|_| node != source) {
+            capacity[prev][node] -= bottleneck;
+            capacity[node][prev] += bottleneck;
+            node = prev;
+        }
+        flow += bottleneck;
+    }
+}
+
+/// Breadth-first search over the edges with residual capacity; `parent[v]` is the
+/// node `v` was reached from (the source is its own parent), `None` if unreachable.
+fn residual_bfs(capacity: &[Vec<f64>], source: usize) -> Vec<Option<usize>> {
+    let mut parent = vec![None; capacity.len()];
+    parent[source] = Some(source);
+    let mut queue = VecDeque::from([source]);
+    while let Some(node) = queue.pop_front() {
+        for (next, &residual) in capacity[node].iter().enumerate() {
+            if residual > EPS && parent[next].is_none() {
+                parent[next] = Some(node);
+                queue.push_back(next);
+            }
+        }
+    }
+    parent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distances::kf_from_snapshots;
+    use phylotree::tree::Tree as PhyloTree;
+
+    fn snapshot(newick: &str, include_trivial: bool) -> TreeSnapshot {
+        TreeSnapshot::from_tree(&PhyloTree::from_newick(newick).unwrap(), include_trivial).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_shared_splits_match_kf() {
+        // Same topology, different branch lengths: a single orthant
+        for include_trivial in [false, true] {
+            let a = snapshot("((A:1,B:2):0.5,C:1,(D:3,(E:1,F:1):2):1);", include_trivial);
+            let b = snapshot(
+                "((A:2,B:1):1.5,C:4,(D:1,(E:1,F:2):0.5):3);",
+                include_trivial,
+            );
+            assert_close(
+                bhv_distance_from_snapshots(&a, &b),
+                kf_from_snapshots(&a, &b),
+            );
+            assert_eq!(bhv_distance_from_snapshots(&a, &a), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_incompatible_splits_pass_through_star_tree() {
+        // One internal split each, incompatible: shrink one to 0, then grow the other
+        let a = snapshot("(A:1,B:1,(C:1,D:1):2);", false);
+        let b = snapshot("(A:1,C:1,(B:1,D:1):3);", false);
+        assert_close(bhv_distance_from_snapshots(&a, &b), 5.0);
+        assert!(kf_from_snapshots(&a, &b) < 5.0);
+    }
+
+    #[test]
+    fn test_geodesic_with_two_legs() {
+        // Splits AB and DE against AC and BE; DE is compatible with AC, so AB can be
+        // traded for AC before DE is traded for BE
+        let a = snapshot("((A:1,B:1):1,C:1,(D:1,E:1):4);", false);
+        let b = snapshot("((A:1,C:1):4,D:1,(B:1,E:1):1);", false);
+        assert_close(bhv_distance_from_snapshots(&a, &b), 50f64.sqrt());
+        assert_close(bhv_distance_from_snapshots(&b, &a), 50f64.sqrt());
+
+        // With the lengths swapped, the two legs would be out of order: cone path
+        let a = snapshot("((A:1,B:1):4,C:1,(D:1,E:1):1);", false);
+        let b = snapshot("((A:1,C:1):1,D:1,(B:1,E:1):4);", false);
+        assert_close(bhv_distance_from_snapshots(&a, &b), 2.0 * 17f64.sqrt());
+    }
+
+    #[test]
+    fn test_compatible_splits_count_as_shared() {
+        // {C,D} is missing from B but compatible with its only split
+        let a = snapshot("(A:1,B:1,((C:1,D:1):2,E:1):3);", false);
+        let b = snapshot("(A:1,B:1,(C:1,D:1,E:1):1);", false);
+        assert_close(
+            bhv_distance_from_snapshots(&a, &b),
+            kf_from_snapshots(&a, &b),
+        );
+    }
+}
@@ -9,6 +9,9 @@
 //! - Partition {A, C} → bitset `0b0101` (bits 0 and 2 set)
 //! - Partition {B, C, D} → bitset `0b1110` (bits 1, 2, 3 set)
 
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
 /// A compact bitset for representing which leaves belong to a partition.
 ///
 /// Internally stores bits in `Vec<u64>` words to support arbitrarily large trees.
@@ -17,10 +20,54 @@
 /// # Memory efficiency
 /// - Traditional HashSet<usize>: ~24 bytes per element + overhead
 /// - Bitset: 1 bit per possible element (8 bytes per 64 leaves)
-#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+///
+/// # Equality
+/// Comparison, ordering and hashing ignore trailing all-zero words, so the same leaf
+/// set stored with more words (e.g. from a snapshot with more leaves) still matches:
+/// `Bitset(vec![0b101]) == Bitset(vec![0b101, 0])`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bitset(pub Vec<u64>);
 
+impl PartialEq for Bitset {
+    fn eq(&self, other: &Self) -> bool {
+        self.significant_words() == other.significant_words()
+    }
+}
+
+impl Eq for Bitset {}
+
+impl Hash for Bitset {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.significant_words().hash(state);
+    }
+}
+
+impl PartialOrd for Bitset {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Bitset {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.significant_words().cmp(other.significant_words())
+    }
+}
+
 impl Bitset {
+    /// The words up to the last nonzero one; equality, ordering and hashing use these
+    /// so trailing zero words make no difference.
+    #[inline]
+    fn significant_words(&self) -> &[u64] {
+        let len = self
+            .0
+            .iter()
+            .rposition(|&w| w != 0)
+            .map_or(0, |last| last + 1);
+        &self.0[..len]
+    }
+
     /// Creates a new bitset with all bits set to 0.
     ///
     /// # Parameters
@@ -37,6 +84,25 @@ impl Bitset {
         Bitset(vec![0u64; words])
     }
 
+    /// Creates a bitset of `words` words with the given leaf indices set.
+    ///
+    /// # Panics
+    /// If an index does not fit in `words` words (`idx >= words * 64`).
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let bs = Bitset::from_indices([0, 2, 5], 1);
+    /// assert_eq!(bs.0, vec![0b00100101]);
+    /// ```
+    pub fn from_indices(indices: impl IntoIterator<Item = usize>, words: usize) -> Self {
+        let mut bs = Bitset::zeros(words);
+        for idx in indices {
+            bs.set(idx);
+        }
+        bs
+    }
+
     /// Sets the bit at the given index to 1.
     ///
     /// Marks a leaf as present in this partition.
@@ -59,6 +125,47 @@ impl Bitset {
         self.0[word] |= 1u64 << bit;
     }
 
+    /// Returns whether the bit at the given index is 1.
+    ///
+    /// Checks if a leaf is present in this partition. Indices past the last word
+    /// read as 0, as if the bitset were zero-extended.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut bs = Bitset::zeros(1);
+    /// bs.set(5);
+    /// assert!(bs.get(5));
+    /// assert!(!bs.get(0));
+    /// assert!(!bs.get(200));
+    /// ```
+    #[inline]
+    pub fn get(&self, idx: usize) -> bool {
+        let word = idx >> 6;
+        let bit = idx & 63;
+        self.0.get(word).is_some_and(|w| (w >> bit) & 1 == 1)
+    }
+
+    /// Sets the bit at the given index to 0.
+    ///
+    /// Removes a leaf from this partition; the counterpart of [`Bitset::set`].
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut bs = Bitset::zeros(1);
+    /// bs.set(0);
+    /// bs.set(5);
+    /// bs.clear(0);
+    /// assert_eq!(bs.0[0], 0b00100000);
+    /// ```
+    #[inline]
+    pub fn clear(&mut self, idx: usize) {
+        let word = idx >> 6;
+        let bit = idx & 63;
+        self.0[word] &= !(1u64 << bit);
+    }
+
     /// Performs bitwise OR with another bitset (union operation).
     ///
     /// Merges two leaf sets: `self` becomes `self ∪ other`
@@ -82,6 +189,177 @@ impl Bitset {
         }
     }
 
+    /// Performs bitwise AND with another bitset (intersection operation).
+    ///
+    /// Keeps only shared leaves: `self` becomes `self ∩ other`.
+    /// If `other` has fewer words it is treated as zero-extended, so the extra
+    /// words of `self` are cleared.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut left = Bitset::zeros(1);
+    /// left.set(0);
+    /// left.set(1);   // {0, 1}
+    ///
+    /// let mut right = Bitset::zeros(1);
+    /// right.set(1);
+    /// right.set(2);  // {1, 2}
+    ///
+    /// left.and_assign(&right);  // {0, 1} ∩ {1, 2} = {1}
+    /// assert_eq!(left.0[0], 0b10);
+    /// ```
+    #[inline]
+    pub fn and_assign(&mut self, other: &Bitset) {
+        for (k, a) in self.0.iter_mut().enumerate() {
+            *a &= other.0.get(k).copied().unwrap_or(0);
+        }
+    }
+
+    /// Counts the leaves shared by two bitsets: `|self ∩ other|`.
+    ///
+    /// ANDs each word pair and sums the population counts without allocating
+    /// a temporary bitset. Bitsets of differing word counts are compared as if
+    /// the shorter one were zero-extended.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut left = Bitset::zeros(1);
+    /// left.set(0);
+    /// left.set(1);
+    ///
+    /// let mut right = Bitset::zeros(1);
+    /// right.set(1);
+    /// right.set(2);
+    ///
+    /// assert_eq!(left.intersection_count(&right), 1);
+    /// ```
+    #[inline]
+    pub fn intersection_count(&self, other: &Bitset) -> usize {
+        // zip stops at the shorter bitset: the missing words are zero and add nothing
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a & b).count_ones() as usize)
+            .sum()
+    }
+
+    /// Counts the leaves in exactly one of two bitsets: `|self △ other|`.
+    ///
+    /// XORs each word pair and sums the population counts without allocating a
+    /// temporary bitset. Bitsets of differing word counts are compared as if the
+    /// shorter one were zero-extended, so the extra words count in full.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut left = Bitset::zeros(1);
+    /// left.set(0);
+    /// left.set(1);
+    ///
+    /// let mut right = Bitset::zeros(1);
+    /// right.set(1);
+    /// right.set(2);
+    ///
+    /// assert_eq!(left.symmetric_difference_count(&right), 2);  // {0, 2}
+    /// ```
+    #[inline]
+    pub fn symmetric_difference_count(&self, other: &Bitset) -> usize {
+        let (long, short) = if self.0.len() >= other.0.len() {
+            (&self.0, &other.0)
+        } else {
+            (&other.0, &self.0)
+        };
+        let shared: usize = long
+            .iter()
+            .zip(short)
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum();
+        let extra: usize = long[short.len()..]
+            .iter()
+            .map(|w| w.count_ones() as usize)
+            .sum();
+        shared + extra
+    }
+
+    /// Returns true when every leaf in `self` is also in `other`: `self ⊆ other`.
+    ///
+    /// Stops at the first word with a leaf missing from `other`. Bitsets of differing
+    /// word counts are compared as if the shorter one were zero-extended.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut small = Bitset::zeros(1);
+    /// small.set(1);
+    ///
+    /// let mut big = Bitset::zeros(1);
+    /// big.set(1);
+    /// big.set(2);
+    ///
+    /// assert!(small.is_subset(&big));
+    /// assert!(!big.is_subset(&small));
+    /// ```
+    #[inline]
+    pub fn is_subset(&self, other: &Bitset) -> bool {
+        self.0
+            .iter()
+            .enumerate()
+            .all(|(k, a)| a & !other.0.get(k).copied().unwrap_or(0) == 0)
+    }
+
+    /// Returns true when `self` and `other` share no leaves: `self ∩ other = ∅`.
+    ///
+    /// Stops at the first word with a shared leaf. Bitsets of differing word counts
+    /// are compared as if the shorter one were zero-extended.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut left = Bitset::zeros(1);
+    /// left.set(0);
+    ///
+    /// let mut right = Bitset::zeros(1);
+    /// right.set(1);
+    ///
+    /// assert!(left.is_disjoint(&right));
+    /// right.set(0);
+    /// assert!(!left.is_disjoint(&right));
+    /// ```
+    #[inline]
+    pub fn is_disjoint(&self, other: &Bitset) -> bool {
+        // zip stops at the shorter bitset: the missing words are zero and share nothing
+        self.0.iter().zip(&other.0).all(|(a, b)| a & b == 0)
+    }
+
+    /// Returns the other side of a partition: every leaf in `0..num_leaves` not in `self`.
+    ///
+    /// Flips each word, then clears the bits at indices `>= num_leaves` so the result
+    /// only holds real leaves. The result has as many words as `self`.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut bs = Bitset::zeros(1);
+    /// bs.set(0);
+    /// bs.set(1);  // {A, B} of 4 leaves
+    ///
+    /// assert_eq!(bs.complement(4).0[0], 0b1100);  // {C, D}
+    /// ```
+    pub fn complement(&self, num_leaves: usize) -> Bitset {
+        let mut complement = Bitset(self.0.iter().map(|w| !w).collect());
+        for (k, word) in complement.0.iter_mut().enumerate() {
+            let start = k * 64;
+            if start >= num_leaves {
+                *word = 0;
+            } else if num_leaves - start < 64 {
+                *word &= (1u64 << (num_leaves - start)) - 1;
+            }
+        }
+        complement
+    }
+
     /// Counts the number of set bits (population count).
     ///
     /// Returns how many leaves are in this partition.
@@ -95,10 +373,152 @@ impl Bitset {
     /// bs.set(5);
     /// assert_eq!(bs.count_ones(), 3);
     /// ```
+    ///
+    /// On x86-64 CPUs with the `popcnt` instruction, bitsets of at least
+    /// `POPCNT_MIN_WORDS` words use it even when the crate is built for the baseline
+    /// target, which otherwise counts each word in software; shorter bitsets are not
+    /// worth the feature check. See `benches/count_ones.rs`.
     #[inline]
     pub fn count_ones(&self) -> usize {
-        self.0.iter().map(|w| w.count_ones() as usize).sum()
+        #[cfg(target_arch = "x86_64")]
+        if self.0.len() >= POPCNT_MIN_WORDS && std::arch::is_x86_feature_detected!("popcnt") {
+            // SAFETY: the CPU supports popcnt, checked just above
+            return unsafe { popcnt_word_counts(&self.0) };
+        }
+        sum_word_counts(&self.0)
+    }
+
+    /// Iterates over the indices of set bits (the leaves in this partition) in ascending order.
+    ///
+    /// Each word is scanned with `trailing_zeros`, so the cost is proportional to the
+    /// number of set bits rather than to 64 per word.
+    ///
+    /// A `Bitset` does not know the number of leaves: bits past the last leaf (only
+    /// possible if they were set by hand) are yielded too. Use
+    /// [`iter_ones_below`](Self::iter_ones_below) to only get leaf indices.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut bs = Bitset::zeros(1);
+    /// bs.set(0);
+    /// bs.set(2);
+    /// bs.set(3);
+    /// assert_eq!(bs.iter_ones().collect::<Vec<_>>(), vec![0, 2, 3]);
+    /// ```
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(k, &word)| {
+            let mut remaining = word;
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+                let bit = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1; // clear lowest set bit
+                Some(k * 64 + bit)
+            })
+        })
     }
+
+    /// Iterates over the indices of set bits below `num_leaves` in ascending order.
+    ///
+    /// Like [`iter_ones`](Self::iter_ones), but stray bits at or above `num_leaves` are
+    /// masked off and words past the last leaf are never read.
+    ///
+    /// # Example
+    /// ```
+    /// # use rust_python_tree_distances::bitset::Bitset;
+    /// let mut bs = Bitset::zeros(1);
+    /// bs.set(1);
+    /// bs.set(5);
+    /// assert_eq!(bs.iter_ones_below(4).collect::<Vec<_>>(), vec![1]);
+    /// ```
+    pub fn iter_ones_below(&self, num_leaves: usize) -> impl Iterator<Item = usize> + '_ {
+        let words = num_leaves.div_ceil(64).min(self.0.len());
+        self.0[..words]
+            .iter()
+            .enumerate()
+            .flat_map(move |(k, &word)| {
+                let leaves_left = num_leaves - k * 64;
+                let mut remaining = if leaves_left < 64 {
+                    word & ((1u64 << leaves_left) - 1)
+                } else {
+                    word
+                };
+                std::iter::from_fn(move || {
+                    if remaining == 0 {
+                        return None;
+                    }
+                    let bit = remaining.trailing_zeros() as usize;
+                    remaining &= remaining - 1; // clear lowest set bit
+                    Some(k * 64 + bit)
+                })
+            })
+    }
+}
+
+/// Shortest bitset for which [`Bitset::count_ones`] checks for `popcnt`.
+#[cfg(target_arch = "x86_64")]
+const POPCNT_MIN_WORDS: usize = 4;
+
+/// Population count of `words`, one word at a time.
+#[inline(always)]
+fn sum_word_counts(words: &[u64]) -> usize {
+    words.iter().map(|w| w.count_ones() as usize).sum()
+}
+
+/// [`sum_word_counts`] compiled with the `popcnt` instruction enabled.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "popcnt")]
+fn popcnt_word_counts(words: &[u64]) -> usize {
+    sum_word_counts(words)
+}
+
+/// Collects leaf indices into a bitset with just enough words for the largest one.
+///
+/// # Example
+/// ```
+/// # use rust_python_tree_distances::bitset::Bitset;
+/// let bs: Bitset = [1, 64].into_iter().collect();
+/// assert_eq!(bs.0.len(), 2);
+/// assert_eq!(bs.iter_ones().collect::<Vec<_>>(), vec![1, 64]);
+/// ```
+impl FromIterator<usize> for Bitset {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let indices: Vec<usize> = iter.into_iter().collect();
+        let words = indices.iter().max().map_or(0, |&max| (max >> 6) + 1);
+        Bitset::from_indices(indices, words)
+    }
+}
+
+/// Returns true when two splits of `num_leaves` leaves can be in the same tree.
+///
+/// Classic four-way test: the splits `A | Ā` and `B | B̄` are compatible when at least
+/// one of `A ∩ B`, `A ∩ B̄`, `Ā ∩ B` and `Ā ∩ B̄` is empty, i.e. one side of a split
+/// is nested in (or disjoint from) a side of the other. Either side of a split may be
+/// given, so canonical and uncanonicalized bitsets can be mixed, and bitsets with fewer
+/// words than `num_leaves` needs are zero-extended.
+///
+/// # Example
+/// ```
+/// # use rust_python_tree_distances::bitset::{Bitset, are_compatible};
+/// let ab = Bitset::from_indices([0, 1], 1);
+/// let abc = Bitset::from_indices([0, 1, 2], 1);
+/// let bc = Bitset::from_indices([1, 2], 1);
+///
+/// assert!(are_compatible(&ab, &abc, 5));  // nested
+/// assert!(!are_compatible(&ab, &bc, 5));  // overlapping
+/// ```
+pub fn are_compatible(a: &Bitset, b: &Bitset, num_leaves: usize) -> bool {
+    // Widen first: `complement` keeps the word count, which may not reach `num_leaves`
+    let other_side = |split: &Bitset| {
+        let mut widened = Bitset::zeros(num_leaves.div_ceil(64).max(split.0.len()));
+        widened.or_assign(split);
+        widened.complement(num_leaves)
+    };
+    let not_a = other_side(a);
+    let not_b = other_side(b);
+    a.is_disjoint(b) || a.is_disjoint(&not_b) || not_a.is_disjoint(b) || not_a.is_disjoint(&not_b)
 }
 
 #[cfg(test)]
@@ -113,6 +533,43 @@ mod tests {
         assert_eq!(bs.0[0], 0b0101);
     }
 
+    #[test]
+    fn test_get_and_clear_across_word_boundary() {
+        let mut bs = Bitset::zeros(2);
+        bs.set(63);
+        bs.set(64);
+        assert!(bs.get(63) && bs.get(64));
+        assert!(!bs.get(62) && !bs.get(65));
+
+        bs.clear(63);
+        assert!(!bs.get(63));
+        assert!(bs.get(64));
+        assert_eq!(bs.0, vec![0, 1]);
+
+        bs.clear(64);
+        assert_eq!(bs, Bitset::zeros(2));
+        // Clearing an unset bit changes nothing
+        bs.clear(10);
+        assert_eq!(bs, Bitset::zeros(2));
+    }
+
+    #[test]
+    fn test_from_indices_and_collect() {
+        let built = Bitset::from_indices([0, 63, 64], 2);
+        let collected: Bitset = [64, 0, 63].into_iter().collect();
+        assert_eq!(built.count_ones(), 3);
+        assert_eq!(collected.count_ones(), 3);
+        assert_eq!(built, collected);
+        assert_eq!(collected.0.len(), 2);
+
+        // Extra words are allowed and stay zero
+        assert_eq!(
+            Bitset::from_indices([0, 63, 64], 4).0,
+            vec![1 | (1 << 63), 1, 0, 0]
+        );
+        assert_eq!(std::iter::empty().collect::<Bitset>().0.len(), 0);
+    }
+
     #[test]
     fn test_bitset_or() {
         let mut bs1 = Bitset::zeros(1);
@@ -127,6 +584,207 @@ mod tests {
         assert_eq!(bs1.0[0], 0b1111);
     }
 
+    #[test]
+    fn test_bitset_and() {
+        let mut bs1 = Bitset::zeros(1);
+        bs1.set(0);
+        bs1.set(1);
+        bs1.set(2);
+
+        let mut bs2 = Bitset::zeros(1);
+        bs2.set(1);
+        bs2.set(2);
+        bs2.set(3);
+
+        assert_eq!(bs1.intersection_count(&bs2), 2);
+        bs1.and_assign(&bs2);
+        assert_eq!(bs1.0[0], 0b0110);
+    }
+
+    #[test]
+    fn test_intersection_differing_word_counts() {
+        // 100 leaves need 2 words; the short bitset only covers the first 64
+        let mut long = Bitset::zeros(2);
+        long.set(3);
+        long.set(63);
+        long.set(64);
+        long.set(99);
+
+        let mut short = Bitset::zeros(1);
+        short.set(3);
+        short.set(10);
+        short.set(63);
+
+        assert_eq!(long.intersection_count(&short), 2);
+        assert_eq!(short.intersection_count(&long), 2);
+
+        let mut long_and = long.clone();
+        long_and.and_assign(&short);
+        assert_eq!(long_and.0, vec![(1u64 << 3) | (1u64 << 63), 0]);
+
+        let mut short_and = short.clone();
+        short_and.and_assign(&long);
+        assert_eq!(short_and.0, vec![(1u64 << 3) | (1u64 << 63)]);
+    }
+
+    #[test]
+    fn test_symmetric_difference_count() {
+        // {0, 1, 2} △ {1, 2, 3} = {0, 3}
+        let left = Bitset::from_indices([0, 1, 2], 1);
+        let right = Bitset::from_indices([1, 2, 3], 1);
+        assert_eq!(left.symmetric_difference_count(&right), 2);
+        assert_eq!(left.symmetric_difference_count(&left), 0);
+
+        // Spanning two words: {5, 63, 64, 100} △ {5, 64, 70} = {63, 70, 100}
+        let low_high = Bitset::from_indices([5, 63, 64, 100], 2);
+        let other = Bitset::from_indices([5, 64, 70], 2);
+        assert_eq!(low_high.symmetric_difference_count(&other), 3);
+
+        // The shorter bitset counts as zero-extended: {5, 10} △ {5, 63, 64, 100}
+        let short = Bitset::from_indices([5, 10], 1);
+        assert_eq!(short.symmetric_difference_count(&low_high), 4);
+        assert_eq!(low_high.symmetric_difference_count(&short), 4);
+    }
+
+    #[test]
+    fn test_subset_and_disjoint() {
+        let mut small = Bitset::zeros(1);
+        small.set(1);
+        small.set(2);
+        let mut big = small.clone();
+        big.set(5);
+
+        // Proper subset
+        assert!(small.is_subset(&big));
+        assert!(!big.is_subset(&small));
+        assert!(!small.is_disjoint(&big));
+
+        // Equal sets are subsets of each other
+        assert!(small.is_subset(&small.clone()));
+
+        // Disjoint sets spanning two words
+        let mut low = Bitset::zeros(2);
+        low.set(3);
+        low.set(63);
+        let mut high = Bitset::zeros(2);
+        high.set(64);
+        high.set(100);
+        assert!(low.is_disjoint(&high));
+        assert!(high.is_disjoint(&low));
+        assert!(!low.is_subset(&high));
+        high.set(63);
+        assert!(!low.is_disjoint(&high));
+
+        // The shorter bitset counts as zero-extended
+        let mut short = Bitset::zeros(1);
+        short.set(3);
+        assert!(short.is_subset(&low));
+        assert!(!low.is_subset(&short));
+        let mut only_high = Bitset::zeros(2);
+        only_high.set(64);
+        assert!(short.is_disjoint(&only_high));
+        assert!(!only_high.is_subset(&short));
+    }
+
+    #[test]
+    fn test_are_compatible() {
+        let split = |indices: &[usize]| indices.iter().copied().collect::<Bitset>();
+        let n = 6;
+        let ab = split(&[0, 1]);
+
+        // Nested: {A,B} ⊂ {A,B,C}
+        assert!(are_compatible(&ab, &split(&[0, 1, 2]), n));
+        // Disjoint: {A,B} and {D,E}
+        assert!(are_compatible(&ab, &split(&[3, 4]), n));
+        // Sides that cover all leaves between them: {A,B} and {C,D,E,F}
+        assert!(are_compatible(&ab, &split(&[2, 3, 4, 5]), n));
+        // A split is compatible with itself and with its other side
+        assert!(are_compatible(&ab, &ab, n));
+        assert!(are_compatible(&ab, &ab.complement(n), n));
+
+        // Incompatible: {A,B} and {B,C} overlap and neither contains the other
+        let bc = split(&[1, 2]);
+        assert!(!are_compatible(&ab, &bc, n));
+        assert!(!are_compatible(&bc, &ab, n));
+        // ...whichever sides are given
+        assert!(!are_compatible(&ab.complement(n), &bc, n));
+        assert!(!are_compatible(&ab, &bc.complement(n), n));
+
+        // Across a word boundary
+        let n = 130;
+        let low = split(&[0, 64]);
+        assert!(are_compatible(&low, &split(&[0, 64, 129]), n));
+        assert!(!are_compatible(&low, &split(&[64, 129]), n));
+    }
+
+    #[test]
+    fn test_complement() {
+        // 4 leaves: {A, B} ↔ {C, D}
+        let mut bs = Bitset::zeros(1);
+        bs.set(0);
+        bs.set(1);
+        assert_eq!(bs.complement(4).0, vec![0b1100]);
+        assert_eq!(bs.complement(4).complement(4), bs);
+
+        // 70 leaves span two words; bits 70..128 must stay clear
+        let mut bs = Bitset::zeros(2);
+        bs.set(0);
+        bs.set(63);
+        bs.set(69);
+        let complement = bs.complement(70);
+        assert_eq!(complement.count_ones(), 67);
+        assert_eq!(complement.0[0], !(1u64 | (1u64 << 63)));
+        assert_eq!(complement.0[1], (1u64 << 5) - 1);
+        assert!(complement.is_disjoint(&bs));
+        assert_eq!(complement.complement(70), bs);
+    }
+
+    #[test]
+    fn test_iter_ones() {
+        let mut bs = Bitset::zeros(2);
+        bs.set(0);
+        bs.set(63);
+        bs.set(64);
+        bs.set(127);
+
+        assert_eq!(bs.iter_ones().collect::<Vec<_>>(), vec![0, 63, 64, 127]);
+        assert_eq!(Bitset::zeros(2).iter_ones().count(), 0);
+
+        // Bits at or above num_leaves are stray high bits and must be cut off
+        let below = |num_leaves| bs.iter_ones_below(num_leaves).collect::<Vec<_>>();
+        assert_eq!(below(100), vec![0, 63, 64]);
+        assert_eq!(below(64), vec![0, 63]);
+        assert_eq!(below(63), vec![0]);
+        assert_eq!(below(128), vec![0, 63, 64, 127]);
+        assert_eq!(below(200), vec![0, 63, 64, 127]);
+        assert_eq!(below(0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_trailing_zero_words_are_ignored() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |bs: &Bitset| {
+            let mut hasher = DefaultHasher::new();
+            bs.hash(&mut hasher);
+            hasher.finish()
+        };
+        let short = Bitset(vec![0b101]);
+        let long = Bitset(vec![0b101, 0]);
+        assert_eq!(short, long);
+        assert_eq!(hash(&short), hash(&long));
+        assert_eq!(short.cmp(&long), Ordering::Equal);
+
+        let set: std::collections::HashSet<Bitset> = [long].into();
+        assert!(set.contains(&short));
+
+        // Nonzero words still count, wherever they are
+        assert_ne!(Bitset(vec![0b101]), Bitset(vec![0b101, 1]));
+        assert_ne!(Bitset(vec![0, 0b101]), Bitset(vec![0b101]));
+        assert_eq!(Bitset(Vec::new()), Bitset::zeros(3));
+        assert!(Bitset(vec![0b101, 0]) < Bitset(vec![0b101, 1]));
+    }
+
     #[test]
     fn test_count_ones() {
         let mut bs = Bitset::zeros(1);
@@ -136,6 +794,35 @@ mod tests {
         assert_eq!(bs.count_ones(), 3);
     }
 
+    #[test]
+    fn test_count_ones_matches_naive() {
+        // xorshift64: deterministic pseudo-random words without a rand dependency
+        let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        // Bit by bit, independent of both hardware and software popcounts
+        let naive = |bs: &Bitset| -> usize {
+            bs.0.iter()
+                .map(|w| (0..64).filter(|bit| w >> bit & 1 == 1).count())
+                .sum()
+        };
+
+        // Covers both sides of POPCNT_MIN_WORDS
+        for words in 1..=20 {
+            for _ in 0..10 {
+                let bs = Bitset((0..words).map(|_| next()).collect());
+                assert_eq!(bs.count_ones(), naive(&bs), "{words} words");
+                assert_eq!(sum_word_counts(&bs.0), naive(&bs), "{words} words");
+            }
+        }
+        assert_eq!(Bitset(vec![u64::MAX; 9]).count_ones(), 9 * 64);
+        assert_eq!(Bitset(Vec::new()).count_ones(), 0);
+    }
+
     /// Visual example: How bitsets represent a small tree
     ///
     /// ```text
@@ -0,0 +1,558 @@
+//! Summary trees built from the partitions of many snapshots.
+//!
+//! # Overview
+//! A [`ConsensusResult`] is a set of canonical bipartitions (plus branch lengths)
+//! that together describe a tree. Because every canonical bitset is the side that
+//! does NOT contain leaf 0, the partitions can be read as clusters of a tree rooted
+//! at leaf 0, which makes reconstruction a matter of nesting clusters by containment.
+//!
+//! ```text
+//! clusters: {C,D,E}, {D,E}      leaves: A B C D E
+//!
+//!        (root)
+//!      /   |    \
+//!     A    B   {C,D,E}
+//!              /    \
+//!             C    {D,E}
+//!                  /   \
+//!                 D     E
+//!
+//! Newick: (A,B,(C,(D,E)));
+//! ```
+
+use crate::bitset::{Bitset, are_compatible};
+use crate::distances::DistanceError;
+use crate::snapshot::TreeSnapshot;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// A tree described by its canonical partitions, ready to be written as Newick.
+#[derive(Debug, Clone)]
+pub struct ConsensusResult {
+    /// Taxon names in bit-index order (shared by all input snapshots)
+    pub leaf_names: Vec<String>,
+
+    /// Canonical partitions kept in the summary tree
+    pub parts: Vec<Bitset>,
+
+    /// Branch length for each kept partition
+    pub lengths: HashMap<Bitset, f64>,
+
+    /// Support (fraction of input trees) for each kept partition, written as node labels
+    pub support: HashMap<Bitset, f64>,
+}
+
+impl ConsensusResult {
+    /// Reconstruct a Newick string from the kept partitions.
+    ///
+    /// # Algorithm
+    /// 1. Sort clusters by size (largest first) so every container precedes its contents
+    /// 2. Attach each cluster to the smallest already-placed cluster containing it (or the root)
+    /// 3. Attach each leaf to the smallest cluster containing it (or the root)
+    /// 4. Write nodes recursively, ordering children by their lowest leaf index
+    ///
+    /// Single-leaf clusters are written as the leaf itself carrying the cluster's length.
+    /// Internal nodes with a `support` entry get it as their label, e.g. `(C,D)0.75:1`.
+    pub fn to_newick(&self) -> String {
+        let num_leaves = self.leaf_names.len();
+
+        let mut clusters: Vec<&Bitset> = self.parts.iter().collect();
+        clusters.sort_by(|a, b| b.count_ones().cmp(&a.count_ones()).then(a.cmp(b)));
+
+        // Node 0 is the root, node k + 1 is clusters[k]
+        let mut child_nodes: Vec<Vec<usize>> = vec![Vec::new(); clusters.len() + 1];
+        for (k, cluster) in clusters.iter().enumerate() {
+            let parent = (0..k)
+                .rev()
+                .find(|&p| cluster.is_subset(clusters[p]))
+                .map_or(0, |p| p + 1);
+            child_nodes[parent].push(k + 1);
+        }
+
+        let mut child_leaves: Vec<Vec<usize>> = vec![Vec::new(); clusters.len() + 1];
+        for leaf in 0..num_leaves {
+            let owner = (0..clusters.len())
+                .rev()
+                .find(|&k| clusters[k].get(leaf))
+                .map_or(0, |k| k + 1);
+            // A single-leaf cluster is written as the leaf itself
+            if owner == 0 || clusters[owner - 1].count_ones() > 1 {
+                child_leaves[owner].push(leaf);
+            }
+        }
+
+        let mut out = String::new();
+        self.write_node(0, &clusters, &child_nodes, &child_leaves, &mut out);
+        out.push(';');
+        out
+    }
+
+    fn write_node(
+        &self,
+        node: usize,
+        clusters: &[&Bitset],
+        child_nodes: &[Vec<usize>],
+        child_leaves: &[Vec<usize>],
+        out: &mut String,
+    ) {
+        let cluster = node.checked_sub(1).map(|k| clusters[k]);
+
+        if let Some(bitset) = cluster.filter(|c| c.count_ones() == 1) {
+            let leaf = first_bit(bitset).expect("single-leaf cluster has a bit set");
+            out.push_str(&self.leaf_names[leaf]);
+        } else {
+            // Children keyed by lowest leaf index for a deterministic layout
+            let mut children: Vec<(usize, Option<usize>)> = child_leaves[node]
+                .iter()
+                .map(|&leaf| (leaf, None))
+                .chain(child_nodes[node].iter().map(|&child| {
+                    let first = first_bit(clusters[child - 1]).unwrap_or(usize::MAX);
+                    (first, Some(child))
+                }))
+                .collect();
+            children.sort_unstable();
+
+            out.push('(');
+            for (k, (leaf, child)) in children.into_iter().enumerate() {
+                if k > 0 {
+                    out.push(',');
+                }
+                match child {
+                    Some(child) => self.write_node(child, clusters, child_nodes, child_leaves, out),
+                    None => out.push_str(&self.leaf_names[leaf]),
+                }
+            }
+            out.push(')');
+            if let Some(support) = cluster.and_then(|c| self.support.get(c)) {
+                out.push_str(&format!("{support}"));
+            }
+        }
+
+        if let Some(length) = cluster.and_then(|c| self.lengths.get(c)) {
+            out.push_str(&format!(":{length}"));
+        }
+    }
+}
+
+/// Average branch lengths over snapshots that all share one topology.
+///
+/// Every snapshot must have the same leaf names and the same partition set as the
+/// first one; each partition's length in the result is the mean of its lengths
+/// across all snapshots.
+///
+/// # Errors
+/// - [`DistanceError::Empty`] if `snaps` is empty
+/// - [`DistanceError::LeafSetMismatch`] if a snapshot has different taxa
+/// - [`DistanceError::TopologyMismatch`] if a snapshot has a different partition set
+pub fn mean_branch_length_tree(snaps: &[TreeSnapshot]) -> Result<ConsensusResult, DistanceError> {
+    let first = snaps.first().ok_or(DistanceError::Empty)?;
+
+    for (index, snap) in snaps.iter().enumerate().skip(1) {
+        if snap.leaf_names != first.leaf_names {
+            return Err(DistanceError::LeafSetMismatch { index });
+        }
+        if snap.parts != first.parts {
+            return Err(DistanceError::TopologyMismatch { index });
+        }
+    }
+
+    let n = snaps.len() as f64;
+    let lengths: HashMap<Bitset, f64> = first
+        .parts
+        .iter()
+        .map(|part| {
+            let total: f64 = snaps
+                .iter()
+                .map(|snap| snap.lengths.get(part).unwrap_or(&0.0))
+                .sum();
+            (part.clone(), total / n)
+        })
+        .collect();
+
+    let mut parts: Vec<Bitset> = first.parts.iter().cloned().collect();
+    parts.sort_unstable();
+
+    Ok(ConsensusResult {
+        leaf_names: first.leaf_names.clone(),
+        parts,
+        lengths,
+        support: HashMap::new(),
+    })
+}
+
+/// Strict consensus: the tree made of the partitions present in every snapshot.
+///
+/// Returns the Newick string (topology only, no branch lengths), or `None` if
+/// `snaps` is empty or the snapshots do not share the same leaves.
+///
+/// # Example
+/// ```text
+/// ((A,B),(C,(D,E)));   partitions {C,D,E}, {D,E}
+/// ((A,B),(E,(C,D)));   partitions {C,D,E}, {C,D}
+///
+/// strict consensus: {C,D,E} → (A,B,(C,D,E));
+/// ```
+pub fn strict_consensus(snaps: &[TreeSnapshot]) -> Option<String> {
+    let first = snaps.first()?;
+    if snaps
+        .iter()
+        .any(|snap| snap.num_leaves != first.num_leaves || snap.leaf_names != first.leaf_names)
+    {
+        return None;
+    }
+
+    let mut parts: Vec<Bitset> = partition_counts(snaps)
+        .into_iter()
+        .filter(|&(_, count)| count == snaps.len())
+        .map(|(part, _)| part.clone())
+        .collect();
+    parts.sort_unstable();
+
+    let result = ConsensusResult {
+        leaf_names: first.leaf_names.clone(),
+        parts,
+        lengths: HashMap::new(),
+        support: HashMap::new(),
+    };
+    Some(result.to_newick())
+}
+
+/// Fraction of snapshots containing each canonical bipartition, most frequent first.
+///
+/// Ties are ordered by bitset, so the table is reproducible. The snapshots must share
+/// the same leaf order; map a bipartition back to taxon names with
+/// [`TreeSnapshot::taxa`]. Returns an empty table if `snaps` is empty.
+///
+/// # Example
+/// ```text
+/// ((A,B),(C,(D,E)));   ((A,B),(C,(D,E)));   ((A,B),(E,(C,D)));
+///
+/// {C,D,E}: 1.0, {D,E}: 0.667, {C,D}: 0.333
+/// ```
+pub fn bipartition_frequencies(snaps: &[TreeSnapshot]) -> Vec<(Bitset, f64)> {
+    let n = snaps.len() as f64;
+    let mut frequencies: Vec<(Bitset, f64)> = partition_counts(snaps)
+        .into_iter()
+        .map(|(part, count)| (part.clone(), count as f64 / n))
+        .collect();
+    frequencies.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    frequencies
+}
+
+/// Majority-rule consensus with support values.
+///
+/// Every partition found in more than `threshold` of the snapshots is a candidate.
+/// Candidates are added greedily in descending frequency (ties by bitset order), and
+/// a candidate that conflicts with an already accepted partition is skipped, so the
+/// result is always a tree even for `threshold < 0.5`. Internal nodes are labelled
+/// with their frequency.
+///
+/// Returns `None` if `snaps` is empty or the snapshots do not share the same leaves.
+///
+/// # Example
+/// ```text
+/// 3 × ((A,B),(C,(D,E)))  +  1 × ((A,B),(E,(C,D)))   threshold 0.5
+///
+/// {C,D,E}: 4/4, {D,E}: 3/4, {C,D}: 1/4 (dropped)
+/// → (A,B,(C,(D,E)0.75)1);
+/// ```
+pub fn majority_rule_consensus(snaps: &[TreeSnapshot], threshold: f64) -> Option<String> {
+    let first = snaps.first()?;
+    if snaps
+        .iter()
+        .any(|snap| snap.num_leaves != first.num_leaves || snap.leaf_names != first.leaf_names)
+    {
+        return None;
+    }
+
+    let candidates = bipartition_frequencies(snaps)
+        .into_iter()
+        .take_while(|&(_, freq)| freq > threshold);
+
+    let mut parts: Vec<Bitset> = Vec::new();
+    let mut support = HashMap::new();
+    for (part, freq) in candidates {
+        if parts
+            .iter()
+            .all(|accepted| are_compatible(&part, accepted, first.num_leaves))
+        {
+            parts.push(part.clone());
+            support.insert(part, freq);
+        }
+    }
+    parts.sort_unstable();
+
+    let result = ConsensusResult {
+        leaf_names: first.leaf_names.clone(),
+        parts,
+        lengths: HashMap::new(),
+        support,
+    };
+    Some(result.to_newick())
+}
+
+/// Number of snapshots containing each partition, counted in parallel.
+///
+/// Every rayon task folds its snapshots into a local map and the maps are merged by
+/// summing. Integer counts do not depend on how the work was split, so callers that
+/// sort the result get the same order on every run.
+fn partition_counts(snaps: &[TreeSnapshot]) -> HashMap<&Bitset, usize> {
+    snaps
+        .par_iter()
+        .fold(HashMap::new, |mut counts: HashMap<&Bitset, usize>, snap| {
+            for part in &snap.parts {
+                *counts.entry(part).or_insert(0) += 1;
+            }
+            counts
+        })
+        .reduce(HashMap::new, |mut merged, counts| {
+            for (part, count) in counts {
+                *merged.entry(part).or_insert(0) += count;
+            }
+            merged
+        })
+}
+
+/// Lowest leaf index in `bitset`, if any.
+fn first_bit(bitset: &Bitset) -> Option<usize> {
+    bitset
+        .0
+        .iter()
+        .enumerate()
+        .find(|(_, w)| **w != 0)
+        .map(|(i, w)| i * 64 + w.trailing_zeros() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phylotree::tree::Tree as PhyloTree;
+
+    fn snapshot(newick: &str) -> TreeSnapshot {
+        TreeSnapshot::from_tree(&PhyloTree::from_newick(newick).unwrap(), false).unwrap()
+    }
+
+    /// Three samples of one topology that only differ in the {D,E} branch length:
+    ///
+    /// ```text
+    ///        root
+    ///       /    \
+    ///    {A,B}   {C,D,E}
+    ///            /    \
+    ///           C    {D,E}  ← length 1.0, 2.0, 3.0
+    /// ```
+    #[test]
+    fn test_mean_branch_length_tree() {
+        let snaps = [
+            snapshot("((A:1,B:1):0.5,(C:1,(D:1,E:1):1.0):0.5);"),
+            snapshot("((A:1,B:1):0.5,(C:1,(D:1,E:1):2.0):0.5);"),
+            snapshot("((A:1,B:1):0.5,(C:1,(D:1,E:1):3.0):0.5);"),
+        ];
+
+        let mean = mean_branch_length_tree(&snaps).unwrap();
+
+        let de = Bitset::from_indices([3, 4], 1);
+        let cde = Bitset::from_indices([2, 3, 4], 1);
+
+        assert_eq!(mean.parts.len(), 2);
+        assert_eq!(mean.lengths[&de], 2.0);
+        assert_eq!(mean.lengths[&cde], 0.5);
+        assert_eq!(mean.to_newick(), "(A,B,(C,(D,E):2):0.5);");
+    }
+
+    #[test]
+    fn test_strict_consensus() {
+        let snaps = [
+            snapshot("((A:1,B:1):1,(C:1,(D:1,E:1):1):1);"),
+            snapshot("((A:1,B:1):1,(E:1,(C:1,D:1):1):1);"),
+        ];
+        assert_eq!(strict_consensus(&snaps).unwrap(), "(A,B,(C,D,E));");
+
+        // Differing leaf counts have no consensus
+        let mixed = [snaps[0].clone(), snapshot("((A:1,B:1):1,(C:1,D:1):1);")];
+        assert_eq!(strict_consensus(&mixed), None);
+        assert_eq!(strict_consensus(&[]), None);
+    }
+
+    #[test]
+    fn test_strict_consensus_of_identical_trees_round_trips() {
+        let newick = "((A:1,B:1):1,(C:1,(D:1,(E:1,F:1):1):1):1);";
+        let snaps = [snapshot(newick), snapshot(newick), snapshot(newick)];
+
+        let consensus = snapshot(&strict_consensus(&snaps).unwrap());
+        assert_eq!(consensus.parts, snaps[0].parts);
+    }
+
+    #[test]
+    fn test_bipartition_frequencies() {
+        let snaps = [
+            snapshot("((A,B),(C,(D,E)));"),
+            snapshot("((A,B),(C,(D,E)));"),
+            snapshot("((A,B),(E,(C,D)));"),
+        ];
+
+        let table: Vec<(Vec<&str>, f64)> = bipartition_frequencies(&snaps)
+            .iter()
+            .map(|(part, freq)| (snaps[0].taxa(part), *freq))
+            .collect();
+        assert_eq!(
+            table,
+            [
+                (vec!["C", "D", "E"], 1.0),
+                (vec!["D", "E"], 2.0 / 3.0),
+                (vec!["C", "D"], 1.0 / 3.0),
+            ]
+        );
+        assert!(bipartition_frequencies(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_majority_rule_consensus() {
+        let de = "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);";
+        let cd = "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);";
+        let snaps = [snapshot(de), snapshot(de), snapshot(de), snapshot(cd)];
+
+        assert_eq!(
+            majority_rule_consensus(&snaps, 0.5).unwrap(),
+            "(A,B,(C,(D,E)0.75)1);"
+        );
+        assert_eq!(majority_rule_consensus(&[], 0.5), None);
+    }
+
+    #[test]
+    fn test_parallel_partition_counts() {
+        // 100 interleaved trees, enough for rayon to split the counting across tasks:
+        // 60 × {C,D,E} {D,E}, 30 × {C,D,E} {C,D} and 10 × {B,D,E} {D,E}
+        let snaps: Vec<TreeSnapshot> = (0..100)
+            .map(|i| match i % 10 {
+                0..=5 => snapshot("((A,B),(C,(D,E)));"),
+                6..=8 => snapshot("((A,B),(E,(C,D)));"),
+                _ => snapshot("((A,C),(B,(D,E)));"),
+            })
+            .collect();
+        let cde = Bitset::from_indices([2, 3, 4], 1);
+        let de = Bitset::from_indices([3, 4], 1);
+        let cd = Bitset::from_indices([2, 3], 1);
+        let bde = Bitset::from_indices([1, 3, 4], 1);
+        let expected = HashMap::from([(&cde, 90), (&de, 70), (&cd, 30), (&bde, 10)]);
+
+        for _ in 0..3 {
+            assert_eq!(partition_counts(&snaps), expected);
+            assert_eq!(
+                majority_rule_consensus(&snaps, 0.5).unwrap(),
+                "(A,B,(C,(D,E)0.7)0.9);"
+            );
+            assert_eq!(strict_consensus(&snaps).unwrap(), "(A,B,C,D,E);");
+        }
+    }
+
+    #[test]
+    fn test_parallel_consensus_matches_sequential() {
+        // 200 caterpillars over 8 taxa; the leaf order cycles, so splits recur unevenly
+        let taxa = ["A", "B", "C", "D", "E", "F", "G", "H"];
+        let snaps: Vec<TreeSnapshot> = (0..200)
+            .map(|i| {
+                let mut order = taxa.to_vec();
+                order.swap(i % 8, (i / 8) % 8);
+                order.swap(1 + i % 3, 5 + i % 3);
+                let mut newick = order[0].to_string();
+                for taxon in &order[1..] {
+                    newick = format!("({newick},{taxon})");
+                }
+                snapshot(&format!("{newick};"))
+            })
+            .collect();
+        let num_leaves = snaps[0].num_leaves;
+
+        // Sequential reference: plain counting loop, then the same greedy selection
+        let mut counts: HashMap<&Bitset, usize> = HashMap::new();
+        for snap in &snaps {
+            for part in &snap.parts {
+                *counts.entry(part).or_insert(0) += 1;
+            }
+        }
+        let mut candidates: Vec<(&Bitset, f64)> = counts
+            .iter()
+            .map(|(&part, &count)| (part, count as f64 / snaps.len() as f64))
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+
+        for threshold in [0.0, 0.25, 0.5] {
+            let mut parts: Vec<Bitset> = Vec::new();
+            let mut support = HashMap::new();
+            for &(part, freq) in candidates.iter().filter(|(_, freq)| *freq > threshold) {
+                if parts
+                    .iter()
+                    .all(|accepted| are_compatible(part, accepted, num_leaves))
+                {
+                    parts.push(part.clone());
+                    support.insert(part.clone(), freq);
+                }
+            }
+            parts.sort_unstable();
+            let sequential = ConsensusResult {
+                leaf_names: snaps[0].leaf_names.clone(),
+                parts,
+                lengths: HashMap::new(),
+                support,
+            }
+            .to_newick();
+
+            for _ in 0..3 {
+                assert_eq!(
+                    majority_rule_consensus(&snaps, threshold).unwrap(),
+                    sequential
+                );
+            }
+        }
+
+        let mut strict: Vec<Bitset> = counts
+            .iter()
+            .filter(|&(_, &count)| count == snaps.len())
+            .map(|(&part, _)| part.clone())
+            .collect();
+        strict.sort_unstable();
+        let expected = ConsensusResult {
+            leaf_names: snaps[0].leaf_names.clone(),
+            parts: strict,
+            lengths: HashMap::new(),
+            support: HashMap::new(),
+        }
+        .to_newick();
+        for _ in 0..3 {
+            assert_eq!(strict_consensus(&snaps).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_majority_rule_skips_conflicting_splits() {
+        // {D,E} (2/3) and {C,D} (1/3) share D: only the more frequent one is kept
+        let de = "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);";
+        let cd = "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);";
+        let snaps = [snapshot(de), snapshot(de), snapshot(cd)];
+
+        let newick = majority_rule_consensus(&snaps, 0.3).unwrap();
+        assert_eq!(newick, "(A,B,(C,(D,E)0.6666666666666666)1);");
+
+        let split_de = Bitset::from_indices([3, 4], 1);
+        let split_cd = Bitset::from_indices([2, 3], 1);
+        assert!(!are_compatible(&split_de, &split_cd, 5));
+    }
+
+    #[test]
+    fn test_mean_branch_length_tree_topology_mismatch() {
+        let snaps = [
+            snapshot("((A:1,B:1):0.5,(C:1,(D:1,E:1):1.0):0.5);"),
+            snapshot("((A:1,C:1):0.5,(B:1,(D:1,E:1):1.0):0.5);"),
+        ];
+
+        assert_eq!(
+            mean_branch_length_tree(&snaps).unwrap_err(),
+            DistanceError::TopologyMismatch { index: 1 }
+        );
+        assert_eq!(
+            mean_branch_length_tree(&[]).unwrap_err(),
+            DistanceError::Empty
+        );
+    }
+}
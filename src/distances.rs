@@ -1,23 +1,712 @@
 //! Tree distance metrics using bitset-based snapshots.
 //!
-//! This module implements three phylogenetic tree distance measures:
+//! This module implements the following phylogenetic tree distance measures:
 //!
 //! 1. **Robinson-Foulds (RF)**: Counts the number of bipartitions that differ
 //!    between two trees. Range: [0, 2n-6] where n is the number of leaves.
+//!    The generalized variant compares trees with partly overlapping taxa on the
+//!    splits restricted to their shared taxa.
 //!
 //! 2. **Weighted Robinson-Foulds**: Like RF but considers branch lengths.
 //!    For shared partitions, adds |length_a - length_b|.
 //!    For unique partitions, adds the full branch length.
 //!
+//!    A normalized variant first scales each tree's branch lengths to sum to 1,
+//!    for trees on different absolute time scales.
+//!
 //! 3. **Kuhner-Felsenstein (Branch Score)**: Similar to weighted RF but uses
-//!    squared differences: sqrt(Σ(length_a - length_b)²)
+//!    squared differences: sqrt(Σ(length_a - length_b)²). The squared variant
+//!    (without the square root) is additive over branches.
+//!    Both are cases of the Lp branch score (Σ|length_a - length_b|^p)^(1/p):
+//!    weighted RF is p = 1 and KF is p = 2.
+//!
+//! 4. **Matching split**: Pairs up the splits of both trees so that the total
+//!    number of leaves that must move is minimal (Hungarian algorithm).
+//!
+//! 5. **Path difference**: Euclidean distance between the leaf-to-leaf path
+//!    length (edge count) matrices of both trees.
+//!
+//! 6. **Triplet distance** (rooted trees only): Number of leaf triplets whose
+//!    rooted resolution differs.
+//!
+//! 7. **Jaccard-Robinson-Foulds**: Matches splits by the Jaccard similarity of
+//!    their sides raised to `k`, interpolating between matching split and RF.
+//!
+//! 8. **Clustering information** (Smith 2020): Entropy of both trees' splits minus
+//!    twice the mutual clustering information of an optimal split matching.
+//!
+//! 9. **Cluster affinity**: A similarity that credits every split with the overlap
+//!    of its best-matching split in the other tree, so near misses still score.
+//!
+//! Each metric is also available as a (mostly unit) struct implementing [`TreeDistance`],
+//! so matrix builders can be written once and accept any metric, including
+//! third-party ones.
 
-use crate::snapshot::TreeSnapshot;
+use crate::bitset::Bitset;
+use crate::matrix::{MatrixRows, SymMatrix};
+use crate::snapshot::{SnapshotError, TreeSnapshot};
+use crate::utils;
 use phylotree::tree::{Tree as PhyloTree, TreeError};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[cfg(test)]
 use itertools::Itertools;
 
+/// Errors for computations that need a set of mutually compatible snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DistanceError {
+    /// No snapshots were provided.
+    Empty,
+    /// The snapshot at `index` has a different leaf set than snapshot 0.
+    LeafSetMismatch { index: usize },
+    /// The snapshot at `index` has a different topology than snapshot 0.
+    TopologyMismatch { index: usize },
+    /// A rooted-only metric was given an unrooted tree.
+    Unrooted,
+    /// A selected tree index is not below the number of trees.
+    IndexOutOfRange { index: usize, len: usize },
+    /// A tree index was selected more than once.
+    DuplicateIndex { index: usize },
+}
+
+impl fmt::Display for DistanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DistanceError::Empty => write!(f, "no trees were provided"),
+            DistanceError::LeafSetMismatch { index } => {
+                write!(f, "tree {index} has a different leaf set than tree 0")
+            }
+            DistanceError::TopologyMismatch { index } => {
+                write!(f, "tree {index} has a different topology than tree 0")
+            }
+            DistanceError::Unrooted => write!(f, "metric requires rooted trees"),
+            DistanceError::IndexOutOfRange { index, len } => {
+                write!(f, "tree index {index} is out of range for {len} trees")
+            }
+            DistanceError::DuplicateIndex { index } => {
+                write!(f, "tree index {index} is selected more than once")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DistanceError {}
+
+/// A distance value that can be written to a matrix and widened to `f64`.
+pub trait DistanceValue: Copy + Default + Send + Sync + fmt::Display {
+    /// Convert to `f64`, e.g. for averaging.
+    fn to_f64(self) -> f64;
+}
+
+impl DistanceValue for usize {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl DistanceValue for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// A distance between two tree snapshots.
+///
+/// Implementors are usually zero-sized unit structs; see [`RobinsonFoulds`],
+/// [`WeightedRobinsonFoulds`], [`KuhnerFelsenstein`], [`NormalizedRobinsonFoulds`],
+/// [`MatchingSplit`], [`PathDifference`] and [`JaccardRobinsonFoulds`].
+///
+/// # Example
+/// ```
+/// # use rust_python_tree_distances::distances::{RobinsonFoulds, TreeDistance, pairwise_matrix};
+/// # use rust_python_tree_distances::snapshot::TreeSnapshot;
+/// # use phylotree::tree::Tree;
+/// let metric = RobinsonFoulds;
+/// let snaps: Vec<TreeSnapshot> = ["(A,B,(C,D));", "(A,C,(B,D));"]
+///     .iter()
+///     .map(|nwk| TreeSnapshot::from_tree(&Tree::from_newick(nwk).unwrap(), metric.include_trivial()))
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+///
+/// assert_eq!(pairwise_matrix(&snaps, &metric), vec![vec![0, 2], vec![2, 0]]);
+/// ```
+pub trait TreeDistance: Sync {
+    /// Type of a single distance (`usize` for counts, `f64` for lengths).
+    type Output: DistanceValue;
+
+    /// Short label used in progress messages.
+    fn name(&self) -> &'static str;
+
+    /// Compute the distance between two snapshots.
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> Self::Output;
+
+    /// Whether snapshots must be built with trivial (leaf) partitions.
+    ///
+    /// Metrics that compare branch lengths need the pendant branches too.
+    fn include_trivial(&self) -> bool {
+        false
+    }
+
+    /// [`TreeDistance::distance`] widened to `f64`.
+    fn distance_f64(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        self.distance(a, b).to_f64()
+    }
+}
+
+/// Robinson-Foulds distance, see [`rf_from_snapshots`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RobinsonFoulds;
+
+impl TreeDistance for RobinsonFoulds {
+    type Output = usize;
+
+    fn name(&self) -> &'static str {
+        "RF"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> usize {
+        rf_from_snapshots(a, b)
+    }
+}
+
+/// Robinson-Foulds distance with a forced rootedness, see [`rf_from_snapshots_mode`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RobinsonFouldsWithMode {
+    pub mode: RfMode,
+}
+
+impl TreeDistance for RobinsonFouldsWithMode {
+    type Output = usize;
+
+    fn name(&self) -> &'static str {
+        "RF"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> usize {
+        rf_from_snapshots_mode(a, b, self.mode)
+    }
+}
+
+/// Robinson-Foulds distance scaled to `[0, 1]`, see [`normalized_rf_from_snapshots`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NormalizedRobinsonFoulds;
+
+impl TreeDistance for NormalizedRobinsonFoulds {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "Normalized RF"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        normalized_rf_from_snapshots(a, b)
+    }
+}
+
+/// Weighted Robinson-Foulds distance, see [`weighted_rf_from_snapshots`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WeightedRobinsonFoulds;
+
+impl TreeDistance for WeightedRobinsonFoulds {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "Weighted"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        weighted_rf_from_snapshots(a, b)
+    }
+
+    fn include_trivial(&self) -> bool {
+        true
+    }
+}
+
+/// Weighted Robinson-Foulds distance on normalized branch lengths, see
+/// [`weighted_rf_normalized_from_snapshots`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NormalizedWeightedRobinsonFoulds;
+
+impl TreeDistance for NormalizedWeightedRobinsonFoulds {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "Normalized weighted"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        weighted_rf_normalized_from_snapshots(a, b)
+    }
+
+    fn include_trivial(&self) -> bool {
+        true
+    }
+}
+
+/// Kuhner-Felsenstein (branch score) distance, see [`kf_from_snapshots`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KuhnerFelsenstein;
+
+impl TreeDistance for KuhnerFelsenstein {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "KF"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        kf_from_snapshots(a, b)
+    }
+
+    fn include_trivial(&self) -> bool {
+        true
+    }
+}
+
+/// Squared Kuhner-Felsenstein distance, see [`kf_squared_from_snapshots`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KuhnerFelsensteinSquared;
+
+impl TreeDistance for KuhnerFelsensteinSquared {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "KF2"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        kf_squared_from_snapshots(a, b)
+    }
+
+    fn include_trivial(&self) -> bool {
+        true
+    }
+}
+
+/// Matching-split distance, see [`matching_split_from_snapshots`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchingSplit;
+
+impl TreeDistance for MatchingSplit {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "Matching split"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        matching_split_from_snapshots(a, b)
+    }
+}
+
+/// Path-difference distance, see [`path_difference_from_snapshots`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PathDifference;
+
+impl TreeDistance for PathDifference {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "Path difference"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        path_difference_from_snapshots(a, b)
+    }
+}
+
+/// Clustering information distance, see [`clustering_info_distance_from_snapshots`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClusteringInfo;
+
+impl TreeDistance for ClusteringInfo {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "Clustering information"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        clustering_info_distance_from_snapshots(a, b)
+    }
+}
+
+/// Generalized Jaccard-Robinson-Foulds distance with exponent `k`, see
+/// [`jaccard_rf_from_snapshots`].
+#[derive(Debug, Clone, Copy)]
+pub struct JaccardRobinsonFoulds {
+    pub k: f64,
+}
+
+impl Default for JaccardRobinsonFoulds {
+    fn default() -> Self {
+        Self { k: 1.0 }
+    }
+}
+
+impl TreeDistance for JaccardRobinsonFoulds {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "JRF"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        jaccard_rf_from_snapshots(a, b, self.k)
+    }
+}
+
+/// Branch score under the `Lp` norm with exponent `p`, see
+/// [`lp_branch_score_from_snapshots`].
+///
+/// With `include_root_edges`, the edges below the root of two rooted trees are
+/// compared one by one, see [`lp_branch_score_with_root_edges`]; `p = 1` then gives
+/// rooted weighted RF and `p = 2` rooted KF.
+#[derive(Debug, Clone, Copy)]
+pub struct LpBranchScore {
+    pub p: f64,
+    pub include_root_edges: bool,
+}
+
+impl Default for LpBranchScore {
+    fn default() -> Self {
+        Self {
+            p: 2.0,
+            include_root_edges: false,
+        }
+    }
+}
+
+impl TreeDistance for LpBranchScore {
+    type Output = f64;
+
+    fn name(&self) -> &'static str {
+        "Lp branch score"
+    }
+
+    fn distance(&self, a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        if self.include_root_edges {
+            lp_branch_score_with_root_edges(a, b, self.p)
+        } else {
+            lp_branch_score_from_snapshots(a, b, self.p)
+        }
+    }
+
+    fn include_trivial(&self) -> bool {
+        true
+    }
+}
+
+/// Progress callback receiving `(pairs_done, pairs_total)`.
+pub type Progress<'a> = &'a (dyn Fn(usize, usize) + Sync);
+
+/// Compute the full symmetric distance matrix of `snaps` under `metric`.
+///
+/// Only the upper triangle (i < j) is computed, in parallel, and mirrored.
+/// The diagonal is `Output::default()` (zero).
+pub fn pairwise_matrix<D: TreeDistance>(snaps: &[TreeSnapshot], metric: &D) -> Vec<Vec<D::Output>> {
+    compute_matrix(snaps, metric, None)
+}
+
+/// [`pairwise_matrix`] with an optional progress callback, see [`compute_pairs`].
+pub fn compute_matrix<D: TreeDistance>(
+    snaps: &[TreeSnapshot],
+    metric: &D,
+    progress: Option<Progress<'_>>,
+) -> Vec<Vec<D::Output>> {
+    compute_sym_matrix(snaps, metric, progress).to_dense()
+}
+
+/// Lazily compute the upper-triangle `(i, j, distance)` pairs, `i < j`, in condensed
+/// order.
+///
+/// Each row is computed in parallel when the iterator reaches it and yielded pair by
+/// pair, so memory stays at one row of distances however many trees there are.
+/// Callers can write every pair as it arrives instead of materializing the matrix.
+///
+/// # Example
+/// ```
+/// # use phylotree::tree::Tree;
+/// # use rust_python_tree_distances::distances::{RobinsonFoulds, pairwise_iter};
+/// # use rust_python_tree_distances::snapshot::TreeSnapshot;
+/// let snaps: Vec<TreeSnapshot> = ["(A,B,(C,D));", "(A,C,(B,D));", "(A,D,(B,C));"]
+///     .iter()
+///     .map(|nwk| TreeSnapshot::from_tree(&Tree::from_newick(nwk).unwrap(), false).unwrap())
+///     .collect();
+/// for (i, j, dist) in pairwise_iter(&snaps, &RobinsonFoulds) {
+///     assert_eq!(dist, 2, "trees {i} and {j}");
+/// }
+/// ```
+pub fn pairwise_iter<'a, D: TreeDistance>(
+    snaps: &'a [TreeSnapshot],
+    metric: &'a D,
+) -> impl Iterator<Item = (usize, usize, D::Output)> + 'a {
+    let n = snaps.len();
+    (0..n).flat_map(move |i| {
+        let row: Vec<D::Output> = (i + 1..n)
+            .into_par_iter()
+            .map(|j| metric.distance(&snaps[i], &snaps[j]))
+            .collect();
+        row.into_iter()
+            .enumerate()
+            .map(move |(offset, dist)| (i, i + 1 + offset, dist))
+    })
+}
+
+/// [`compute_matrix`] storing only the upper triangle, see [`SymMatrix`].
+pub fn compute_sym_matrix<D: TreeDistance>(
+    snaps: &[TreeSnapshot],
+    metric: &D,
+    progress: Option<Progress<'_>>,
+) -> SymMatrix<D::Output> {
+    // compute_pairs yields (i, j) in condensed order already
+    let condensed = compute_pairs(snaps, metric, progress)
+        .into_iter()
+        .map(|(_, _, dist)| dist)
+        .collect();
+    SymMatrix::from_condensed(snaps.len(), condensed)
+}
+
+/// Compute the distance matrix among `snaps[indices[0]]`, `snaps[indices[1]]`, ...
+///
+/// Row `k` of the result belongs to `snaps[indices[k]]`, so it equals the
+/// corresponding entries of [`compute_sym_matrix`] over all of `snaps` without
+/// computing the pairs outside the selection.
+///
+/// # Errors
+/// - [`DistanceError::IndexOutOfRange`] if an index is not below `snaps.len()`
+/// - [`DistanceError::DuplicateIndex`] if an index appears more than once
+pub fn pairwise_subset<D: TreeDistance>(
+    snaps: &[TreeSnapshot],
+    indices: &[usize],
+    metric: &D,
+) -> Result<SymMatrix<D::Output>, DistanceError> {
+    check_subset(indices, snaps.len())?;
+
+    let k = indices.len();
+    let condensed = (0..k)
+        .into_par_iter()
+        .flat_map_iter(|a| {
+            let first = &snaps[indices[a]];
+            indices[a + 1..]
+                .iter()
+                .map(move |&idx| metric.distance(first, &snaps[idx]))
+        })
+        .collect();
+    Ok(SymMatrix::from_condensed(k, condensed))
+}
+
+/// Check that `indices` select distinct trees out of `len`.
+pub(crate) fn check_subset(indices: &[usize], len: usize) -> Result<(), DistanceError> {
+    let mut seen = vec![false; len];
+    for &index in indices {
+        if index >= len {
+            return Err(DistanceError::IndexOutOfRange { index, len });
+        }
+        if std::mem::replace(&mut seen[index], true) {
+            return Err(DistanceError::DuplicateIndex { index });
+        }
+    }
+    Ok(())
+}
+
+/// Grow `existing`, the matrix of `old_snaps`, to also cover `new_snaps`.
+///
+/// Only the new×old and new×new pairs are computed (in parallel); the existing
+/// entries are kept. Afterwards row `old_snaps.len() + k` belongs to `new_snaps[k]`,
+/// so the result equals [`compute_sym_matrix`] over the concatenated snapshots. This
+/// suits online monitoring, where trees keep arriving and a full recomputation would
+/// redo every old pair.
+///
+/// # Panics
+/// If `existing` does not have one row per old snapshot.
+pub fn extend_matrix<D: TreeDistance>(
+    existing: &mut SymMatrix<D::Output>,
+    old_snaps: &[TreeSnapshot],
+    new_snaps: &[TreeSnapshot],
+    metric: &D,
+) {
+    let n_old = old_snaps.len();
+    assert_eq!(
+        existing.len(),
+        n_old,
+        "matrix rows must match the old snapshots"
+    );
+    let snap = |idx: usize| {
+        if idx < n_old {
+            &old_snaps[idx]
+        } else {
+            &new_snaps[idx - n_old]
+        }
+    };
+
+    let pairs: Vec<(usize, usize, D::Output)> = (0..new_snaps.len())
+        .into_par_iter()
+        .flat_map_iter(|k| {
+            let j = n_old + k;
+            (0..j).map(move |i| (i, j, metric.distance(snap(i), &new_snaps[k])))
+        })
+        .collect();
+
+    existing.grow(n_old + new_snaps.len());
+    for (i, j, dist) in pairs {
+        existing.set(i, j, dist);
+    }
+}
+
+/// Group identical snapshots so distances only need computing between one
+/// representative per group.
+///
+/// Snapshots are bucketed by [`TreeSnapshot::topology_hash`] and confirmed with
+/// [`TreeSnapshot::same_topology`]. With `compare_lengths`, the branch lengths must
+/// match exactly as well, which weighted metrics need.
+///
+/// Returns `(representatives, group_of)`: the index of the first snapshot of each
+/// group, in input order, and for every snapshot the position of its group in
+/// `representatives`.
+pub fn dedup_snapshots(snaps: &[TreeSnapshot], compare_lengths: bool) -> (Vec<usize>, Vec<usize>) {
+    let mut representatives: Vec<usize> = Vec::new();
+    let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut group_of = Vec::with_capacity(snaps.len());
+
+    for (idx, snap) in snaps.iter().enumerate() {
+        let bucket = buckets.entry(snap.topology_hash()).or_default();
+        let existing = bucket.iter().copied().find(|&group| {
+            let rep = &snaps[representatives[group]];
+            rep.same_topology(snap) && (!compare_lengths || rep.lengths == snap.lengths)
+        });
+        let group = existing.unwrap_or_else(|| {
+            representatives.push(idx);
+            bucket.push(representatives.len() - 1);
+            representatives.len() - 1
+        });
+        group_of.push(group);
+    }
+
+    (representatives, group_of)
+}
+
+/// Expand a matrix between group representatives back to every snapshot:
+/// entry `(i, j)` is entry `(group_of[i], group_of[j])` of `unique`, see
+/// [`dedup_snapshots`]. Trees of the same group read the diagonal of `unique`.
+///
+/// `unique` can be a nested `Vec` or a [`SymMatrix`]; each of its rows is read once,
+/// so only one row is held besides the result.
+pub fn expand_matrix<M>(unique: &M, group_of: &[usize]) -> SymMatrix<M::Item>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: Default,
+{
+    let mut members: Vec<Vec<usize>> = vec![Vec::new(); unique.size()];
+    for (i, &group) in group_of.iter().enumerate() {
+        members[group].push(i);
+    }
+
+    let mut mat = SymMatrix::new(group_of.len());
+    for (group, rows) in members
+        .iter()
+        .enumerate()
+        .filter(|(_, rows)| !rows.is_empty())
+    {
+        let row: Vec<M::Item> = unique.row(group).collect();
+        for &i in rows {
+            for (j, &other) in group_of.iter().enumerate().skip(i + 1) {
+                mat.set(i, j, row[other]);
+            }
+        }
+    }
+    mat
+}
+
+/// Compute the upper triangle of the distance matrix as `(i, j, distance)` with `i < j`.
+///
+/// Pairs are computed in parallel and returned in row-major order, (0, 1), (0, 2), …,
+/// (1, 2), …, which is the condensed order of SciPy's `squareform`.
+pub fn pairwise_pairs<D: TreeDistance>(
+    snaps: &[TreeSnapshot],
+    metric: &D,
+) -> Vec<(usize, usize, D::Output)> {
+    compute_pairs(snaps, metric, None)
+}
+
+/// [`pairwise_pairs`] with an optional progress callback.
+///
+/// `progress` is called from the worker threads each time a row of the upper
+/// triangle completes, with the number of pairs done so far (tracked by an atomic
+/// counter) and the total. Rows finish out of order, so calls are not strictly
+/// increasing. With `None` the plain pair loop runs, without any counter.
+pub fn compute_pairs<D: TreeDistance>(
+    snaps: &[TreeSnapshot],
+    metric: &D,
+    progress: Option<Progress<'_>>,
+) -> Vec<(usize, usize, D::Output)> {
+    let n = snaps.len();
+    let Some(report) = progress else {
+        return (0..n)
+            .into_par_iter()
+            .flat_map_iter(|i| (i + 1..n).map(move |j| (i, j)))
+            .map(|(i, j)| (i, j, metric.distance(&snaps[i], &snaps[j])))
+            .collect();
+    };
+
+    let total = n * n.saturating_sub(1) / 2;
+    let done = AtomicUsize::new(0);
+    (0..n)
+        .into_par_iter()
+        .flat_map_iter(|i| {
+            let row: Vec<(usize, usize, D::Output)> = (i + 1..n)
+                .map(|j| (i, j, metric.distance(&snaps[i], &snaps[j])))
+                .collect();
+            if !row.is_empty() {
+                let now = done.fetch_add(row.len(), Ordering::Relaxed) + row.len();
+                report(now, total);
+            }
+            row
+        })
+        .collect()
+}
+
+/// Compute the distance from every snapshot in `others` to a single `reference`.
+///
+/// This is the O(n) alternative to [`pairwise_matrix`] when only drift against a
+/// fixed tree (e.g. the MCC tree) matters. Entries are computed in parallel and
+/// follow the order of `others`.
+pub fn distances_to_reference<D: TreeDistance>(
+    reference: &TreeSnapshot,
+    others: &[TreeSnapshot],
+    metric: &D,
+) -> Vec<f64> {
+    others
+        .par_iter()
+        .map(|snap| metric.distance_f64(reference, snap))
+        .collect()
+}
+
+/// Compute the rectangular matrix of distances from every snapshot in `a` (rows) to
+/// every snapshot in `b` (columns), e.g. between the trees of two independent MCMC
+/// chains to check that they converged to the same posterior.
+///
+/// All `a.len() × b.len()` entries are computed, in parallel by row; the two sets
+/// must share the same leaf order. `cross_matrix(x, x)` is the full square matrix of
+/// [`pairwise_matrix`], with a zero diagonal.
+pub fn cross_matrix<D: TreeDistance>(
+    a: &[TreeSnapshot],
+    b: &[TreeSnapshot],
+    metric: &D,
+) -> Vec<Vec<f64>> {
+    a.par_iter()
+        .map(|snap_a| {
+            b.iter()
+                .map(|snap_b| metric.distance_f64(snap_a, snap_b))
+                .collect()
+        })
+        .collect()
+}
+
 /// Compute Robinson-Foulds distance between two trees.
 ///
 /// # Algorithm
@@ -31,6 +720,8 @@ use itertools::Itertools;
 /// # Rooted Tree Adjustment
 /// For rooted trees, if the root position differs, we add 2 to the distance.
 /// This accounts for the two extra bipartitions created by moving the root.
+/// The adjustment only applies when both roots are binary: an unrooted tree with a
+/// basal trifurcation such as `(A,B,(C,D))` has no root position to compare.
 ///
 /// # Example
 /// ```text
@@ -42,10 +733,10 @@ use itertools::Itertools;
 /// ```
 ///
 /// # Errors
-/// Returns `TreeError` if trees have different leaf sets or are malformed.
-pub fn robinson_foulds(tree_a: &PhyloTree, tree_b: &PhyloTree) -> Result<usize, TreeError> {
-    let snap_a = TreeSnapshot::from_tree(tree_a)?;
-    let snap_b = TreeSnapshot::from_tree(tree_b)?;
+/// Returns a [`SnapshotError`] if a tree is malformed or has duplicate leaf names.
+pub fn robinson_foulds(tree_a: &PhyloTree, tree_b: &PhyloTree) -> Result<usize, SnapshotError> {
+    let snap_a = TreeSnapshot::from_tree(tree_a, false)?;
+    let snap_b = TreeSnapshot::from_tree(tree_b, false)?;
 
     Ok(rf_from_snapshots(&snap_a, &snap_b))
 }
@@ -62,17 +753,244 @@ pub fn robinson_foulds(tree_a: &PhyloTree, tree_b: &PhyloTree) -> Result<usize,
 ///
 /// This is dramatically faster than the O(m+n) merge algorithm for sorted vectors,
 /// and much simpler too! HashSet intersection is optimized at the system level.
+///
+/// # Rooted adjustment
+/// For two rooted trees with binary roots, 2 is added when their root splits differ.
+/// The root children are compared as sets of canonical bitsets, so the order in which
+/// they are listed, or which side of the root split a child stands for, does not matter.
 pub fn rf_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> usize {
+    rf_from_snapshots_mode(a, b, RfMode::Auto)
+}
+
+/// Whether [`rf_from_snapshots_mode`] treats the trees as rooted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RfMode {
+    /// Apply the rooted adjustment only if both snapshots are rooted
+    #[default]
+    Auto,
+    /// Never apply the rooted adjustment; the root children are ignored
+    ForceUnrooted,
+    /// Apply the rooted adjustment whenever both roots are binary, whatever the
+    /// snapshots' `rooted` flags say
+    ForceRooted,
+}
+
+/// Compute Robinson-Foulds distance with the rooted adjustment of
+/// [`rf_from_snapshots`] chosen by `mode` instead of inferred from the snapshots.
+///
+/// This suits mixed analyses where some files mark their trees as rooted and others
+/// do not, but all trees should be read the same way.
+pub fn rf_from_snapshots_mode(a: &TreeSnapshot, b: &TreeSnapshot, mode: RfMode) -> usize {
     let inter = a.parts.intersection(&b.parts).count();
     let rf = a.parts.len() + b.parts.len() - 2 * inter;
-    let same_root = a.root_children == b.root_children;
-    if a.rooted && b.rooted && rf != 0 && !same_root {
+    let rooted = match mode {
+        RfMode::Auto => a.rooted && b.rooted,
+        RfMode::ForceUnrooted => false,
+        RfMode::ForceRooted => true,
+    };
+    let binary_roots = a.root_children.len() == 2 && b.root_children.len() == 2;
+    if rooted && binary_roots && rf != 0 && root_split(a) != root_split(b) {
         rf + 2
     } else {
         rf
     }
 }
 
+/// The root children of `snap` as a set of canonical bitsets (the side without leaf 0).
+///
+/// Both children of a binary root canonicalize to the same bitset, the root split.
+fn root_split(snap: &TreeSnapshot) -> HashSet<Bitset> {
+    snap.root_children
+        .iter()
+        .map(|child| {
+            if child.get(0) {
+                child.complement(snap.num_leaves)
+            } else {
+                child.clone()
+            }
+        })
+        .collect()
+}
+
+/// Robinson-Foulds distance together with the splits behind it, see
+/// [`rf_detailed_from_snapshots`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RfDetail {
+    /// The RF distance, as returned by [`rf_from_snapshots`]
+    pub distance: usize,
+    /// Canonical splits of tree A that tree B lacks, sorted
+    pub only_in_a: Vec<Bitset>,
+    /// Canonical splits of tree B that tree A lacks, sorted
+    pub only_in_b: Vec<Bitset>,
+}
+
+/// Compute Robinson-Foulds distance and list the splits found in only one tree.
+///
+/// `only_in_a` is `a.parts - b.parts` and `only_in_b` is `b.parts - a.parts`; map a
+/// split back to taxon names with [`TreeSnapshot::taxa`]. Without a rooted
+/// adjustment, `distance` is the total number of listed splits. For rooted trees
+/// with different root splits, the adjustment of [`rf_from_snapshots`] adds 2 that
+/// no listed split accounts for.
+pub fn rf_detailed_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> RfDetail {
+    let sorted_difference = |x: &TreeSnapshot, y: &TreeSnapshot| {
+        let mut parts: Vec<Bitset> = x.parts.difference(&y.parts).cloned().collect();
+        parts.sort_unstable();
+        parts
+    };
+
+    RfDetail {
+        distance: rf_from_snapshots(a, b),
+        only_in_a: sorted_difference(a, b),
+        only_in_b: sorted_difference(b, a),
+    }
+}
+
+/// Compute Robinson-Foulds distance broken down by the size of the differing splits.
+///
+/// Each split found in only one tree is binned by the size of its smaller side,
+/// `min(k, n - k)` for a split with `k` of the `n` leaves. `bins` are ascending upper
+/// bounds: a split lands in the first bin whose bound is at least its size, and
+/// splits larger than every bound land in an extra last bin. The result thus has
+/// `bins.len() + 1` entries and sums to [`rf_from_snapshots`].
+///
+/// The 2 added by the rooted adjustment is split between the bins of the two root
+/// splits, one each.
+///
+/// # Example
+/// ```text
+/// bins = [2, 5]  →  [size ≤ 2, 2 < size ≤ 5, size > 5]
+/// ```
+///
+/// # Panics
+/// If `bins` is not sorted in ascending order.
+pub fn rf_by_size_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot, bins: &[usize]) -> Vec<usize> {
+    assert!(
+        bins.is_sorted(),
+        "size bins must be sorted in ascending order"
+    );
+    let mut counts = vec![0; bins.len() + 1];
+    let mut add = |split: &Bitset, n: usize| {
+        let ones = split.count_ones();
+        let size = ones.min(n - ones);
+        counts[bins.partition_point(|&bound| bound < size)] += 1;
+    };
+    for split in a.parts.symmetric_difference(&b.parts) {
+        add(split, a.num_leaves);
+    }
+    if rf_from_snapshots(a, b) != rf_from_snapshots_mode(a, b, RfMode::ForceUnrooted) {
+        for split in root_split(a).iter().chain(&root_split(b)) {
+            add(split, a.num_leaves);
+        }
+    }
+    counts
+}
+
+/// Compute the generalized Robinson-Foulds distance between trees whose taxon sets
+/// only partly overlap.
+///
+/// # Algorithm
+/// 1. Snapshot both trees over the sorted union of their taxa, so bit `i` is the same
+///    taxon in both
+/// 2. Restrict every partition to the shared taxa and re-canonicalize it (store the
+///    side without the first shared taxon)
+/// 3. Drop partitions left with fewer than two shared taxa on either side, and merge
+///    duplicates
+/// 4. Count the partitions found in only one tree
+///
+/// This equals [`robinson_foulds`] on both trees pruned to their shared taxa, without
+/// rebuilding the trees. Splits are compared as unrooted, so the rooted adjustment of
+/// [`rf_from_snapshots`] is not applied.
+///
+/// # Errors
+/// Returns `TreeError::IsEmpty` (as [`SnapshotError::Tree`]) if the trees share no
+/// taxa, or another [`SnapshotError`] if a tree is malformed.
+pub fn generalized_rf(tree_a: &PhyloTree, tree_b: &PhyloTree) -> Result<usize, SnapshotError> {
+    let names_a = utils::leaf_names(tree_a);
+    let names_b = utils::leaf_names(tree_b);
+    let mut order: Vec<String> = names_a.union(&names_b).cloned().collect();
+    order.sort();
+
+    let snap_a = TreeSnapshot::from_tree_with_order(tree_a, false, &order)?;
+    let snap_b = TreeSnapshot::from_tree_with_order(tree_b, false, &order)?;
+
+    let mut shared = Bitset::zeros(snap_a.words);
+    for (idx, name) in order.iter().enumerate() {
+        if names_a.contains(name) && names_b.contains(name) {
+            shared.set(idx);
+        }
+    }
+    if shared.count_ones() == 0 {
+        return Err(TreeError::IsEmpty.into());
+    }
+
+    let parts_a = restrict_partitions(&snap_a, &shared);
+    let parts_b = restrict_partitions(&snap_b, &shared);
+    Ok(parts_a.symmetric_difference(&parts_b).count())
+}
+
+/// The nontrivial partitions of `snap` restricted to the leaves in `mask`,
+/// canonicalized to the side without the first leaf of `mask`.
+fn restrict_partitions(snap: &TreeSnapshot, mask: &Bitset) -> HashSet<Bitset> {
+    let kept = mask.count_ones();
+    let first = mask.iter_ones().next();
+
+    snap.parts
+        .iter()
+        .filter_map(|part| {
+            let mut side = part.clone();
+            side.and_assign(mask);
+            let size = side.count_ones();
+            if size < 2 || kept - size < 2 {
+                return None;
+            }
+            if first.is_some_and(|idx| side.get(idx)) {
+                side = side.complement(snap.num_leaves);
+                side.and_assign(mask);
+            }
+            Some(side)
+        })
+        .collect()
+}
+
+/// Compute Robinson-Foulds distance scaled to `[0, 1]`.
+///
+/// # Algorithm
+/// normalized RF = RF / max RF
+///
+/// where, for binary trees with n leaves:
+/// - unrooted: max RF = 2n - 6 (all n - 3 internal splits differ)
+/// - both rooted: max RF = 2n - 4 (the root adjustment of [`rf_from_snapshots`] adds 2)
+///
+/// Trees with fewer than 4 leaves have no informative splits, so 0.0 is returned.
+/// The result is capped at 1.0, since non-binary input is not covered by the bound.
+pub fn normalized_rf_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    // A rooted/unrooted pair gets no root adjustment, so the unrooted bound applies
+    let max_rf = max_rf(a).min(max_rf(b));
+    if max_rf == 0 {
+        return 0.0;
+    }
+    (rf_from_snapshots(a, b) as f64 / max_rf as f64).min(1.0)
+}
+
+/// Largest RF distance possible between binary trees on the leaves of `snap`.
+///
+/// Twice the number of internal edges of a binary tree, counted as
+/// [`rf_from_snapshots`] does:
+/// - unrooted: 2n - 6 (n - 3 nontrivial splits)
+/// - rooted: 2n - 4 (the root adjustment counts the root split as well)
+///
+/// Trees with fewer than 4 leaves have no informative splits, so 0 is returned.
+pub fn max_rf(snap: &TreeSnapshot) -> usize {
+    let n = snap.num_leaves;
+    if n < 4 {
+        0
+    } else if snap.rooted {
+        2 * n - 4
+    } else {
+        2 * n - 6
+    }
+}
+
 /// Compute Weighted Robinson-Foulds distance between two trees.
 ///
 /// # Algorithm
@@ -93,11 +1011,17 @@ pub fn rf_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> usize {
 /// Different leaf branches contribute their full lengths
 /// ```
 ///
+/// Pendant (leaf) branches are included, so trees that only differ in a tip
+/// length still have a nonzero distance.
+///
 /// # Errors
-/// Returns `TreeError` if trees have different leaf sets or are malformed.
-pub fn weighted_robinson_foulds(tree_a: &PhyloTree, tree_b: &PhyloTree) -> Result<f64, TreeError> {
-    let snap_a = TreeSnapshot::from_tree(tree_a)?;
-    let snap_b = TreeSnapshot::from_tree(tree_b)?;
+/// Returns a [`SnapshotError`] if a tree is malformed or has duplicate leaf names.
+pub fn weighted_robinson_foulds(
+    tree_a: &PhyloTree,
+    tree_b: &PhyloTree,
+) -> Result<f64, SnapshotError> {
+    let snap_a = TreeSnapshot::from_tree(tree_a, true)?;
+    let snap_b = TreeSnapshot::from_tree(tree_b, true)?;
 
     Ok(weighted_rf_from_snapshots(&snap_a, &snap_b))
 }
@@ -106,87 +1030,985 @@ pub fn weighted_robinson_foulds(tree_a: &PhyloTree, tree_b: &PhyloTree) -> Resul
 ///
 /// Uses HashSet/HashMap for O(n) performance instead of O(m+n) merge.
 pub fn weighted_rf_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
-    let mut distance = 0.0;
+    lp_branch_score_from_snapshots(a, b, 1.0)
+}
+
+/// Compute Weighted RF distance, treating shared branch lengths closer than `eps` as
+/// equal.
+///
+/// For a partition in both trees, a length difference below `eps` contributes 0
+/// instead of its value, which removes the jitter that tiny numerical differences in
+/// BEAST output add to convergence plots. Partitions in only one tree still add their
+/// full length. With `eps = 0.0` this is [`weighted_rf_from_snapshots`].
+pub fn weighted_rf_with_tolerance(a: &TreeSnapshot, b: &TreeSnapshot, eps: f64) -> f64 {
+    wrf_components_with_tolerance(a, b, eps).total()
+}
+
+/// Weighted RF split into the contributions of shared and unique partitions.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WrfComponents {
+    /// Sum of `|length_a - length_b|` over partitions in both trees
+    pub shared_diff: f64,
+    /// Sum of the lengths of partitions only in tree A
+    pub unique_a: f64,
+    /// Sum of the lengths of partitions only in tree B
+    pub unique_b: f64,
+}
+
+impl WrfComponents {
+    /// The weighted RF distance, the sum of all three components.
+    pub fn total(&self) -> f64 {
+        self.shared_diff + self.unique_a + self.unique_b
+    }
+}
+
+/// Compute Weighted RF distance as [`WrfComponents`], telling branch-length
+/// differences on shared splits apart from splits found in only one tree.
+///
+/// [`WrfComponents::total`] equals [`weighted_rf_from_snapshots`].
+pub fn weighted_rf_components_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> WrfComponents {
+    wrf_components_with_tolerance(a, b, 0.0)
+}
+
+/// Accumulate the [`WrfComponents`], skipping shared differences below `eps`.
+fn wrf_components_with_tolerance(a: &TreeSnapshot, b: &TreeSnapshot, eps: f64) -> WrfComponents {
+    let mut components = WrfComponents::default();
 
     // Iterate through partitions in tree A
     for part in &a.parts {
         let length_a = a.lengths.get(part).unwrap_or(&0.0);
 
         if let Some(length_b) = b.lengths.get(part) {
-            // Partition in both: add absolute difference
-            distance += (length_a - length_b).abs();
+            // Partition in both: add absolute difference unless within tolerance
+            let diff = (length_a - length_b).abs();
+            if diff >= eps {
+                components.shared_diff += diff;
+            }
         } else {
             // Partition only in A: add full length
-            distance += length_a;
+            components.unique_a += length_a;
         }
     }
 
     // Add partitions only in B
     for part in &b.parts {
         if !a.parts.contains(part) {
-            distance += b.lengths.get(part).unwrap_or(&0.0);
+            components.unique_b += b.lengths.get(part).unwrap_or(&0.0);
+        }
+    }
+
+    components
+}
+
+/// Compute Weighted RF distance on branch lengths normalized per tree.
+///
+/// Each partition length is divided by its tree's total branch length (the sum of
+/// `lengths`) before accumulating the absolute differences as in
+/// [`weighted_rf_from_snapshots`], so trees on different absolute time scales are
+/// comparable and the result lies in `[0, 2]`.
+///
+/// Returns 0.0 if either tree has a total branch length of zero.
+pub fn weighted_rf_normalized_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    let total_a: f64 = a.lengths.values().sum();
+    let total_b: f64 = b.lengths.values().sum();
+    if total_a == 0.0 || total_b == 0.0 {
+        return 0.0;
+    }
+
+    let mut distance = 0.0;
+
+    for part in &a.parts {
+        let length_a = a.lengths.get(part).unwrap_or(&0.0) / total_a;
+
+        if let Some(length_b) = b.lengths.get(part) {
+            distance += (length_a - length_b / total_b).abs();
+        } else {
+            distance += length_a;
+        }
+    }
+
+    for part in &b.parts {
+        if !a.parts.contains(part) {
+            distance += b.lengths.get(part).unwrap_or(&0.0) / total_b;
         }
     }
 
     distance
 }
 
-/// Compute Kuhner-Felsenstein (Branch Score) distance between two trees.
-///
-/// # Algorithm
-/// Like Weighted RF but uses squared differences:
-/// distance = sqrt(Σ (length_a - length_b)²)
+/// Branch lengths of two snapshots aligned by split, see [`match_edge_lengths`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgeMatching {
+    /// Splits of both trees as `(split, length_a, length_b)`, sorted by split
+    pub shared: Vec<(Bitset, f64, f64)>,
+    /// Splits of tree A that tree B lacks, with their length in A, sorted
+    pub only_in_a: Vec<(Bitset, f64)>,
+    /// Splits of tree B that tree A lacks, with their length in B, sorted
+    pub only_in_b: Vec<(Bitset, f64)>,
+}
+
+/// Pair up the branch lengths of two snapshots over the same leaf order.
+///
+/// # Algorithm
+/// 1. Re-canonicalize every partition to the side without leaf 0, so a split stored
+///    by its other side (e.g. after restricting to a common leaf set) still matches
+/// 2. Split identical bitsets into `shared`; every other split lands in `only_in_a`
+///    or `only_in_b` with its own length
+///
+/// A partition without a recorded length counts as 0.0, as in
+/// [`weighted_rf_from_snapshots`], which equals the sum of `|length_a - length_b|`
+/// over `shared` plus all unshared lengths.
+pub fn match_edge_lengths(a: &TreeSnapshot, b: &TreeSnapshot) -> EdgeMatching {
+    let canonical_lengths = |snap: &TreeSnapshot| -> HashMap<Bitset, f64> {
+        snap.parts
+            .iter()
+            .map(|part| {
+                let length = *snap.lengths.get(part).unwrap_or(&0.0);
+                let side = if part.get(0) {
+                    part.complement(snap.num_leaves)
+                } else {
+                    part.clone()
+                };
+                (side, length)
+            })
+            .collect()
+    };
+    let (lengths_a, lengths_b) = (canonical_lengths(a), canonical_lengths(b));
+
+    let mut shared = Vec::new();
+    let mut only_in_a = Vec::new();
+    for (part, &length_a) in &lengths_a {
+        match lengths_b.get(part) {
+            Some(&length_b) => shared.push((part.clone(), length_a, length_b)),
+            None => only_in_a.push((part.clone(), length_a)),
+        }
+    }
+    let mut only_in_b: Vec<(Bitset, f64)> = lengths_b
+        .iter()
+        .filter(|(part, _)| !lengths_a.contains_key(*part))
+        .map(|(part, &length)| (part.clone(), length))
+        .collect();
+
+    shared.sort_unstable_by(|x, y| x.0.cmp(&y.0));
+    only_in_a.sort_unstable_by(|x, y| x.0.cmp(&y.0));
+    only_in_b.sort_unstable_by(|x, y| x.0.cmp(&y.0));
+
+    EdgeMatching {
+        shared,
+        only_in_a,
+        only_in_b,
+    }
+}
+
+/// Compute Kuhner-Felsenstein (Branch Score) distance between two trees.
+///
+/// # Algorithm
+/// Like Weighted RF but uses squared differences:
+/// distance = sqrt(Σ (length_a - length_b)²)
+///
+/// For each partition:
+/// - If in both trees: add (length_a - length_b)²
+/// - If only in A: add length_a²
+/// - If only in B: add length_b²
+///
+/// Then take the square root of the sum.
+///
+/// # Properties
+/// - More sensitive to large branch length differences
+/// - Euclidean metric in branch length space
+/// - Range: [0, ∞)
+///
+/// Like [`weighted_robinson_foulds`], pendant (leaf) branches are included.
+///
+/// # Errors
+/// Returns a [`SnapshotError`] if a tree is malformed or has duplicate leaf names.
+pub fn kuhner_felsenstein(tree_a: &PhyloTree, tree_b: &PhyloTree) -> Result<f64, SnapshotError> {
+    let snap_a = TreeSnapshot::from_tree(tree_a, true)?;
+    let snap_b = TreeSnapshot::from_tree(tree_b, true)?;
+
+    Ok(kf_from_snapshots(&snap_a, &snap_b))
+}
+
+/// Compute Kuhner-Felsenstein distance from two pre-computed snapshots.
+///
+/// The square root of [`kf_squared_from_snapshots`], i.e. the `p = 2` case of
+/// [`lp_branch_score_from_snapshots`].
+pub fn kf_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    lp_branch_score_from_snapshots(a, b, 2.0)
+}
+
+/// Compute the squared Kuhner-Felsenstein distance from two pre-computed snapshots:
+/// Σ(length_a - length_b)² without the final square root.
+///
+/// Unlike KF itself this is additive over branches, so it can be summed or averaged
+/// directly.
+///
+/// Uses HashSet/HashMap for O(n) performance, accumulating squared differences.
+pub fn kf_squared_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    branch_differences(a, b).map(|diff| diff * diff).sum()
+}
+
+/// Compute the branch score under the `Lp` norm: `(Σ |length_a - length_b|^p)^(1/p)`
+/// over all partitions, a partition missing from one tree counting with length 0.
+///
+/// `p = 1` is [`weighted_rf_from_snapshots`] and `p = 2` is [`kf_from_snapshots`];
+/// larger `p` weights the biggest branch differences more, and `p = f64::INFINITY`
+/// is the largest single difference (Chebyshev distance). Values of `p` below 1 are
+/// accepted but do not give a metric (the triangle inequality can fail).
+///
+/// # Panics
+/// If `p` is not positive (including NaN).
+pub fn lp_branch_score_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot, p: f64) -> f64 {
+    lp_norm(branch_differences(a, b), p)
+}
+
+/// Compute the `Lp` branch score of two rooted trees with every edge below the root
+/// compared on its own.
+///
+/// A binary root joins two edges into a single unrooted partition, whose length in a
+/// snapshot is only one of them, so [`lp_branch_score_from_snapshots`] misses
+/// differences in the other root edge. Here the root edges are matched by the clade
+/// below them instead: the same clade under both roots adds its length difference,
+/// a clade under one root only adds its full length. All other partitions count as in
+/// [`lp_branch_score_from_snapshots`], which this equals unless both trees are rooted.
+///
+/// # Example
+/// ```
+/// # use phylotree::tree::Tree;
+/// # use rust_python_tree_distances::distances::weighted_rf_with_root_edges;
+/// # use rust_python_tree_distances::snapshot::TreeSnapshot;
+/// let snap = |nwk| TreeSnapshot::from_tree(&Tree::from_newick(nwk).unwrap(), true).unwrap();
+/// let a = snap("((A:1,B:1):1,(C:1,D:1):2);");
+/// let b = snap("((A:1,B:1):1,(C:1,D:1):3);");
+/// assert_eq!(weighted_rf_with_root_edges(&a, &b), 1.0);
+/// ```
+///
+/// # Panics
+/// If `p` is not positive (including NaN).
+pub fn lp_branch_score_with_root_edges(a: &TreeSnapshot, b: &TreeSnapshot, p: f64) -> f64 {
+    if !(a.rooted && b.rooted) {
+        return lp_branch_score_from_snapshots(a, b, p);
+    }
+    lp_norm(branch_differences_with_root_edges(a, b).into_iter(), p)
+}
+
+/// Weighted RF of two rooted trees with the root edges compared one by one: the
+/// `p = 1` case of [`lp_branch_score_with_root_edges`].
+pub fn weighted_rf_with_root_edges(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    lp_branch_score_with_root_edges(a, b, 1.0)
+}
+
+/// Kuhner-Felsenstein distance of two rooted trees with the root edges compared one by
+/// one: the `p = 2` case of [`lp_branch_score_with_root_edges`].
+pub fn kf_with_root_edges(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    lp_branch_score_with_root_edges(a, b, 2.0)
+}
+
+/// `(Σ diff^p)^(1/p)` over non-negative differences, the largest one for infinite `p`.
+///
+/// # Panics
+/// If `p` is not positive (including NaN).
+fn lp_norm(diffs: impl Iterator<Item = f64>, p: f64) -> f64 {
+    assert!(p > 0.0, "the Lp exponent must be positive, got {p}");
+    // The common exponents avoid powf so they match the dedicated metrics exactly
+    if p == f64::INFINITY {
+        diffs.fold(0.0, f64::max)
+    } else if p == 1.0 {
+        diffs.sum()
+    } else if p == 2.0 {
+        diffs.map(|diff| diff * diff).sum::<f64>().sqrt()
+    } else {
+        diffs.map(|diff| diff.powf(p)).sum::<f64>().powf(p.recip())
+    }
+}
+
+/// Absolute branch-length difference of every partition in either tree: the
+/// difference for shared partitions, the full length for partitions in one tree.
+fn branch_differences<'a>(
+    a: &'a TreeSnapshot,
+    b: &'a TreeSnapshot,
+) -> impl Iterator<Item = f64> + 'a {
+    let length = |snap: &TreeSnapshot, part: &Bitset| *snap.lengths.get(part).unwrap_or(&0.0);
+    // Partitions in A, shared or not, then those only in B
+    let in_a = a.parts.iter().map(move |part| match b.lengths.get(part) {
+        Some(length_b) => (length(a, part) - length_b).abs(),
+        None => length(a, part),
+    });
+    let only_in_b = b
+        .parts
+        .iter()
+        .filter(|part| !a.parts.contains(*part))
+        .map(move |part| length(b, part));
+    in_a.chain(only_in_b)
+}
+
+/// [`branch_differences`] with the partitions of the root edges replaced by the root
+/// edges themselves, matched by the clade below them.
+fn branch_differences_with_root_edges(a: &TreeSnapshot, b: &TreeSnapshot) -> Vec<f64> {
+    let (skip_a, skip_b) = (root_split(a), root_split(b));
+    let below_root = |snap: &TreeSnapshot, skip: &HashSet<Bitset>, part: &Bitset| {
+        snap.parts.contains(part) && !skip.contains(part)
+    };
+    let length = |snap: &TreeSnapshot, part: &Bitset| *snap.lengths.get(part).unwrap_or(&0.0);
+
+    let mut diffs = Vec::with_capacity(a.parts.len() + b.root_lengths.len());
+    for part in a.parts.iter().filter(|part| !skip_a.contains(*part)) {
+        diffs.push(if below_root(b, &skip_b, part) {
+            (length(a, part) - length(b, part)).abs()
+        } else {
+            length(a, part)
+        });
+    }
+    for part in b.parts.iter().filter(|part| !skip_b.contains(*part)) {
+        if !below_root(a, &skip_a, part) {
+            diffs.push(length(b, part));
+        }
+    }
+
+    for (clade, length_a) in &a.root_lengths {
+        diffs.push(match b.root_lengths.get(clade) {
+            Some(length_b) => (length_a - length_b).abs(),
+            None => *length_a,
+        });
+    }
+    for (clade, length_b) in &b.root_lengths {
+        if !a.root_lengths.contains_key(clade) {
+            diffs.push(*length_b);
+        }
+    }
+    diffs
+}
+
+/// Compute the path-difference distance (Steel & Penny) from two snapshots.
+///
+/// # Algorithm
+/// The path length between leaves `i` and `j` is the number of edges between them,
+/// which equals the number of partitions that put `i` and `j` on different sides:
+/// ```text
+/// d(i, j) = |{ p : p[i] != p[j] }|
+/// PD      = sqrt( Σ_{i<j} (d_a(i, j) - d_b(i, j))² )
+/// ```
+/// Only nontrivial partitions are counted: every path also crosses the two pendant
+/// edges of its end points, which adds the same 2 to both trees and cancels out.
+/// Paths are measured on the unrooted tree, so the two edges below a binary root
+/// count as one.
+///
+/// # Example
+/// ```text
+/// (A,B,(C,D)) vs (A,C,(B,D)):
+/// AB: 2 vs 3, CD: 2 vs 3, AC: 3 vs 2, BD: 3 vs 2, AD and BC: 3 vs 3
+/// PD = sqrt(1 + 1 + 1 + 1) = 2
+/// ```
+///
+/// # Complexity
+/// O(n² · p) for n leaves and p partitions: every partition is checked against
+/// every leaf pair. Unlike RF, this is sensitive to *where* in the tree two
+/// topologies differ. Trees with fewer than 2 leaves have no paths and give 0.0.
+pub fn path_difference_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    debug_assert_same_leaves(a, b);
+    let n = a.num_leaves;
+    if n < 2 {
+        return 0.0;
+    }
+
+    // Upper triangle of the path-length matrix, row by row
+    let path_lengths = |snap: &TreeSnapshot| -> Vec<i64> {
+        let mut lengths = vec![0i64; n * (n - 1) / 2];
+        for part in &snap.parts {
+            if !(2..=n - 2).contains(&part.count_ones()) {
+                continue;
+            }
+            let mut k = 0;
+            for i in 0..n {
+                let side_i = part.get(i);
+                for j in i + 1..n {
+                    if side_i != part.get(j) {
+                        lengths[k] += 1;
+                    }
+                    k += 1;
+                }
+            }
+        }
+        lengths
+    };
+
+    let sum_squared: i64 = path_lengths(a)
+        .into_iter()
+        .zip(path_lengths(b))
+        .map(|(da, db)| (da - db).pow(2))
+        .sum();
+    (sum_squared as f64).sqrt()
+}
+
+/// Compute the rooted triplet distance from two snapshots.
+///
+/// Counts the leaf triplets `{i, j, k}` whose rooted resolution (`ij|k`, `ik|j`,
+/// `jk|i` or unresolved) differs between the two trees.
+///
+/// # Algorithm
+/// 1. Recover the rooted clusters: a canonical part is a cluster if it lies within
+///    one root child, otherwise its complement is; the root children are added too
+/// 2. For every leaf pair, the size of the smallest cluster containing both (their LCA)
+/// 3. For every triplet, the pair with the strictly smallest LCA is the resolved pair;
+///    if there is none, the triplet is unresolved (polytomy)
+///
+/// This is an O(n²·p + n³) enumeration, intended for small rooted trees.
+///
+/// # Example
+/// ```text
+/// (((A,B),C),D) vs (((A,C),B),D): only {A,B,C} differs (AB|C vs AC|B) → 1
+/// ```
+///
+/// # Errors
+/// Returns [`DistanceError::Unrooted`] if either tree is unrooted.
+pub fn triplet_distance_from_snapshots(
+    a: &TreeSnapshot,
+    b: &TreeSnapshot,
+) -> Result<usize, DistanceError> {
+    if !a.rooted || !b.rooted {
+        return Err(DistanceError::Unrooted);
+    }
+    debug_assert_same_leaves(a, b);
+    let n = a.num_leaves;
+
+    let (lca_a, lca_b) = (lca_cluster_sizes(a), lca_cluster_sizes(b));
+    let resolution = |lca: &[Vec<usize>], i: usize, j: usize, k: usize| -> u8 {
+        let (ij, ik, jk) = (lca[i][j], lca[i][k], lca[j][k]);
+        if ij < ik && ij < jk {
+            0
+        } else if ik < ij && ik < jk {
+            1
+        } else if jk < ij && jk < ik {
+            2
+        } else {
+            3
+        }
+    };
+
+    let mut differing = 0;
+    for i in 0..n {
+        for j in i + 1..n {
+            for k in j + 1..n {
+                if resolution(&lca_a, i, j, k) != resolution(&lca_b, i, j, k) {
+                    differing += 1;
+                }
+            }
+        }
+    }
+    Ok(differing)
+}
+
+/// For every leaf pair of a rooted snapshot, the size of the smallest cluster
+/// containing both (the whole tree, `num_leaves`, if no other cluster does).
+fn lca_cluster_sizes(snap: &TreeSnapshot) -> Vec<Vec<usize>> {
+    let n = snap.num_leaves;
+    let mut lca = vec![vec![n; n]; n];
+
+    // (bitset, whether the cluster is the set side) for every rooted cluster
+    let clusters = snap
+        .parts
+        .iter()
+        .map(|part| {
+            let size = part.count_ones();
+            let is_cluster = snap
+                .root_children
+                .iter()
+                .any(|child| part.intersection_count(child) == size);
+            (part, is_cluster)
+        })
+        .chain(snap.root_children.iter().map(|child| (child, true)));
+
+    for (bits, set_side) in clusters {
+        let members: Vec<usize> = (0..n).filter(|&x| bits.get(x) == set_side).collect();
+        for (idx, &x) in members.iter().enumerate() {
+            for &y in &members[idx + 1..] {
+                lca[x][y] = lca[x][y].min(members.len());
+                lca[y][x] = lca[x][y];
+            }
+        }
+    }
+    lca
+}
+
+/// Compute the matching-split distance (Bogdanowicz & Giaro) from two snapshots.
+///
+/// # Algorithm
+/// 1. Keep the nontrivial splits of each tree (2 ≤ |side| ≤ n - 2)
+/// 2. Cost of pairing split `A|A'` with `B|B'`: the number of leaves that must
+///    change side, min(|A Δ B|, n - |A Δ B|), where |A Δ B| = |A| + |B| - 2|A ∩ B|
+/// 3. Pad the smaller tree with empty splits, costing min(|A|, n - |A|)
+/// 4. Solve the minimum-weight perfect matching with the Hungarian algorithm, O(k³)
+///
+/// # Example
+/// ```text
+/// Caterpillar A,B,C,D,E,F,G,H with A moved from one end to the other:
+/// RF = 10 (all 5 splits differ in both trees),
+/// matching split = 6 (A moves across 4 splits, one split swaps B for H).
+/// ```
+///
+/// Unlike RF, a single taxon moving far through the tree is only counted once per split.
+pub fn matching_split_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    debug_assert_same_leaves(a, b);
+    let n = a.num_leaves;
+    let (splits_a, splits_b) = (nontrivial_splits(a), nontrivial_splits(b));
+
+    let k = splits_a.len().max(splits_b.len());
+    let side_cost = |size: usize| size.min(n - size) as f64;
+    let cost: Vec<Vec<f64>> = (0..k)
+        .map(|i| {
+            (0..k)
+                .map(|j| match (splits_a.get(i), splits_b.get(j)) {
+                    (Some(x), Some(y)) => {
+                        let sym_diff =
+                            x.count_ones() + y.count_ones() - 2 * x.intersection_count(y);
+                        side_cost(sym_diff)
+                    }
+                    (Some(x), None) | (None, Some(x)) => side_cost(x.count_ones()),
+                    (None, None) => 0.0,
+                })
+                .collect()
+        })
+        .collect();
+
+    min_cost_assignment(&cost)
+}
+
+/// Compute the generalized Jaccard-Robinson-Foulds distance (Böcker et al.) from two snapshots.
+///
+/// # Algorithm
+/// 1. Keep the nontrivial splits of each tree, as in [`matching_split_from_snapshots`]
+/// 2. Similarity of `A|A'` and `B|B'`, with Jaccard index J(X, Y) = |X ∩ Y| / |X ∪ Y|:
+///    ```text
+///    sim = max( min(J(A, B), J(A', B')), min(J(A, B'), J(A', B)) ) ^ k
+///    ```
+///    All set sizes follow from |A|, |B|, |A ∩ B| and n, so only one AND count per pair is needed
+/// 3. A matched pair costs 2 - 2·sim, an unmatched split costs 1
+/// 4. Minimum-cost matching with the Hungarian algorithm, O(s³) for s splits
+///
+/// `k` tunes how strictly splits must agree: small `k` rewards near-identical splits
+/// (closer to matching split), and as `k → ∞` only identical splits score, giving plain
+/// RF (without the rooted adjustment of [`rf_from_snapshots`]).
+pub fn jaccard_rf_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot, k: f64) -> f64 {
+    debug_assert_same_leaves(a, b);
+    let n = a.num_leaves;
+    let (splits_a, splits_b) = (nontrivial_splits(a), nontrivial_splits(b));
+
+    let jaccard = |inter: usize, union: usize| inter as f64 / union as f64;
+    let similarity = |x: &Bitset, y: &Bitset| -> f64 {
+        let (size_x, size_y) = (x.count_ones(), y.count_ones());
+        let inter = x.intersection_count(y);
+        let union = size_x + size_y - inter;
+        let same_sides = jaccard(inter, union).min(jaccard(n - union, n - inter));
+        let crossed_sides = jaccard(size_x - inter, n - size_y + inter)
+            .min(jaccard(size_y - inter, n - size_x + inter));
+        same_sides.max(crossed_sides).powf(k)
+    };
+
+    let size = splits_a.len().max(splits_b.len());
+    let cost: Vec<Vec<f64>> = (0..size)
+        .map(|i| {
+            (0..size)
+                .map(|j| match (splits_a.get(i), splits_b.get(j)) {
+                    (Some(x), Some(y)) => 2.0 - 2.0 * similarity(x, y),
+                    (Some(_), None) | (None, Some(_)) => 1.0,
+                    (None, None) => 0.0,
+                })
+                .collect()
+        })
+        .collect();
+
+    min_cost_assignment(&cost)
+}
+
+/// Compute the clustering information distance (Smith 2020) from two snapshots.
+///
+/// # Algorithm
+/// Each split `A|A'` clusters the `n` leaves into two groups. With `p = |A| / n`, its
+/// clustering entropy (in bits) is
+/// ```text
+/// H(A) = -p·log2(p) - (1 - p)·log2(1 - p)
+/// ```
+/// and the mutual information of two splits follows from the 2×2 contingency table
+/// of leaf counts `n_xy` (`|A ∩ B|`, `|A ∩ B'|`, `|A' ∩ B|`, `|A' ∩ B'|`):
+/// ```text
+/// I(A, B) = Σ (n_xy / n)·log2( n·n_xy / (n_x·n_y) )
+/// ```
+/// 1. Keep the nontrivial splits of each tree, as in [`matching_split_from_snapshots`]
+/// 2. Mutual clustering information MCI: the maximum total `I` over a matching of
+///    splits (Hungarian algorithm); unmatched splits contribute nothing
+/// 3. CID = H(T_a) + H(T_b) - 2·MCI, where H(T) sums the entropies of the tree's splits
+///
+/// Identical trees have distance 0. A tree with no nontrivial splits (a star, or fewer
+/// than 4 leaves) has H(T) = 0, so its distance to any tree is that tree's entropy.
+/// Unlike RF, splits that nearly agree still share most of their information, so the
+/// distance does not saturate for dissimilar trees.
+pub fn clustering_info_distance_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    debug_assert_same_leaves(a, b);
+    let n = a.num_leaves;
+    if n == 0 {
+        return 0.0;
+    }
+    let (splits_a, splits_b) = (nontrivial_splits(a), nontrivial_splits(b));
+
+    // Σ (count / n)·log2(n·count / (row·col)) over the nonzero cells
+    let nf = n as f64;
+    let cell = |count: usize, row: usize, col: usize| -> f64 {
+        if count == 0 {
+            return 0.0;
+        }
+        let count = count as f64;
+        count / nf * (nf * count / (row as f64 * col as f64)).log2()
+    };
+    let mutual_info = |x: &Bitset, y: &Bitset| -> f64 {
+        let (size_x, size_y) = (x.count_ones(), y.count_ones());
+        let inter = x.intersection_count(y);
+        cell(inter, size_x, size_y)
+            + cell(size_x - inter, size_x, n - size_y)
+            + cell(size_y - inter, n - size_x, size_y)
+            + cell(n + inter - size_x - size_y, n - size_x, n - size_y)
+    };
+
+    // A split shares all of its information with itself: H(A) = I(A, A)
+    let entropy = |x: &Bitset| mutual_info(x, x);
+
+    // Maximize the information by minimizing its negation
+    let size = splits_a.len().max(splits_b.len());
+    let cost: Vec<Vec<f64>> = (0..size)
+        .map(|i| {
+            (0..size)
+                .map(|j| match (splits_a.get(i), splits_b.get(j)) {
+                    (Some(x), Some(y)) => -mutual_info(x, y),
+                    _ => 0.0,
+                })
+                .collect()
+        })
+        .collect();
+    let mci = -min_cost_assignment(&cost);
+
+    let total_entropy: f64 = splits_a.iter().chain(&splits_b).map(entropy).sum();
+    // Rounding can leave a tiny negative value for identical trees
+    (total_entropy - 2.0 * mci).max(0.0)
+}
+
+/// Compute the cluster affinity of two snapshots, a similarity (higher is closer).
+///
+/// # Algorithm
+/// 1. Keep the nontrivial splits of each tree, as in [`matching_split_from_snapshots`],
+///    each as its canonical side (the side without leaf 0)
+/// 2. Affinity of two clusters, normalized by the larger of both so that only identical
+///    clusters reach 1:
+///    ```text
+///    aff(X, Y) = |X ∩ Y| / max(|X|, |Y|)
+///    ```
+/// 3. Each cluster of A scores its best match in B, and vice versa; the two directed
+///    sums are averaged so the result is symmetric:
+///    ```text
+///    CA(A, B) = ( Σ_X max_Y aff(X, Y) + Σ_Y max_X aff(X, Y) ) / 2
+///    ```
+///
+/// Clusters need not be matched one to one, so this costs O(s²) AND counts for s splits
+/// and no assignment problem is solved. The self-affinity `CA(A, A)` equals the number
+/// of nontrivial splits of `A` and bounds `CA(A, B)` when both trees have that many
+/// splits; a tree without nontrivial splits contributes 0.
+pub fn cluster_affinity_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+    let (splits_a, splits_b) = (nontrivial_splits(a), nontrivial_splits(b));
+
+    let affinity = |x: &Bitset, y: &Bitset| -> f64 {
+        let larger = x.count_ones().max(y.count_ones());
+        x.intersection_count(y) as f64 / larger as f64
+    };
+    let best_matches = |from: &[Bitset], to: &[Bitset]| -> f64 {
+        from.iter()
+            .map(|x| to.iter().map(|y| affinity(x, y)).fold(0.0, f64::max))
+            .sum()
+    };
+
+    (best_matches(&splits_a, &splits_b) + best_matches(&splits_b, &splits_a)) / 2.0
+}
+
+/// Check in debug builds that `a` and `b` share their leaves, for the metrics that
+/// index both snapshots with the leaf count of `a`.
+fn debug_assert_same_leaves(a: &TreeSnapshot, b: &TreeSnapshot) {
+    debug_assert!(
+        a.num_leaves == b.num_leaves && a.leaf_names == b.leaf_names,
+        "snapshots must share the same leaves ({} vs {} leaves)",
+        a.num_leaves,
+        b.num_leaves
+    );
+}
+
+/// Nontrivial splits of a snapshot (2 ≤ |side| ≤ n - 2), sorted.
+fn nontrivial_splits(snap: &TreeSnapshot) -> Vec<Bitset> {
+    let n = snap.num_leaves;
+    let mut splits: Vec<Bitset> = snap
+        .parts
+        .iter()
+        .filter(|part| (2..=n.saturating_sub(2)).contains(&part.count_ones()))
+        .cloned()
+        .collect();
+    splits.sort_unstable();
+    splits
+}
+
+/// Minimum total cost of a perfect matching in a square cost matrix (Hungarian algorithm).
+///
+/// Uses row/column potentials `u`/`v`; `p[j]` is the row assigned to column `j` and
+/// column 0 is a sentinel.
+fn min_cost_assignment(cost: &[Vec<f64>]) -> f64 {
+    let n = cost.len();
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; n + 1];
+    let mut p = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut minv = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+
+        // Grow an alternating tree from row i until a free column is reached
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=n {
+                if !used[j] {
+                    let reduced = cost[i0 - 1][j - 1] - u[i0] - v[j];
+                    if reduced < minv[j] {
+                        minv[j] = reduced;
+                        way[j] = j0;
+                    }
+                    if minv[j] < delta {
+                        delta = minv[j];
+                        j1 = j;
+                    }
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+
+        // Flip the augmenting path
+        while j0 != 0 {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+        }
+    }
+
+    (1..=n).map(|j| cost[p[j] - 1][j - 1]).sum()
+}
+
+/// Pearson correlation of branch lengths over the partitions shared by two snapshots.
+///
+/// # Algorithm
+/// Pair up `a.lengths[p]` and `b.lengths[p]` for every `p` in `A ∩ B`, then
+/// r = cov(x, y) / (σx σy)
+///
+/// Unlike [`kf_from_snapshots`], which measures the magnitude of length differences,
+/// this is scale-invariant: a tree whose shared branches are all twice as long still
+/// correlates perfectly.
+///
+/// Returns `None` if fewer than two partitions are shared, or if the shared lengths of
+/// either tree are constant (zero variance).
+pub fn shared_branch_length_correlation(a: &TreeSnapshot, b: &TreeSnapshot) -> Option<f64> {
+    let pairs: Vec<(f64, f64)> = a
+        .parts
+        .intersection(&b.parts)
+        .map(|part| {
+            (
+                *a.lengths.get(part).unwrap_or(&0.0),
+                *b.lengths.get(part).unwrap_or(&0.0),
+            )
+        })
+        .collect();
+
+    if pairs.len() < 2 {
+        return None;
+    }
+
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in &pairs {
+        let (dx, dy) = (x - mean_x, y - mean_y);
+        cov += dx * dy;
+        var_x += dx * dx;
+        var_y += dy * dy;
+    }
+
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
+
+/// Mean distance of every tree to all others: the row means of `mat`, leaving out the
+/// zero diagonal.
 ///
-/// For each partition:
-/// - If in both trees: add (length_a - length_b)²
-/// - If only in A: add length_a²
-/// - If only in B: add length_b²
+/// The tree with the smallest mean is the medoid of [`medoid_from_snapshots`], which
+/// finds it without building the matrix. A single tree has mean 0.0.
 ///
-/// Then take the square root of the sum.
+/// # Example
+/// ```
+/// # use rust_python_tree_distances::distances::row_means;
+/// # use rust_python_tree_distances::matrix::SymMatrix;
+/// let mat = SymMatrix::from_condensed(3, vec![1.0, 2.0, 3.0]);
+/// assert_eq!(row_means(&mat), [1.5, 2.0, 2.5]);
+/// ```
+pub fn row_means<T: DistanceValue>(mat: &SymMatrix<T>) -> Vec<f64> {
+    let n = mat.len();
+    let others = n.saturating_sub(1).max(1) as f64;
+    let mut sums = vec![0.0; n];
+    for (i, j, value) in mat.pairs() {
+        let value = value.to_f64();
+        sums[i] += value;
+        sums[j] += value;
+    }
+    sums.into_iter().map(|sum| sum / others).collect()
+}
+
+/// Index of the medoid tree: the row of `mat` with the smallest summed distance to all
+/// others, a cheap stand-in for the maximum clade credibility tree.
 ///
-/// # Properties
-/// - More sensitive to large branch length differences
-/// - Euclidean metric in branch length space
-/// - Range: [0, ∞)
+/// Ties resolve to the lowest index, as in [`medoid_from_snapshots`].
 ///
-/// # Errors
-/// Returns `TreeError` if trees have different leaf sets or are malformed.
-pub fn kuhner_felsenstein(tree_a: &PhyloTree, tree_b: &PhyloTree) -> Result<f64, TreeError> {
-    let snap_a = TreeSnapshot::from_tree(tree_a)?;
-    let snap_b = TreeSnapshot::from_tree(tree_b)?;
-
-    Ok(kf_from_snapshots(&snap_a, &snap_b))
+/// # Panics
+/// If `mat` is empty.
+pub fn medoid_index<T: DistanceValue>(mat: &SymMatrix<T>) -> usize {
+    row_means(mat)
+        .into_iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+        .expect("medoid of an empty matrix")
 }
 
-/// Compute Kuhner-Felsenstein distance from two pre-computed snapshots.
+/// Order the rows of `mat` by average-linkage (UPGMA) hierarchical clustering, so that
+/// similar trees end up next to each other, e.g. in a heatmap.
 ///
-/// Uses HashSet/HashMap for O(n) performance, accumulating squared differences.
-pub fn kf_from_snapshots(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
-    let mut sum_squared = 0.0;
+/// Returns a permutation: `order[k]` is the row shown at position `k`, ready for
+/// [`SymMatrix::permuted`]. The order is the leaf order of the UPGMA dendrogram; at
+/// every merge the cluster holding the lower input index comes first, so the result
+/// is deterministic.
+///
+/// # Algorithm
+/// Nearest-neighbor chain: follow nearest neighbors from any cluster until two
+/// clusters are each other's nearest neighbors, merge them, and continue from the
+/// rest of the chain. Average linkage never makes a merged cluster closer to the
+/// others than its parts were, so this finds the same merges as the textbook
+/// closest-pair loop in O(n²) time instead of O(n³). Distances to a merged cluster
+/// follow the Lance-Williams update `d(k, i ∪ j) = (|i| d(k, i) + |j| d(k, j)) / (|i| + |j|)`.
+pub fn cluster_order<T: DistanceValue>(mat: &SymMatrix<T>) -> Vec<usize> {
+    let n = mat.len();
+    let mut dist =
+        SymMatrix::from_condensed(n, mat.condensed().iter().map(|d| d.to_f64()).collect());
+    // members[c] is the leaf order of cluster c, empty once merged into another
+    let mut members: Vec<Vec<usize>> = (0..n).map(|i| vec![i]).collect();
+    let mut active: Vec<usize> = (0..n).collect();
+    let mut chain: Vec<usize> = Vec::new();
 
-    // Iterate through partitions in tree A
-    for part in &a.parts {
-        let length_a = a.lengths.get(part).unwrap_or(&0.0);
+    while active.len() > 1 {
+        if chain.is_empty() {
+            chain.push(active[0]);
+        }
+        let a = *chain.last().expect("chain is not empty");
+        let previous = chain.len().checked_sub(2).map(|idx| chain[idx]);
+        // Nearest active neighbor of a; ties prefer the previous chain link, which
+        // guarantees the chain terminates
+        let mut best = previous;
+        let mut best_dist = previous.map_or(f64::INFINITY, |p| dist.get(a, p));
+        for &c in &active {
+            if c != a && dist.get(a, c) < best_dist {
+                best = Some(c);
+                best_dist = dist.get(a, c);
+            }
+        }
+        let b = best.expect("at least two active clusters");
 
-        if let Some(length_b) = b.lengths.get(part) {
-            // Partition in both: add (diff)²
-            let diff = length_a - length_b;
-            sum_squared += diff * diff;
-        } else {
-            // Partition only in A: add length²
-            sum_squared += length_a * length_a;
+        if Some(b) != previous {
+            chain.push(b);
+            continue;
         }
-    }
+        chain.truncate(chain.len() - 2);
 
-    // Add partitions only in B
-    for part in &b.parts {
-        if !a.parts.contains(part) {
-            let length_b = b.lengths.get(part).unwrap_or(&0.0);
-            sum_squared += length_b * length_b;
+        // Merge the later cluster into the one holding the lower index
+        let (keep, gone) = if members[a][0] < members[b][0] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let (size_keep, size_gone) = (members[keep].len() as f64, members[gone].len() as f64);
+        active.retain(|&c| c != gone);
+        for &c in &active {
+            if c != keep {
+                let merged = (size_keep * dist.get(keep, c) + size_gone * dist.get(gone, c))
+                    / (size_keep + size_gone);
+                dist.set(keep, c, merged);
+            }
         }
+        let gone_members = std::mem::take(&mut members[gone]);
+        members[keep].extend(gone_members);
     }
 
-    sum_squared.sqrt()
+    active
+        .first()
+        .map_or_else(Vec::new, |&root| std::mem::take(&mut members[root]))
+}
+
+/// Find the medoid tree: the one with the smallest summed distance to all others.
+///
+/// Returns `(index, mean_distance)`, or `None` when `snaps` is empty. Ties resolve to
+/// the lowest index.
+///
+/// # Memory
+/// Only one row sum per tree is kept, so memory is O(n) on top of the snapshots
+/// instead of the O(n²) full matrix. Snapshots only hold the canonical bitsets and
+/// their lengths, which is considerably smaller than the parsed `PhyloTree`s (no node
+/// structs, names or child lists), so callers can drop trees once snapshotted.
+///
+/// The trade-off is that each pair is computed twice (once per row).
+pub fn medoid_from_snapshots<F>(snaps: &[TreeSnapshot], metric: F) -> Option<(usize, f64)>
+where
+    F: Fn(&TreeSnapshot, &TreeSnapshot) -> f64 + Sync,
+{
+    let n = snaps.len();
+    let row_sums: Vec<f64> = (0..n)
+        .into_par_iter()
+        .map(|i| {
+            (0..n)
+                .filter(|&j| j != i)
+                .map(|j| metric(&snaps[i], &snaps[j]))
+                .sum()
+        })
+        .collect();
+
+    let (index, sum) = row_sums
+        .into_iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    let others = n.saturating_sub(1).max(1) as f64;
+    Some((index, sum / others))
+}
+
+/// Snapshot of a Newick tree for the tests below.
+#[cfg(test)]
+fn snapshot(newick: &str, include_trivial: bool) -> TreeSnapshot {
+    TreeSnapshot::from_tree(&PhyloTree::from_newick(newick).unwrap(), include_trivial).unwrap()
 }
 
 #[test]
@@ -232,6 +2054,25 @@ fn robinson_foulds_treedist() {
     }
 }
 
+#[test]
+fn max_rf_bounds() {
+    // 10 taxa, rooted and unrooted resolutions of the first treedist fixture above
+    let rooted = "(A:0.1,(B:0.1,(H:0.1,(D:0.1,(J:0.1,(((G:0.1,E:0.1):0.1,(F:0.1,I:0.1):0.1):0.1,C:0.1):0.1):0.1):0.1):0.1):0.1);";
+    let unrooted = "(A:0.1,B:0.1,(H:0.1,(D:0.1,(J:0.1,(((G:0.1,E:0.1):0.1,(F:0.1,I:0.1):0.1):0.1,C:0.1):0.1):0.1):0.1):0.1);";
+
+    let rooted = snapshot(rooted, false);
+    let unrooted = snapshot(unrooted, false);
+    assert_eq!(rooted.num_leaves, 10);
+    assert_eq!(max_rf(&rooted), 16);
+    assert_eq!(max_rf(&unrooted), 14);
+    // Every split of a binary tree is an internal edge
+    assert_eq!(max_rf(&unrooted), 2 * unrooted.parts.len());
+
+    assert_eq!(max_rf(&snapshot("((A,B),C);", false)), 0);
+    assert_eq!(max_rf(&snapshot("(A,B,C);", false)), 0);
+    assert_eq!(max_rf(&snapshot("(A,B,(C,D));", false)), 2);
+}
+
 #[test]
 // Robinson foulds distances according to
 // https://evolution.genetics.washington.edu/phylip/doc/treedist.html
@@ -426,7 +2267,13 @@ fn weighted_robinson_foulds_treedist() {
         let t0 = PhyloTree::from_newick(trees[i0]).unwrap();
         let t1 = PhyloTree::from_newick(trees[i1]).unwrap();
 
-        assert!((weighted_robinson_foulds(&t0, &t1).unwrap() - rfs[i0][i1]).abs() <= f64::EPSILON)
+        assert!((weighted_robinson_foulds(&t0, &t1).unwrap() - rfs[i0][i1]).abs() <= f64::EPSILON);
+
+        let s0 = TreeSnapshot::from_tree(&t0, true).unwrap();
+        let s1 = TreeSnapshot::from_tree(&t1, true).unwrap();
+        assert!(
+            (lp_branch_score_from_snapshots(&s0, &s1, 1.0) - rfs[i0][i1]).abs() <= f64::EPSILON
+        );
     }
 }
 
@@ -630,6 +2477,815 @@ fn kuhner_felsenstein_treedist() {
             rfs[i0][i1]
         );
 
-        assert_eq!(kuhner_felsenstein(&t0, &t1).unwrap(), rfs[i0][i1])
+        assert_eq!(kuhner_felsenstein(&t0, &t1).unwrap(), rfs[i0][i1]);
+
+        let s0 = TreeSnapshot::from_tree(&t0, true).unwrap();
+        let s1 = TreeSnapshot::from_tree(&t1, true).unwrap();
+        let kf = kf_from_snapshots(&s0, &s1);
+        assert!((kf_squared_from_snapshots(&s0, &s1) - kf * kf).abs() < 1e-12);
+        assert_eq!(lp_branch_score_from_snapshots(&s0, &s1, 2.0), rfs[i0][i1]);
+    }
+}
+
+#[test]
+fn rf_rooted_against_unrooted_trifurcation() {
+    let rooted = snapshot("((A,B),(C,(D,E)));", false);
+    let trifurcation = snapshot("(A,B,(C,(D,E)));", false);
+    assert_eq!(rooted.root_children.len(), 2);
+    assert_eq!(trifurcation.root_children.len(), 3);
+
+    // Same unrooted topology: no root adjustment may be added
+    assert_eq!(rf_from_snapshots(&rooted, &trifurcation), 0);
+    assert_eq!(rf_from_snapshots(&trifurcation, &rooted), 0);
+
+    // One differing split on each side: plain RF 2, not 4
+    let moved = snapshot("(A,C,(B,(D,E)));", false);
+    assert_eq!(rf_from_snapshots(&rooted, &moved), 2);
+}
+
+#[test]
+fn normalized_rf_bounds() {
+    // Unrooted 5-leaf trees: max RF = 2 * 5 - 6 = 4; `b` shares {D,E} with `a`, `c` shares nothing
+    let a = snapshot("(A:1,B:1,(C:1,(D:1,E:1):1):1);", false);
+    let b = snapshot("(A:1,C:1,(B:1,(D:1,E:1):1):1);", false);
+    let c = snapshot("(A:1,D:1,(B:1,(C:1,E:1):1):1);", false);
+    assert_eq!(normalized_rf_from_snapshots(&a, &a), 0.0);
+    assert_eq!(normalized_rf_from_snapshots(&a, &b), 0.5);
+    assert_eq!(normalized_rf_from_snapshots(&a, &c), 1.0);
+
+    // Fewer than 4 leaves: nothing to normalize by
+    let tiny = snapshot("(A:1,B:1,C:1);", false);
+    assert_eq!(normalized_rf_from_snapshots(&tiny, &tiny), 0.0);
+}
+
+#[test]
+fn matching_split_distance() {
+    // A single nearest-neighbor interchange of two leaves: one split changes, by 2 leaves
+    let t0 = snapshot("(A,B,(C,(D,(E,F))));", false);
+    let nni = snapshot("(A,B,(D,(C,(E,F))));", false);
+    assert_eq!(rf_from_snapshots(&t0, &nni), 2);
+    assert_eq!(matching_split_from_snapshots(&t0, &nni), 2.0);
+    assert_eq!(matching_split_from_snapshots(&t0, &t0), 0.0);
+
+    // Moving A from one end of a caterpillar to the other changes all 5 splits,
+    // but 4 of them are fixed by moving A alone; only {A,B} ↔ {A,H} costs 2
+    let before = snapshot("(A,B,(C,(D,(E,(F,(G,H))))));", false);
+    let after = snapshot("(B,C,(D,(E,(F,(G,(H,A))))));", false);
+    let rf = rf_from_snapshots(&before, &after) as f64;
+    let matching = matching_split_from_snapshots(&before, &after);
+    assert_eq!(rf, 10.0);
+    assert_eq!(matching, 6.0);
+}
+
+#[test]
+fn path_difference_four_taxa() {
+    let ab_cd = snapshot("(A,B,(C,D));", false);
+    let ac_bd = snapshot("(A,C,(B,D));", false);
+
+    // See the worked example on `path_difference_from_snapshots`
+    assert_eq!(path_difference_from_snapshots(&ab_cd, &ac_bd), 2.0);
+    assert_eq!(path_difference_from_snapshots(&ab_cd, &ab_cd), 0.0);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "snapshots must share the same leaves")]
+fn path_difference_rejects_other_leaf_sets() {
+    let four = snapshot("(A,B,(C,D));", false);
+    let five = snapshot("(A,B,(C,(D,E)));", false);
+    path_difference_from_snapshots(&four, &five);
+}
+
+#[test]
+fn jaccard_rf_approaches_rf() {
+    let before = snapshot("(A,B,(C,(D,(E,(F,(G,H))))));", false);
+    let after = snapshot("(B,C,(D,(E,(F,(G,(H,A))))));", false);
+    let rf = rf_from_snapshots(&before, &after) as f64;
+
+    // Near-identical splits still score for small k, so JRF stays below RF
+    assert!(jaccard_rf_from_snapshots(&before, &after, 1.0) < rf);
+    let mut previous = 0.0;
+    for k in [1.0, 4.0, 16.0, 64.0] {
+        let jrf = jaccard_rf_from_snapshots(&before, &after, k);
+        assert!(jrf >= previous);
+        previous = jrf;
+    }
+    assert!((jaccard_rf_from_snapshots(&before, &after, 1e6) - rf).abs() < 1e-9);
+    assert_eq!(jaccard_rf_from_snapshots(&before, &before, 1.0), 0.0);
+}
+
+#[test]
+fn triplet_distance_rooted() {
+    let ab = snapshot("(((A:1,B:1):1,C:1):1,D:1);", false);
+    let ac = snapshot("(((A:1,C:1):1,B:1):1,D:1);", false);
+
+    assert_eq!(triplet_distance_from_snapshots(&ab, &ac), Ok(1));
+    assert_eq!(triplet_distance_from_snapshots(&ab, &ab), Ok(0));
+
+    let unrooted = snapshot("(A:1,B:1,(C:1,D:1):1);", false);
+    assert_eq!(
+        triplet_distance_from_snapshots(&ab, &unrooted),
+        Err(DistanceError::Unrooted)
+    );
+}
+
+#[test]
+fn min_cost_assignment_small() {
+    // Greedy would take the 1 at (0, 0) and pay 1 + 10; optimal is 2 + 2
+    let cost = vec![vec![1.0, 2.0], vec![2.0, 10.0]];
+    assert_eq!(min_cost_assignment(&cost), 4.0);
+    assert_eq!(min_cost_assignment(&[]), 0.0);
+}
+
+#[test]
+fn weighted_distances_include_tip_lengths() {
+    // Same topology, only the pendant branch of C differs (1.0 vs 1.5)
+    let t0 = PhyloTree::from_newick("((A:1,B:1):1,(C:1,D:1):1);").unwrap();
+    let t1 = PhyloTree::from_newick("((A:1,B:1):1,(C:1.5,D:1):1);").unwrap();
+
+    assert_eq!(robinson_foulds(&t0, &t1).unwrap(), 0);
+    assert_eq!(weighted_robinson_foulds(&t0, &t1).unwrap(), 0.5);
+    assert_eq!(kuhner_felsenstein(&t0, &t1).unwrap(), 0.5);
+}
+
+#[test]
+fn medoid_from_snapshots_picks_central_tree() {
+    // "center" is one NNI away from "left" and "right", and closest to the outlier
+    let left = "((A:1,B:1):1,(D:1,(C:1,E:1):1):1);";
+    let outlier = "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);";
+    let center = "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);";
+    let right = "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);";
+
+    let snaps: Vec<TreeSnapshot> = [left, outlier, center, right]
+        .iter()
+        .map(|nwk| snapshot(nwk, false))
+        .collect();
+
+    // Row sums: left 10, outlier 16, center 8, right 10
+    let (index, mean) =
+        medoid_from_snapshots(&snaps, |a, b| rf_from_snapshots(a, b) as f64).unwrap();
+    assert_eq!(index, 2);
+    assert!((mean - 8.0 / 3.0).abs() <= f64::EPSILON);
+
+    assert!(medoid_from_snapshots(&[], |a, b| rf_from_snapshots(a, b) as f64).is_none());
+}
+
+#[test]
+fn shared_branch_length_correlation_is_scale_invariant() {
+    // Same topology, every internal branch of `b` is twice as long as in `a`
+    let a = "(A:1,(B:1,(C:1,(D:1,(E:1,F:1):0.5):1.0):1.5):2.0);";
+    let b = "(A:1,(B:1,(C:1,(D:1,(E:1,F:1):1.0):2.0):3.0):4.0);";
+    let snap_a = snapshot(a, false);
+    let snap_b = snapshot(b, false);
+
+    let r = shared_branch_length_correlation(&snap_a, &snap_b).unwrap();
+    assert!((r - 1.0).abs() < 1e-12);
+    assert!(kf_from_snapshots(&snap_a, &snap_b) > 0.0);
+
+    // A tree sharing a single partition has no defined correlation
+    let c = "(A:1,(F:1,(C:1,(D:1,(E:1,B:1):0.5):1.0):1.5):2.0);";
+    let snap_c = snapshot(c, false);
+    assert!(shared_branch_length_correlation(&snap_a, &snap_c).is_none());
+}
+
+#[test]
+fn pairwise_matrix_single_thread_matches_default() {
+    let newicks = [
+        "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+        "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);",
+        "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);",
+        "((A:2,E:1):1,(B:1,(D:1,C:3):1):1);",
+    ];
+    let snaps: Vec<TreeSnapshot> = newicks.iter().map(|nwk| snapshot(nwk, true)).collect();
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    let single = pool.install(|| pairwise_matrix(&snaps, &KuhnerFelsenstein));
+    assert_eq!(single, pairwise_matrix(&snaps, &KuhnerFelsenstein));
+}
+
+#[test]
+fn compute_matrix_reports_progress() {
+    use std::sync::Mutex;
+
+    let newicks = [
+        "((A,B),(C,(D,E)));",
+        "((A,B),(E,(C,D)));",
+        "((A,C),(B,(D,E)));",
+        "((A,E),(B,(D,C)));",
+        "((A,D),(B,(E,C)));",
+    ];
+    let snaps: Vec<TreeSnapshot> = newicks.iter().map(|nwk| snapshot(nwk, false)).collect();
+
+    let calls = Mutex::new(Vec::new());
+    let record = |done: usize, total: usize| calls.lock().unwrap().push((done, total));
+    let matrix = compute_matrix(&snaps, &RobinsonFoulds, Some(&record));
+    assert_eq!(matrix, pairwise_matrix(&snaps, &RobinsonFoulds));
+
+    // One call per non-empty row, ending at all 10 pairs
+    let calls = calls.into_inner().unwrap();
+    assert_eq!(calls.len(), 4);
+    assert!(calls.iter().all(|&(_, total)| total == 10));
+    assert_eq!(calls.iter().map(|&(done, _)| done).max(), Some(10));
+}
+
+#[test]
+fn distances_to_reference_matches_matrix() {
+    let newicks = [
+        "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+        "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);",
+        "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);",
+    ];
+    let snaps: Vec<TreeSnapshot> = newicks.iter().map(|nwk| snapshot(nwk, true)).collect();
+
+    let rf = distances_to_reference(&snaps[0], &snaps, &RobinsonFoulds);
+    assert_eq!(rf[0], 0.0);
+    assert_eq!(rf[1], 2.0);
+
+    let kf = distances_to_reference(&snaps[0], &snaps, &KuhnerFelsenstein);
+    assert_eq!(kf[0], 0.0);
+    let matrix = pairwise_matrix(&snaps, &KuhnerFelsenstein);
+    assert_eq!(kf, matrix[0]);
+}
+
+#[test]
+fn rf_unchanged_by_trivial_splits() {
+    let newicks = [
+        "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+        "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);",
+        "(A:1,B:1,(C:1,(D:1,E:1):1):1);",
+    ];
+    let build = |include_trivial| -> Vec<TreeSnapshot> {
+        newicks
+            .iter()
+            .map(|nwk| snapshot(nwk, include_trivial))
+            .collect()
+    };
+
+    // The Python TreeSet keeps trivial splits so one snapshot set serves every metric
+    assert_eq!(
+        pairwise_matrix(&build(true), &RobinsonFoulds),
+        pairwise_matrix(&build(false), &RobinsonFoulds)
+    );
+}
+
+#[test]
+fn normalized_weighted_rf_ignores_scale() {
+    let tree = "((A:1,B:2):0.5,(C:1.5,(D:1,E:0.25):1):1);";
+    let scaled = "((A:3,B:6):1.5,(C:4.5,(D:3,E:0.75):3):3);";
+    let (a, b) = (snapshot(tree, true), snapshot(scaled, true));
+
+    assert!(weighted_rf_from_snapshots(&a, &b) > 0.0);
+    assert!(weighted_rf_normalized_from_snapshots(&a, &b).abs() < 1e-12);
+
+    // Zero total length is guarded
+    let flat = snapshot("((A:0,B:0):0,(C:0,(D:0,E:0):0):0);", true);
+    assert_eq!(weighted_rf_normalized_from_snapshots(&a, &flat), 0.0);
+
+    // Different topologies stay within [0, 2]
+    let other = snapshot("((A:1,C:2):0.5,(B:1.5,(D:1,E:0.25):1):1);", true);
+    let d = weighted_rf_normalized_from_snapshots(&a, &other);
+    assert!(d > 0.0 && d <= 2.0);
+}
+
+#[test]
+fn dedup_matches_full_matrix() {
+    let newicks = [
+        "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+        "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);",
+        "((B:1,A:1):1,((E:1,D:1):1,C:1):1);", // same topology as tree 0
+        "((A:2,B:1):1,(C:1,(D:1,E:1):1):1);", // same topology as tree 0, other lengths
+        "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);", // exact copy of tree 1
+    ];
+    let snaps: Vec<TreeSnapshot> = newicks.iter().map(|nwk| snapshot(nwk, true)).collect();
+
+    let (reps, groups) = dedup_snapshots(&snaps, false);
+    assert_eq!(reps, vec![0, 1]);
+    assert_eq!(groups, vec![0, 1, 0, 0, 1]);
+    let unique: Vec<TreeSnapshot> = reps.iter().map(|&i| snaps[i].clone()).collect();
+    let full = pairwise_matrix(&snaps, &RobinsonFoulds);
+    assert_eq!(
+        expand_matrix(&pairwise_matrix(&unique, &RobinsonFoulds), &groups).to_dense(),
+        full
+    );
+    assert_eq!(
+        expand_matrix(&compute_sym_matrix(&unique, &RobinsonFoulds, None), &groups).to_dense(),
+        full
+    );
+
+    // Weighted metrics only merge trees with identical lengths
+    let (reps, groups) = dedup_snapshots(&snaps, true);
+    assert_eq!(reps, vec![0, 1, 3]);
+    let unique: Vec<TreeSnapshot> = reps.iter().map(|&i| snaps[i].clone()).collect();
+    assert_eq!(
+        expand_matrix(&pairwise_matrix(&unique, &WeightedRobinsonFoulds), &groups).to_dense(),
+        pairwise_matrix(&snaps, &WeightedRobinsonFoulds)
+    );
+}
+
+#[test]
+fn clustering_info_distance() {
+    let t1 = snapshot("((A,B),(C,(D,(E,F))));", false);
+    let t2 = snapshot("((A,C),(B,(D,(E,F))));", false);
+
+    assert!(clustering_info_distance_from_snapshots(&t1, &t1).abs() < 1e-12);
+    assert!(clustering_info_distance_from_snapshots(&t2, &t2).abs() < 1e-12);
+    let d = clustering_info_distance_from_snapshots(&t1, &t2);
+    assert!(d > 0.0);
+    assert!((d - clustering_info_distance_from_snapshots(&t2, &t1)).abs() < 1e-12);
+
+    // A star has no information: the distance is the other tree's entropy,
+    // here one 2|2 split of 4 leaves = 1 bit
+    let star = snapshot("(A,B,C,D);", false);
+    let quartet = snapshot("((A,B),(C,D));", false);
+    assert!((clustering_info_distance_from_snapshots(&star, &quartet) - 1.0).abs() < 1e-12);
+    assert_eq!(clustering_info_distance_from_snapshots(&star, &star), 0.0);
+
+    // With a single split per tree, conflicting quartets share no information
+    let other = snapshot("((A,C),(B,D));", false);
+    assert!((clustering_info_distance_from_snapshots(&quartet, &other) - 2.0).abs() < 1e-12);
+}
+
+#[test]
+fn sym_matrix_matches_nested_matrix() {
+    let newicks = [
+        "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+        "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);",
+        "((A:1,D:1):1,(C:1,(B:1,E:1):1):1);",
+        "((A:2,B:1):1,(C:1,(D:1,E:3):1):1);",
+    ];
+    let snaps: Vec<TreeSnapshot> = newicks.iter().map(|nwk| snapshot(nwk, true)).collect();
+
+    let n = snaps.len();
+    let mut nested = vec![vec![0.0; n]; n];
+    for (i, j, dist) in compute_pairs(&snaps, &WeightedRobinsonFoulds, None) {
+        nested[i][j] = dist;
+        nested[j][i] = dist;
+    }
+
+    let sym = compute_sym_matrix(&snaps, &WeightedRobinsonFoulds, None);
+    assert_eq!(sym.len(), n);
+    assert_eq!(sym.to_dense(), nested);
+    assert!(sym.pairs().all(|(i, j, dist)| nested[i][j] == dist));
+}
+
+#[test]
+fn generalized_rf_matches_pruned_rf() {
+    // Tree B has the extra taxon F; both keep a basal trifurcation after pruning
+    let tree_a = PhyloTree::from_newick("(A,B,(C,(D,E)));").unwrap();
+    let tree_b = PhyloTree::from_newick("(A,C,(B,(D,(E,F))));").unwrap();
+
+    let mut pruned = vec![tree_a.clone(), tree_b.clone()];
+    crate::utils::prune_to_common_taxa(&mut pruned).unwrap();
+    let expected = robinson_foulds(&pruned[0], &pruned[1]).unwrap();
+
+    assert_eq!(expected, 2);
+    assert_eq!(generalized_rf(&tree_a, &tree_b).unwrap(), expected);
+    assert_eq!(generalized_rf(&tree_b, &tree_a).unwrap(), expected);
+    assert_eq!(generalized_rf(&tree_b, &tree_b).unwrap(), 0);
+
+    // F hangs off the root, so restricting to the shared taxa gives back tree A
+    let tree_c = PhyloTree::from_newick("(A,B,(C,(D,E)),F);").unwrap();
+    assert_eq!(generalized_rf(&tree_a, &tree_c).unwrap(), 0);
+
+    let disjoint = PhyloTree::from_newick("(X,Y,Z);").unwrap();
+    assert!(matches!(
+        generalized_rf(&tree_a, &disjoint),
+        Err(SnapshotError::Tree(TreeError::IsEmpty))
+    ));
+}
+
+#[test]
+fn extend_matrix_matches_full_matrix() {
+    let newicks = [
+        "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+        "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);",
+        "((A:1,D:1):1,(C:1,(B:1,E:1):1):1);",
+        "((A:2,B:1):1,(C:1,(D:1,E:3):1):1);",
+        "((A:1,E:1):1,(C:1,(D:1,B:1):1):1);",
+    ];
+    let snaps: Vec<TreeSnapshot> = newicks.iter().map(|nwk| snapshot(nwk, true)).collect();
+    let (old, new) = snaps.split_at(3);
+
+    let mut matrix = compute_sym_matrix(old, &WeightedRobinsonFoulds, None);
+    extend_matrix(&mut matrix, old, new, &WeightedRobinsonFoulds);
+
+    assert_eq!(
+        matrix,
+        compute_sym_matrix(&snaps, &WeightedRobinsonFoulds, None)
+    );
+}
+
+#[test]
+fn rf_detailed_lists_differing_splits() {
+    let a = snapshot("(A,B,(C,D));", false);
+    let b = snapshot("(A,C,(B,D));", false);
+
+    let detail = rf_detailed_from_snapshots(&a, &b);
+    assert_eq!(detail.distance, rf_from_snapshots(&a, &b));
+    assert_eq!(detail.distance, 2);
+    assert_eq!(detail.only_in_a.len(), 1);
+    assert_eq!(detail.only_in_b.len(), 1);
+    assert_eq!(a.taxa(&detail.only_in_a[0]), vec!["C", "D"]);
+    assert_eq!(b.taxa(&detail.only_in_b[0]), vec!["B", "D"]);
+
+    let same = rf_detailed_from_snapshots(&a, &a);
+    assert_eq!(same.distance, 0);
+    assert!(same.only_in_a.is_empty() && same.only_in_b.is_empty());
+}
+
+#[test]
+fn pairwise_iter_matches_matrix() {
+    let newicks = [
+        "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+        "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);",
+        "((A:1,D:1):1,(C:1,(B:1,E:1):1):1);",
+        "((A:2,B:1):1,(C:1,(D:1,E:3):1):1);",
+    ];
+    let snaps: Vec<TreeSnapshot> = newicks.iter().map(|nwk| snapshot(nwk, true)).collect();
+
+    let mut streamed: Vec<(usize, usize, f64)> =
+        pairwise_iter(&snaps, &WeightedRobinsonFoulds).collect();
+    streamed.sort_by_key(|&(i, j, _)| (i, j));
+
+    let dense = pairwise_matrix(&snaps, &WeightedRobinsonFoulds);
+    let upper: Vec<(usize, usize, f64)> = (0..snaps.len())
+        .tuple_combinations()
+        .map(|(i, j)| (i, j, dense[i][j]))
+        .collect();
+    assert_eq!(streamed, upper);
+    assert_eq!(
+        pairwise_iter(&snaps[..1], &WeightedRobinsonFoulds).count(),
+        0
+    );
+}
+
+#[test]
+fn weighted_rf_tolerance_ignores_jitter() {
+    let a = snapshot("((A:1,B:1):1,(C:1,D:1):1);", true);
+    let b = snapshot("((A:1.0000000001,B:1):1,(C:1,D:0.9999999999):1);", true);
+
+    assert!(weighted_rf_from_snapshots(&a, &b) > 0.0);
+    assert_eq!(weighted_rf_with_tolerance(&a, &b, 1e-6), 0.0);
+    assert_eq!(
+        weighted_rf_with_tolerance(&a, &b, 0.0),
+        weighted_rf_from_snapshots(&a, &b)
+    );
+
+    // Differences above the tolerance and unshared splits still count in full
+    let c = snapshot("((A:1.5,C:1):1,(B:1,D:1):1);", true);
+    assert_eq!(
+        weighted_rf_with_tolerance(&a, &c, 1e-6),
+        weighted_rf_from_snapshots(&a, &c)
+    );
+}
+
+#[test]
+fn cluster_affinity_decreases_with_nni() {
+    let t0 = snapshot("(A,B,(C,(D,(E,F))));", false);
+    let nni = snapshot("(A,B,(D,(C,(E,F))));", false);
+    let two_nni = snapshot("(A,B,(D,(E,(C,F))));", false);
+
+    // Self-affinity is the number of nontrivial splits
+    let self_affinity = cluster_affinity_from_snapshots(&t0, &t0);
+    assert_eq!(self_affinity, 3.0);
+    assert_eq!(cluster_affinity_from_snapshots(&nni, &nni), 3.0);
+
+    let near = cluster_affinity_from_snapshots(&t0, &nni);
+    let far = cluster_affinity_from_snapshots(&t0, &two_nni);
+    assert!(near < self_affinity, "{near}");
+    assert!(far < near, "{far} >= {near}");
+    assert_eq!(near, cluster_affinity_from_snapshots(&nni, &t0));
+
+    // {C,D,E,F} and {E,F} are shared; {D,E,F} best matches {C,D,E,F} (3 of 4 leaves)
+    assert_eq!(near, 2.75);
+}
+
+#[test]
+fn match_edge_lengths_pairs_shared_splits() {
+    let a = snapshot("(A:1,B:2,(C:3,(D:4,E:5):0.5):0.25);", false);
+    let b = snapshot("(A:1,C:3,(B:2,(D:4,E:6):0.75):0.5);", false);
+
+    let matching = match_edge_lengths(&a, &b);
+    let de = matching
+        .shared
+        .iter()
+        .find(|(part, _, _)| part.count_ones() == 2)
+        .unwrap();
+    assert_eq!((de.1, de.2), (0.5, 0.75));
+    assert_eq!(a.taxa(&de.0), ["D", "E"]);
+    assert_eq!(matching.only_in_a.len(), 1);
+    assert_eq!(a.taxa(&matching.only_in_a[0].0), ["C", "D", "E"]);
+    assert_eq!(matching.only_in_a[0].1, 0.25);
+    assert_eq!(b.taxa(&matching.only_in_b[0].0), ["B", "D", "E"]);
+
+    // Aligned lengths add up to weighted RF
+    let total: f64 = matching
+        .shared
+        .iter()
+        .map(|(_, x, y)| (x - y).abs())
+        .chain(matching.only_in_a.iter().map(|(_, x)| *x))
+        .chain(matching.only_in_b.iter().map(|(_, y)| *y))
+        .sum();
+    assert!((total - weighted_rf_from_snapshots(&a, &b)).abs() < 1e-12);
+
+    // A split stored by its other side still pairs up
+    let mut flipped = b.clone();
+    let n = flipped.num_leaves;
+    let (parts, lengths) = std::mem::take(&mut flipped.lengths)
+        .into_iter()
+        .map(|(part, length)| (part.complement(n), (part.complement(n), length)))
+        .unzip();
+    flipped.parts = parts;
+    flipped.lengths = lengths;
+    assert_eq!(match_edge_lengths(&a, &flipped), matching);
+}
+
+#[test]
+fn row_means_of_known_matrix() {
+    // 0 2 4
+    // 2 0 6
+    // 4 6 0
+    let mat = SymMatrix::from_condensed(3, vec![2.0, 4.0, 6.0]);
+    assert_eq!(row_means(&mat), [3.0, 4.0, 5.0]);
+
+    let rf = SymMatrix::from_condensed(3, vec![2usize, 4, 6]);
+    assert_eq!(row_means(&rf), [3.0, 4.0, 5.0]);
+    assert_eq!(row_means(&SymMatrix::<f64>::new(1)), [0.0]);
+    assert!(row_means(&SymMatrix::<f64>::new(0)).is_empty());
+}
+
+#[test]
+fn medoid_index_picks_central_tree() {
+    // "center" is one NNI away from both others, which are two apart
+    let snaps = [
+        snapshot("(A,B,(C,(D,(E,F))));", false),
+        snapshot("(A,B,(D,(C,(E,F))));", false),
+        snapshot("(A,B,(C,(E,(D,F))));", false),
+    ];
+    let mat = compute_sym_matrix(&snaps, &RobinsonFoulds, None);
+    assert_eq!(medoid_index(&mat), 0);
+    assert_eq!(
+        medoid_from_snapshots(&snaps, |a, b| RobinsonFoulds.distance_f64(a, b)).map(|(idx, _)| idx),
+        Some(0)
+    );
+
+    // Ties go to the lowest index
+    assert_eq!(medoid_index(&SymMatrix::from_condensed(3, vec![1.0; 3])), 0);
+    assert_eq!(
+        medoid_index(&SymMatrix::from_condensed(3, vec![5.0, 1.0, 1.0])),
+        2
+    );
+}
+
+#[test]
+fn rf_root_comparison_ignores_child_order() {
+    // Same root split {A,B} | {C,D,E}, children written in opposite order
+    let a = snapshot("((A,B),(C,(D,E)));", false);
+    let b = snapshot("(((E,C),D),(B,A));", false);
+    assert_eq!(rf_from_snapshots(&a, &b), 2);
+
+    // Children listed in reverse, as a hand-built or deserialized snapshot may have them
+    let mut reversed = b.clone();
+    reversed.root_children.reverse();
+    assert_ne!(reversed.root_children, a.root_children);
+    assert_eq!(rf_from_snapshots(&a, &reversed), 2);
+
+    // A genuinely different root split still adds 2: {C,D,E} vs {B,C} differ, {D,E} is shared
+    let moved_root = snapshot("((A,(B,C)),(D,E));", false);
+    assert_eq!(rf_from_snapshots(&a, &moved_root), 2 + 2);
+}
+
+#[test]
+fn pairwise_subset_matches_full_matrix() {
+    let snaps = [
+        snapshot("((A:1,B:2):1,(C:1,(D:1,E:3):2):1);", false),
+        snapshot("((A:1,C:2):1,(B:1,(D:1,E:3):2):1);", false),
+        snapshot("((A:2,B:2):1,(C:1,(D:2,E:1):1):1);", false),
+        snapshot("((A:1,E:2):1,(C:1,(D:1,B:3):2):1);", false),
+        snapshot("((A:1,B:1):2,(E:1,(D:1,C:1):1):1);", false),
+    ];
+    let full = compute_sym_matrix(&snaps, &WeightedRobinsonFoulds, None);
+    let indices = [4, 1, 3];
+    let subset = pairwise_subset(&snaps, &indices, &WeightedRobinsonFoulds).unwrap();
+
+    assert_eq!(subset.len(), indices.len());
+    for (a, &i) in indices.iter().enumerate() {
+        for (b, &j) in indices.iter().enumerate() {
+            assert_eq!(subset.get(a, b), full.get(i, j), "entry ({a}, {b})");
+        }
+    }
+
+    assert_eq!(
+        pairwise_subset(&snaps, &[0, 5], &RobinsonFoulds).unwrap_err(),
+        DistanceError::IndexOutOfRange { index: 5, len: 5 }
+    );
+    assert_eq!(
+        pairwise_subset(&snaps, &[2, 0, 2], &RobinsonFoulds).unwrap_err(),
+        DistanceError::DuplicateIndex { index: 2 }
+    );
+}
+
+#[test]
+fn weighted_rf_components_split_shared_and_unique() {
+    // {A,B} is shared with lengths 1.5 and 0.5; {D,E} (2) and {C,D} (3) are unique
+    let a = snapshot("(A:1,B:1,(C:1,(D:1,E:1):2):1.5);", false);
+    let b = snapshot("(A:1,B:1,(E:1,(C:1,D:1):3):0.5);", false);
+
+    let components = weighted_rf_components_from_snapshots(&a, &b);
+    assert_eq!(
+        components,
+        WrfComponents {
+            shared_diff: 1.0,
+            unique_a: 2.0,
+            unique_b: 3.0,
+        }
+    );
+    assert_eq!(components.total(), weighted_rf_from_snapshots(&a, &b));
+}
+
+#[test]
+fn rf_modes_control_the_rooted_adjustment() {
+    let tree = |nwk: &str| PhyloTree::from_newick(nwk).unwrap();
+    // Root splits {A,B} | {C,D,E} and {A,B,C} | {D,E}; unrooted RF is 2
+    let (tree_a, tree_b) = (tree("((A,B),(C,(D,E)));"), tree("((A,(B,C)),(D,E));"));
+
+    let rooted_a = TreeSnapshot::from_tree(&tree_a, false).unwrap();
+    let rooted_b = TreeSnapshot::from_tree(&tree_b, false).unwrap();
+    assert!(rooted_a.rooted && rooted_b.rooted);
+    assert_eq!(
+        rf_from_snapshots_mode(&rooted_a, &rooted_b, RfMode::Auto),
+        4
+    );
+    assert_eq!(rf_from_snapshots(&rooted_a, &rooted_b), 4);
+    assert_eq!(
+        rf_from_snapshots_mode(&rooted_a, &rooted_b, RfMode::ForceRooted),
+        4
+    );
+    assert_eq!(
+        rf_from_snapshots_mode(&rooted_a, &rooted_b, RfMode::ForceUnrooted),
+        2
+    );
+
+    // The same trees flagged unrooted (as by a [&U] marker)
+    let unrooted = crate::snapshot::SnapshotOptions {
+        rooted: Some(false),
+        ..Default::default()
+    };
+    let unrooted_a = TreeSnapshot::from_tree_with(&tree_a, false, unrooted).unwrap();
+    let unrooted_b = TreeSnapshot::from_tree_with(&tree_b, false, unrooted).unwrap();
+    assert_eq!(
+        rf_from_snapshots_mode(&unrooted_a, &unrooted_b, RfMode::Auto),
+        2
+    );
+    assert_eq!(
+        rf_from_snapshots_mode(&unrooted_a, &unrooted_b, RfMode::ForceRooted),
+        4
+    );
+    assert_eq!(
+        rf_from_snapshots_mode(&unrooted_a, &unrooted_b, RfMode::ForceUnrooted),
+        2
+    );
+    assert_eq!(
+        RobinsonFouldsWithMode {
+            mode: RfMode::ForceRooted
+        }
+        .distance(&unrooted_a, &unrooted_b),
+        4
+    );
+}
+
+#[test]
+fn lp_branch_score_interpolates_between_norms() {
+    // Shared {A,B} differs by 1, {D,E} (2) and {C,D} (3) are unique; tips are equal
+    let a = snapshot("(A:1,B:1,(C:1,(D:1,E:1):2):1.5);", true);
+    let b = snapshot("(A:1,B:1,(E:1,(C:1,D:1):3):0.5);", true);
+
+    assert_eq!(lp_branch_score_from_snapshots(&a, &b, 1.0), 6.0);
+    assert_eq!(lp_branch_score_from_snapshots(&a, &b, 2.0), 14f64.sqrt());
+    assert_eq!(lp_branch_score_from_snapshots(&a, &b, f64::INFINITY), 3.0);
+    let p3 = lp_branch_score_from_snapshots(&a, &b, 3.0);
+    assert!((p3 - 36f64.cbrt()).abs() < 1e-12);
+    assert_eq!(
+        LpBranchScore {
+            p: 1.0,
+            ..Default::default()
+        }
+        .distance(&a, &b),
+        weighted_rf_from_snapshots(&a, &b)
+    );
+}
+
+#[test]
+fn cluster_order_groups_blocks() {
+    // Even rows form one tight block, odd rows another; input order interleaves them
+    let n = 8;
+    let mut mat = SymMatrix::new(n);
+    for i in 0..n {
+        for j in i + 1..n {
+            let dist = if i % 2 == j % 2 {
+                1.0 + (i + j) as f64 * 0.01
+            } else {
+                10.0
+            };
+            mat.set(i, j, dist);
+        }
     }
+
+    let order = cluster_order(&mat);
+    let mut sorted = order.clone();
+    sorted.sort_unstable();
+    assert_eq!(sorted, (0..n).collect::<Vec<_>>());
+    // Each block is contiguous: the parity changes exactly once along the order
+    let switches = order.windows(2).filter(|w| w[0] % 2 != w[1] % 2).count();
+    assert_eq!(switches, 1, "order {order:?}");
+    assert_eq!(order[0], 0);
+
+    let reordered = mat.permuted(&order);
+    assert_eq!(reordered.get(0, 1), mat.get(order[0], order[1]));
+
+    assert!(cluster_order(&SymMatrix::<f64>::new(0)).is_empty());
+    assert_eq!(cluster_order(&SymMatrix::<usize>::new(1)), [0]);
+}
+
+#[test]
+fn cross_matrix_of_a_set_with_itself_is_the_pairwise_matrix() {
+    let snaps: Vec<TreeSnapshot> = [
+        "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+        "((A:1,C:1):2,(B:1,(D:1,E:1):1):1);",
+        "((A:1,D:1):1,(C:1,(B:1,E:1):3):1);",
+    ]
+    .iter()
+    .map(|nwk| snapshot(nwk, true))
+    .collect();
+
+    let cross = cross_matrix(&snaps, &snaps, &KuhnerFelsenstein);
+    let square = pairwise_matrix(&snaps, &KuhnerFelsenstein);
+    assert_eq!(cross, square);
+    assert!((0..snaps.len()).all(|i| cross[i][i] == 0.0));
+
+    let rf = cross_matrix(&snaps[..1], &snaps[1..], &RobinsonFoulds);
+    assert_eq!(rf.len(), 1);
+    assert_eq!(rf[0].len(), 2);
+    assert_eq!(rf[0][1], rf_from_snapshots(&snaps[0], &snaps[2]) as f64);
+}
+
+#[test]
+fn root_edges_count_in_rooted_weighted_metrics() {
+    // Rooted chronograms that differ only in the branch above one root child: both
+    // root edges form the same unrooted partition, which keeps a single length
+    let a = snapshot("((A:1,B:1):1,(C:1,D:1):2);", true);
+    let b = snapshot("((A:1,B:1):1,(C:1,D:1):3);", true);
+    let c = snapshot("((A:1,B:1):2,(C:1,D:1):2);", true);
+    assert!(a.rooted && b.rooted);
+
+    assert_eq!(weighted_rf_with_root_edges(&a, &b), 1.0);
+    assert_eq!(kf_with_root_edges(&a, &b), 1.0);
+    assert_eq!(weighted_rf_with_root_edges(&a, &c), 1.0);
+    assert_eq!(weighted_rf_with_root_edges(&a, &a), 0.0);
+    // Without the option one of the two differences is always lost
+    assert_eq!(
+        weighted_rf_from_snapshots(&a, &b) + weighted_rf_from_snapshots(&a, &c),
+        1.0
+    );
+    let rooted_kf = LpBranchScore {
+        p: 2.0,
+        include_root_edges: true,
+    };
+    assert_eq!(rooted_kf.distance(&a, &b), 1.0);
+
+    // A different root split compares the root edges as unmatched clades
+    let moved = snapshot("((A:1,C:1):1,(B:1,D:1):2);", true);
+    assert_eq!(
+        weighted_rf_with_root_edges(&a, &moved),
+        1.0 + 2.0 + 1.0 + 2.0
+    );
+
+    // Unrooted trees are unaffected
+    let u1 = snapshot("(A:1,B:1,(C:1,D:1):2);", true);
+    let u2 = snapshot("(A:1,B:1,(C:1,D:1):3);", true);
+    assert_eq!(
+        weighted_rf_with_root_edges(&u1, &u2),
+        weighted_rf_from_snapshots(&u1, &u2)
+    );
+}
+
+#[test]
+fn rf_by_size_bins_small_disagreements() {
+    // The trees only disagree on which pair forms a cherry inside {A, B, C}
+    let a = snapshot("(((A,B),C),D,((E,F),(G,H)));", false);
+    let b = snapshot("(((A,C),B),D,((E,F),(G,H)));", false);
+    assert_eq!(rf_from_snapshots(&a, &b), 2);
+    assert_eq!(rf_by_size_from_snapshots(&a, &b, &[2, 3]), vec![2, 0, 0]);
+    assert_eq!(rf_by_size_from_snapshots(&a, &b, &[1]), vec![0, 2]);
+    assert_eq!(rf_by_size_from_snapshots(&a, &b, &[]), vec![2]);
+
+    // The rooted adjustment goes to the bins of both root splits
+    let r1 = snapshot("(((A,B),(C,D)),((E,F),(G,H)));", false);
+    let r2 = snapshot("((((A,B),(C,D)),(E,G)),(F,H));", false);
+    let bins = rf_by_size_from_snapshots(&r1, &r2, &[2, 3]);
+    assert_eq!(bins.iter().sum::<usize>(), rf_from_snapshots(&r1, &r2));
+    assert_eq!(bins, vec![5, 0, 1]);
 }
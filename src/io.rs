@@ -1,18 +1,24 @@
-use phylotree::tree::Tree;
-use std::collections::HashMap;
+use phylotree::tree::{NewickParseError, Tree};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::Path;
 
 use flate2::Compression;
+use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 
+use crate::matrix::MatrixRows;
+use crate::utils::capped_list;
+
 /// Strip BEAST annotations from Newick strings.
 ///
 /// BEAST format includes annotations like :[&rate=0.123]2.45 where 2.45 is the actual branch length.
 /// This function removes the [&...] annotations while preserving the branch lengths.
 /// We shouldn't be needing, this TODO: update phylotree to handle BEAST annotations directly.
-fn strip_beast_annotations(newick: &str) -> String {
+pub(crate) fn strip_beast_annotations(newick: &str) -> String {
     let mut result = String::with_capacity(newick.len());
     let mut in_annotation = false;
     let mut chars = newick.chars().peekable();
@@ -33,71 +39,795 @@ fn strip_beast_annotations(newick: &str) -> String {
     result
 }
 
+/// Errors from reading a BEAST/NEXUS tree file.
+#[derive(Debug)]
+pub enum ReadError {
+    /// The file could not be opened, read or decompressed.
+    Io(io::Error),
+    /// The tree block at `index` (0-based, before burn-in) is not valid Newick.
+    Parse {
+        index: usize,
+        source: NewickParseError,
+    },
+    /// With `use_real_taxa`, the tree at `index` has tip labels without a TRANSLATE entry.
+    Untranslated { index: usize, ids: Vec<String> },
+    /// No trees are left after burn-in removal.
+    NoTrees,
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "failed to read file: {e}"),
+            ReadError::Parse { index, source } => {
+                write!(f, "failed to parse tree at index {index}: {source}")
+            }
+            ReadError::Untranslated { index, ids } => {
+                let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+                write!(
+                    f,
+                    "tree at index {index} has tip labels missing from the TRANSLATE block: {}",
+                    capped_list(&ids)
+                )
+            }
+            ReadError::NoTrees => write!(f, "no trees found after burn-in removal"),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(e) => Some(e),
+            ReadError::Parse { source, .. } => Some(source),
+            ReadError::Untranslated { .. } | ReadError::NoTrees => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+/// Trees paired with their names (`<file_basename>_tree_STATE<state>`).
+pub type NamedTrees = Vec<(String, Tree)>;
+
+/// The tree name template used unless another is given, see [`tree_name`].
+pub const DEFAULT_NAME_TEMPLATE: &str = "{base}_tree_STATE{state}";
+
+/// Name a tree by filling in the placeholders of `template`.
+///
+/// - `{base}`: the file name without `.gz` and `.trees`
+/// - `{index}`: the tree's 0-based position in the file, burn-in trees included
+/// - `{state}`: the `STATE_` number, 0 when the header has none
+///
+/// Any other text, braces included, is kept as is.
+///
+/// # Example
+/// ```
+/// # use rust_python_tree_distances::io::{DEFAULT_NAME_TEMPLATE, tree_name};
+/// assert_eq!(tree_name(DEFAULT_NAME_TEMPLATE, "run", 3, 3000), "run_tree_STATE3000");
+/// assert_eq!(tree_name("{base}.{index}", "run", 3, 3000), "run.3");
+/// ```
+pub fn tree_name(template: &str, base: &str, index: usize, state: usize) -> String {
+    template
+        .replace("{base}", base)
+        .replace("{index}", &index.to_string())
+        .replace("{state}", &state.to_string())
+}
+
+/// How many trees of a file were parsed and which were skipped as malformed.
+///
+/// Only trees kept after burn-in are counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseSummary {
+    /// Number of trees parsed successfully
+    pub parsed: usize,
+    /// Indices (0-based, before burn-in) of the trees that failed to parse
+    pub failed: Vec<usize>,
+    /// The `STATE_` number of each parsed tree, in the order the trees are returned
+    pub states: Vec<usize>,
+}
+
+impl ParseSummary {
+    /// Number of malformed trees that were skipped.
+    pub fn skipped(&self) -> usize {
+        self.failed.len()
+    }
+}
+
+/// Read trees from a BEAST/NEXUS file, skipping trees that fail to parse.
+///
+/// Skipped trees are reported on stderr; use [`read_beast_trees_with`] to count them
+/// or, with `strict = true`, to turn them into an error instead.
+///
+/// # Errors
+/// - [`ReadError::Io`] if the file cannot be read
+/// - [`ReadError::Untranslated`] if `use_real_taxa` is set and a tip label has no
+///   TRANSLATE entry
+/// - [`ReadError::NoTrees`] if no parsable trees remain after burn-in
 pub fn read_beast_trees<P: AsRef<Path>>(
     path: P,
     burnin_trees: usize,
     burnin_states: usize,
     use_real_taxa: bool,
-) -> (HashMap<String, String>, Vec<(String, Tree)>) {
-    let content = match fs::read_to_string(path.as_ref()) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Failed to read {:?}: {e}", path.as_ref());
-            return (HashMap::new(), Vec::new());
+) -> Result<(HashMap<String, String>, NamedTrees), ReadError> {
+    read_beast_trees_with(
+        path,
+        burnin_trees,
+        burnin_states,
+        use_real_taxa,
+        false,
+        DEFAULT_NAME_TEMPLATE,
+    )
+    .map(|(taxons, trees, _)| (taxons, trees))
+}
+
+/// Read trees from a BEAST/NEXUS file, choosing how to handle unparsable trees.
+///
+/// All trees are attempted and parse failures are collected. With `strict`, the
+/// first failure is returned as [`ReadError::Parse`]; otherwise failures are
+/// reported on stderr and the remaining trees are returned along with a
+/// [`ParseSummary`] of what was skipped.
+///
+/// Trees are named with `name_template`, see [`tree_name`];
+/// [`DEFAULT_NAME_TEMPLATE`] gives the names of [`read_beast_trees`].
+///
+/// This collects [`iter_beast_trees`]; use the iterator directly to avoid holding
+/// every parsed tree in memory.
+pub fn read_beast_trees_with<P: AsRef<Path>>(
+    path: P,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+    strict: bool,
+    name_template: &str,
+) -> Result<(HashMap<String, String>, NamedTrees, ParseSummary), ReadError> {
+    let iter = iter_beast_trees(&path, burnin_trees, burnin_states, use_real_taxa)?
+        .with_name_template(name_template);
+    collect_trees(iter, strict, &path.as_ref().display().to_string())
+}
+
+/// Parse BEAST/NEXUS content already in memory, e.g. received over the network.
+///
+/// Trees are named `<base_name>_tree_STATE<state>`, as if read from a file called
+/// `<base_name>.trees`; burn-in, TRANSLATE handling and skipping of unparsable trees
+/// follow [`read_beast_trees`], which reads files through the same parser.
+///
+/// # Example
+/// ```
+/// # use rust_python_tree_distances::io::parse_beast_trees_from_str;
+/// let nexus = "#NEXUS\nBegin trees;\ntree STATE_0 = ((A,B),(C,D));\nEnd;\n";
+/// let (_, trees) = parse_beast_trees_from_str(nexus, "run", 0, 0, false)?;
+/// assert_eq!(trees[0].0, "run_tree_STATE0");
+/// # Ok::<(), rust_python_tree_distances::io::ReadError>(())
+/// ```
+///
+/// # Errors
+/// As [`read_beast_trees`], without the file errors.
+pub fn parse_beast_trees_from_str(
+    content: &str,
+    base_name: &str,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+) -> Result<(HashMap<String, String>, NamedTrees), ReadError> {
+    let iter = iter_beast_trees_from_reader(
+        content.as_bytes(),
+        base_name,
+        burnin_trees,
+        burnin_states,
+        use_real_taxa,
+    )?;
+    collect_trees(iter, false, base_name).map(|(taxons, trees, _)| (taxons, trees))
+}
+
+/// Collect every tree of `iter`, reporting parse failures on stderr under `source` or,
+/// with `strict`, returning the first of them.
+fn collect_trees(
+    iter: BeastTreeIter<'_>,
+    strict: bool,
+    source: &str,
+) -> Result<(HashMap<String, String>, NamedTrees, ParseSummary), ReadError> {
+    let taxons = iter.taxons().clone();
+
+    let mut iter = iter;
+    let mut failures: Vec<ReadError> = Vec::new();
+    let mut trees = Vec::new();
+    let mut states = Vec::new();
+    while let Some(item) = iter.next_with_state() {
+        match item {
+            Ok((name, state, tree)) => {
+                trees.push((name, tree));
+                states.push(state);
+            }
+            Err(e @ ReadError::Parse { .. }) => failures.push(e),
+            Err(e) => return Err(e),
         }
+    }
+
+    if strict && !failures.is_empty() {
+        return Err(failures.swap_remove(0));
+    }
+    for failure in &failures {
+        eprintln!("Skipping tree in {source}: {failure}");
+    }
+
+    if trees.is_empty() {
+        return Err(ReadError::NoTrees);
+    }
+
+    let summary = ParseSummary {
+        parsed: trees.len(),
+        failed: failures
+            .iter()
+            .filter_map(|failure| match failure {
+                ReadError::Parse { index, .. } => Some(*index),
+                _ => None,
+            })
+            .collect(),
+        states,
     };
+    Ok((taxons, trees, summary))
+}
+
+/// Read several BEAST/NEXUS files with [`read_beast_trees`] and merge their trees in
+/// file order.
+///
+/// Each tree name is prefixed with `file<N>_`, `N` being the file's position in
+/// `paths`, so chains written under the same basename stay distinguishable. The
+/// TRANSLATE maps are merged; a taxon ID listed by several files keeps the label of
+/// the last one.
+///
+/// # Errors
+/// The position in `paths` and the error of the first file that cannot be read.
+pub fn read_beast_tree_files<P: AsRef<Path>>(
+    paths: &[P],
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+) -> Result<(HashMap<String, String>, NamedTrees), (usize, ReadError)> {
+    read_beast_tree_files_with(
+        paths,
+        burnin_trees,
+        burnin_states,
+        use_real_taxa,
+        false,
+        DEFAULT_NAME_TEMPLATE,
+    )
+    .map(|(taxons, trees, _)| (taxons, trees))
+}
+
+/// Merged TRANSLATE map, named trees and one [`ParseSummary`] per file.
+pub type MergedTrees = (HashMap<String, String>, NamedTrees, Vec<ParseSummary>);
+
+/// [`read_beast_tree_files`] with the `strict` and `name_template` options of
+/// [`read_beast_trees_with`], also returning one [`ParseSummary`] per file.
+///
+/// The `file<N>_` prefix is only added with [`DEFAULT_NAME_TEMPLATE`]; any other
+/// template names the trees on its own, so include `{base}` when files may repeat
+/// indices or states.
+///
+/// # Errors
+/// The position in `paths` and the error of the first file that cannot be read.
+pub fn read_beast_tree_files_with<P: AsRef<Path>>(
+    paths: &[P],
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+    strict: bool,
+    name_template: &str,
+) -> Result<MergedTrees, (usize, ReadError)> {
+    merge_tree_files(paths, name_template, |path| {
+        read_beast_trees_with(
+            path,
+            burnin_trees,
+            burnin_states,
+            use_real_taxa,
+            strict,
+            name_template,
+        )
+    })
+}
+
+/// [`read_beast_tree_files_with`] dropping the first `floor(fraction · total)` trees
+/// of each file, the usual BEAST burn-in (e.g. `fraction = 0.1` for 10%).
+///
+/// Each file is read twice: once to count its trees with [`count_tree_blocks`], then
+/// to parse the trees after the burn-in.
+///
+/// # Errors
+/// The position in `paths` and the error of the first file that cannot be read or
+/// has no trees left after burn-in.
+pub fn read_beast_tree_files_fraction<P: AsRef<Path>>(
+    paths: &[P],
+    fraction: f64,
+    use_real_taxa: bool,
+    strict: bool,
+    name_template: &str,
+) -> Result<MergedTrees, (usize, ReadError)> {
+    merge_tree_files(paths, name_template, |path| {
+        let burnin_trees = burnin_trees_for_fraction(path, fraction)?;
+        read_beast_trees_with(path, burnin_trees, 0, use_real_taxa, strict, name_template)
+    })
+}
+
+/// Number of leading trees to drop from a file for a burn-in `fraction` in `[0, 1]`:
+/// `floor(fraction · total)`, `total` being [`count_tree_blocks`].
+///
+/// # Errors
+/// Returns [`ReadError::Io`] if the file cannot be read.
+pub fn burnin_trees_for_fraction<P: AsRef<Path>>(
+    path: P,
+    fraction: f64,
+) -> Result<usize, ReadError> {
+    let total = count_tree_blocks(path)?;
+    Ok((fraction * total as f64).floor() as usize)
+}
+
+/// Count the `tree` lines of a BEAST/NEXUS file without parsing them, or the
+/// nonblank lines of a plain Newick file (see [`iter_beast_trees`]).
+///
+/// Malformed trees are counted too, matching the indices that `burnin_trees`
+/// refers to.
+///
+/// # Errors
+/// Returns [`ReadError::Io`] if the file cannot be read.
+pub fn count_tree_blocks<P: AsRef<Path>>(path: P) -> Result<usize, ReadError> {
+    let mut count = 0;
+    let mut plain = None;
+    for line in open_maybe_gz(path.as_ref())?.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // The first nonblank line decides the layout, as in `iter_beast_trees`
+        let plain = *plain.get_or_insert_with(|| is_newick_line(&line));
+        if plain || split_tree_line(&line).is_some() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Read every file with `read_one` and merge the results as in
+/// [`read_beast_tree_files_with`].
+fn merge_tree_files<P, F>(
+    paths: &[P],
+    name_template: &str,
+    read_one: F,
+) -> Result<MergedTrees, (usize, ReadError)>
+where
+    P: AsRef<Path>,
+    F: Fn(&Path) -> Result<(HashMap<String, String>, NamedTrees, ParseSummary), ReadError>,
+{
+    let mut all_taxons = HashMap::new();
+    let mut all_trees = Vec::new();
+    let mut summaries = Vec::with_capacity(paths.len());
+
+    for (file_idx, path) in paths.iter().enumerate() {
+        let (taxons, named_trees, summary) = read_one(path.as_ref()).map_err(|e| (file_idx, e))?;
+        summaries.push(summary);
+        all_taxons.extend(taxons);
+        all_trees.extend(
+            named_trees
+                .into_iter()
+                .map(|(name, tree)| (file_tree_name(file_idx, name, name_template), tree)),
+        );
+    }
+
+    Ok((all_taxons, all_trees, summaries))
+}
+
+/// Prefix `name` with `file<N>_` if it was built from [`DEFAULT_NAME_TEMPLATE`], as
+/// [`read_beast_tree_files_with`] names the trees of its `N`-th file.
+pub fn file_tree_name(file_idx: usize, name: String, name_template: &str) -> String {
+    if name_template == DEFAULT_NAME_TEMPLATE {
+        format!("file{file_idx}_{name}")
+    } else {
+        name
+    }
+}
+
+/// Taxon labels plus the name and `STATE_` number of every tree kept after burn-in.
+pub type TreeStates = (Vec<String>, Vec<(String, usize)>);
+
+/// Read the taxon labels and per-tree states of several BEAST/NEXUS files.
+///
+/// Trees are named and filtered exactly as in [`read_beast_tree_files_with`] with the
+/// same `name_template`, so the states line up with the rows of a matrix built from
+/// the same files. Taxon labels
+/// are the sorted, deduplicated TRANSLATE labels of all files (empty without a
+/// TRANSLATE block). Unparsable trees are skipped with a warning on stderr.
+///
+/// # Errors
+/// The position in `paths` and the error of the first file that cannot be read or
+/// has no trees left after burn-in.
+pub fn read_tree_states<P: AsRef<Path>>(
+    paths: &[P],
+    burnin_trees: usize,
+    burnin_states: usize,
+    name_template: &str,
+) -> Result<TreeStates, (usize, ReadError)> {
+    let mut labels = BTreeSet::new();
+    let mut states = Vec::new();
+
+    for (file_idx, path) in paths.iter().enumerate() {
+        let mut iter = iter_beast_trees(path, burnin_trees, burnin_states, false)
+            .map_err(|e| (file_idx, e))?
+            .with_name_template(name_template);
+        labels.extend(iter.taxons().values().cloned());
+
+        let before = states.len();
+        while let Some(item) = iter.next_with_state() {
+            match item {
+                Ok((name, state, _)) => {
+                    states.push((file_tree_name(file_idx, name, name_template), state))
+                }
+                Err(e @ ReadError::Parse { .. }) => {
+                    eprintln!("Skipping tree in {}: {e}", path.as_ref().display())
+                }
+                Err(e) => return Err((file_idx, e)),
+            }
+        }
+        if states.len() == before {
+            return Err((file_idx, ReadError::NoTrees));
+        }
+    }
+
+    Ok((labels.into_iter().collect(), states))
+}
+
+/// Open a BEAST/NEXUS file for streaming, one tree at a time.
+///
+/// The header and TRANSLATE block are read immediately; tree lines are only read,
+/// filtered by burn-in and parsed as the iterator advances, so memory stays bounded
+/// by a single tree regardless of file size. Burn-in and naming follow
+/// [`read_beast_trees`].
+///
+/// A file whose first nonblank line is a bare Newick tree (no `#NEXUS` header,
+/// `tree` or TRANSLATE keywords) is read as plain Newick instead: every nonblank line
+/// is one tree, named `<file_basename>_tree_<index>` with state 0. There is no
+/// TRANSLATE map, so `use_real_taxa` leaves the tip labels as they are.
+///
+/// # Example
+/// ```no_run
+/// # use rust_python_tree_distances::io::iter_beast_trees;
+/// # use rust_python_tree_distances::snapshot::TreeSnapshot;
+/// for item in iter_beast_trees("run.trees", 100, 0, false)? {
+///     let (name, tree) = item?;
+///     let snap = TreeSnapshot::from_tree(&tree, false)?;
+///     // `tree` is dropped here; only the snapshot is kept
+/// #   let _ = (name, snap);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// # Errors
+/// Returns [`ReadError::Io`] if the file cannot be opened or its header read. Items
+/// are [`ReadError::Parse`] for trees that are not valid Newick or
+/// [`ReadError::Untranslated`] for trees with tips missing from the TRANSLATE block
+/// (iteration continues), or [`ReadError::Io`] for read failures (iteration stops).
+pub fn iter_beast_trees<P: AsRef<Path>>(
+    path: P,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+) -> Result<BeastTreeIter<'static>, ReadError> {
+    let path = path.as_ref();
+    let reader = open_maybe_gz(path)?;
 
     let base_name = path
-        .as_ref()
         .file_name()
         .and_then(|s| s.to_str())
-        .map(|s| s.trim_end_matches(".trees"))
+        .map(|s| s.trim_end_matches(".gz").trim_end_matches(".trees"))
         .unwrap_or("unknown");
 
-    let taxons = parse_taxon_block(&content);
-
-    let trees = collect_tree_blocks(&content)
-        .into_iter()
-        .enumerate()
-        //generate tree name & extract state number
-        .map(|(idx, tree)| {
-            let state = extract_state(tree.header);
-            (idx, tree, state, format!("{base_name}_tree_STATE{state}"))
-        })
-        // Filter out burn-in trees based on count and/or state number if 0 we don't filter
-        .filter(|(idx, _tree, state, _name)| {
-            (burnin_trees == 0 && burnin_states == 0)
-                || (burnin_trees > 0 && *idx >= burnin_trees)
-                || (burnin_states > 0 && *state > burnin_states)
-        })
-        // read in the files
-        .filter_map(|(idx, tree, _state, name)| {
+    iter_beast_trees_from_reader(
+        reader,
+        base_name,
+        burnin_trees,
+        burnin_states,
+        use_real_taxa,
+    )
+}
+
+/// Stream the trees of BEAST/NEXUS content from any buffered reader, see
+/// [`iter_beast_trees`]; trees are named after `base_name`.
+///
+/// # Errors
+/// Returns [`ReadError::Io`] if the header cannot be read; items as in
+/// [`iter_beast_trees`].
+pub fn iter_beast_trees_from_reader<'a, R: BufRead + 'a>(
+    mut reader: R,
+    base_name: &str,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+) -> Result<BeastTreeIter<'a>, ReadError> {
+    // Read up to the first tree line, collecting the TRANSLATE block on the way
+    let mut taxons = HashMap::new();
+    let mut in_translate = false;
+    let mut translate_done = false;
+    let mut pending = None;
+    let mut plain = false;
+    let mut seen_content = false;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let upper = line.trim().to_ascii_uppercase();
+        if split_tree_line(&line).is_some() {
+            pending = Some(std::mem::take(&mut line));
+            break;
+        }
+        // Without any NEXUS structure before it, a Newick line starts a plain file
+        if !seen_content && !upper.is_empty() {
+            seen_content = true;
+            if is_newick_line(&line) {
+                plain = true;
+                pending = Some(std::mem::take(&mut line));
+                break;
+            }
+        }
+        if in_translate {
+            if upper.starts_with(';') {
+                in_translate = false;
+                translate_done = true;
+            } else if let Some((id, label)) = parse_translate_line(&line) {
+                taxons.insert(id, label);
+            }
+        } else if !translate_done && upper.starts_with("TRANSLATE") {
+            in_translate = true;
+        }
+        line.clear();
+    }
+
+    Ok(BeastTreeIter {
+        reader: Box::new(reader),
+        pending,
+        done: false,
+        plain,
+        taxons,
+        base_name: base_name.to_string(),
+        name_template: DEFAULT_NAME_TEMPLATE.to_string(),
+        index: 0,
+        burnin_trees,
+        burnin_states,
+        use_real_taxa,
+    })
+}
+
+/// A tree read from a BEAST/NEXUS file, see [`BeastTreeIter::next_parsed`].
+#[derive(Debug)]
+pub struct ParsedTree {
+    /// `<file_basename>_tree_STATE<state>` unless another template was set, see
+    /// [`BeastTreeIter::with_name_template`]
+    pub name: String,
+    /// The `STATE_` number, 0 when the header has none
+    pub state: usize,
+    /// `Some(true)` for a `[&R]` marker, `Some(false)` for `[&U]`, see [`rooted_marker`]
+    pub rooted: Option<bool>,
+    /// The parsed tree, with BEAST annotations removed
+    pub tree: Tree,
+}
+
+/// Streaming iterator over the trees of a BEAST/NEXUS file, see [`iter_beast_trees`].
+pub struct BeastTreeIter<'a> {
+    reader: Box<dyn BufRead + 'a>,
+    pending: Option<String>,
+    done: bool,
+    /// One bare Newick tree per line instead of NEXUS `tree` lines
+    plain: bool,
+    taxons: HashMap<String, String>,
+    base_name: String,
+    name_template: String,
+    index: usize,
+    burnin_trees: usize,
+    burnin_states: usize,
+    use_real_taxa: bool,
+}
+
+impl BeastTreeIter<'_> {
+    /// The TRANSLATE map (taxon id → label) from the file header.
+    pub fn taxons(&self) -> &HashMap<String, String> {
+        &self.taxons
+    }
+
+    /// Name the trees with `template` instead of [`DEFAULT_NAME_TEMPLATE`], see
+    /// [`tree_name`].
+    ///
+    /// Plain Newick files have no states, so with the default template their trees
+    /// are named `<file_basename>_tree_<index>` instead.
+    pub fn with_name_template(mut self, template: &str) -> Self {
+        self.name_template = template.to_string();
+        self
+    }
+
+    /// Next line of the trees block, or `None` at `End;` / end of file.
+    fn next_line(&mut self) -> Option<io::Result<String>> {
+        let line = match self.pending.take() {
+            Some(line) => line,
+            None => {
+                let mut line = String::new();
+                match self.reader.read_line(&mut line) {
+                    Ok(0) => return None,
+                    Ok(_) => line,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+        };
+        if line.trim().to_ascii_uppercase().starts_with("END;") {
+            return None;
+        }
+        Some(Ok(line))
+    }
+}
+
+impl BeastTreeIter<'_> {
+    /// Like [`Iterator::next`], but also returns the tree's `STATE_` number
+    /// (0 when the header has none) as `(name, state, tree)`.
+    pub fn next_with_state(&mut self) -> Option<Result<(String, usize, Tree), ReadError>> {
+        self.next_parsed()
+            .map(|item| item.map(|parsed| (parsed.name, parsed.state, parsed.tree)))
+    }
+
+    /// Like [`Iterator::next`], with everything known about the tree: its name,
+    /// `STATE_` number and `[&R]`/`[&U]` rootedness marker.
+    pub fn next_parsed(&mut self) -> Option<Result<ParsedTree, ReadError>> {
+        while !self.done {
+            let line = match self.next_line() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+                None => {
+                    self.done = true;
+                    return None;
+                }
+            };
+
+            let (header, body) = if self.plain {
+                match line.trim() {
+                    "" => continue,
+                    body => (None, body),
+                }
+            } else {
+                let Some((header, body)) = split_tree_line(&line) else {
+                    continue;
+                };
+                (Some(header), body)
+            };
+            let idx = self.index;
+            self.index += 1;
+
+            //generate tree name & extract state number
+            let state = header.map_or(0, |header| extract_state(header.trim()));
+            // Filter out burn-in trees based on count and/or state number if 0 we don't filter
+            let keep = (self.burnin_trees == 0 && self.burnin_states == 0)
+                || (self.burnin_trees > 0 && idx >= self.burnin_trees)
+                || (self.burnin_states > 0 && state > self.burnin_states);
+            if !keep {
+                continue;
+            }
+
             // Strip BEAST annotations from newick string (e.g., [&rate=...])
             // BEAST format: :[&rate=X.XX]length -> :length
-            let newick = strip_beast_annotations(&tree.body);
-            let mut phylo_tree = match phylotree::tree::Tree::from_newick(&newick) {
+            let newick = strip_beast_annotations(body.trim());
+            let mut phylo_tree = match Tree::from_newick(newick.trim_start()) {
                 Ok(t) => t,
-                Err(e) => {
-                    eprintln!(
-                        "Failed to parse tree {} at index {}: {}",
-                        path.as_ref().display(),
-                        idx,
-                        e
-                    );
-                    return None;
-                }
+                Err(source) => return Some(Err(ReadError::Parse { index: idx, source })),
             };
 
-            // Rename the leaves with the map
-            if use_real_taxa {
-                rename_leaf_nodes(&mut phylo_tree, &taxons);
+            // Rename the leaves with the map; an unmapped tip would end up unnamed
+            if self.use_real_taxa && !self.plain {
+                let ids = rename_leaf_nodes(&mut phylo_tree, &self.taxons);
+                if !ids.is_empty() {
+                    return Some(Err(ReadError::Untranslated { index: idx, ids }));
+                }
             }
 
-            Some((name, phylo_tree))
-        })
-        .collect::<Vec<_>>();
+            let name = if self.plain && self.name_template == DEFAULT_NAME_TEMPLATE {
+                format!("{}_tree_{idx}", self.base_name)
+            } else {
+                tree_name(&self.name_template, &self.base_name, idx, state)
+            };
+            return Some(Ok(ParsedTree {
+                name,
+                state,
+                rooted: rooted_marker(body),
+                tree: phylo_tree,
+            }));
+        }
+        None
+    }
+}
+
+impl Iterator for BeastTreeIter<'_> {
+    type Item = Result<(String, Tree), ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_state()
+            .map(|item| item.map(|(name, _, tree)| (name, tree)))
+    }
+}
+
+/// Open a file for buffered line reading, decompressing it first if the path ends with `.gz`.
+fn open_maybe_gz(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let file = fs::File::open(path)?;
+    if path.to_string_lossy().ends_with(".gz") {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Whether a line is a bare Newick tree: an opening parenthesis, possibly after
+/// `[...]` comments such as a `[&R]` marker.
+fn is_newick_line(line: &str) -> bool {
+    let mut rest = line.trim_start();
+    while let Some(comment) = rest.strip_prefix('[') {
+        let Some((_, after)) = comment.split_once(']') else {
+            return false;
+        };
+        rest = after.trim_start();
+    }
+    rest.starts_with('(')
+}
+
+/// Split a NEXUS `tree <name> = <newick>` line at its `=`, trimming both sides.
+///
+/// The `tree` keyword is matched case-insensitively as the first whitespace-delimited
+/// token, and the `=` may have any spacing around it (`tree STATE_0=(A,B);`). An `=`
+/// inside a `[...]` comment, such as BEAST's `[&lnP=-3562.1]` after the tree name,
+/// is skipped. Returns `None` for lines that are not tree definitions.
+fn split_tree_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    let keyword = line.split_whitespace().next()?;
+    if !keyword.eq_ignore_ascii_case("tree") {
+        return None;
+    }
+
+    let mut depth = 0usize;
+    let eq = line.char_indices().find_map(|(idx, ch)| {
+        match ch {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            '=' if depth == 0 => return Some(idx),
+            _ => {}
+        }
+        None
+    })?;
+    Some((line[..eq].trim(), line[eq + 1..].trim()))
+}
 
-    (taxons, trees)
+/// Read the `[&R]` (rooted) or `[&U]` (unrooted) marker in front of a Newick string.
+///
+/// Only comments before the opening parenthesis are inspected, so annotations inside
+/// the tree are never mistaken for a marker. The marker is case-insensitive; returns
+/// `None` if there is none. Pass the result as
+/// [`SnapshotOptions::rooted`](crate::snapshot::SnapshotOptions::rooted) to override
+/// the rootedness inferred from the tree's shape.
+///
+/// # Example
+/// ```
+/// # use rust_python_tree_distances::io::rooted_marker;
+/// assert_eq!(rooted_marker("[&U] ((A,B),(C,D));"), Some(false));
+/// assert_eq!(rooted_marker("[&lnP=-10.5][&R]((A,B),C);"), Some(true));
+/// assert_eq!(rooted_marker("((A,B),(C,D));"), None);
+/// ```
+pub fn rooted_marker(newick: &str) -> Option<bool> {
+    let mut rest = newick.trim_start();
+    while let Some(comment) = rest.strip_prefix('[') {
+        let (inner, after) = comment.split_once(']')?;
+        match inner.trim() {
+            m if m.eq_ignore_ascii_case("&R") => return Some(true),
+            m if m.eq_ignore_ascii_case("&U") => return Some(false),
+            _ => rest = after.trim_start(),
+        }
+    }
+    None
 }
 
 fn extract_state(header: &str) -> usize {
@@ -115,99 +845,476 @@ fn extract_state(header: &str) -> usize {
     0
 }
 
-struct TreeBlock<'a> {
-    header: &'a str,
-    body: String,
-}
-
-fn collect_tree_blocks(content: &str) -> Vec<TreeBlock<'_>> {
-    content
-        .lines()
-        .skip_while(|line| !line.to_ascii_uppercase().starts_with("TREE "))
-        .take_while(|line| !line.trim().to_ascii_uppercase().starts_with("END;"))
-        .filter_map(|line| {
-            let mut parts = line.splitn(2, " = ");
-            let header = parts.next()?.trim();
-            let body = parts.next()?.trim().to_string();
-            Some(TreeBlock { header, body })
-        })
+/// Find every tree with the given `STATE_` number.
+///
+/// `states` holds the state of each tree in `named_trees`, e.g. the concatenated
+/// [`ParseSummary::states`] of the files they were read from, so the lookup works
+/// with any name template. Several files of one run usually share their states, so
+/// more than one tree can match.
+pub fn find_trees_by_state<'a>(
+    named_trees: &'a [(String, Tree)],
+    states: &[usize],
+    state: usize,
+) -> Vec<&'a (String, Tree)> {
+    named_trees
+        .iter()
+        .zip(states)
+        .filter(|&(_, &tree_state)| tree_state == state)
+        .map(|(named_tree, _)| named_tree)
         .collect()
 }
 
-fn parse_taxon_block(content: &str) -> HashMap<String, String> {
-    content
-        .lines()
-        .skip_while(|line| !line.trim().to_ascii_uppercase().starts_with("TRANSLATE"))
-        .skip(1)
-        .take_while(|line| !line.trim().to_ascii_uppercase().starts_with(";"))
-        // STRUCTURE:
-        // 1 '1959.M.CD.59.ZR59',
-        // 2 '1960.DRC60A',
-        .filter_map(|line| {
-            let line = line.trim().trim_end_matches(',');
-            let mut parts = line.split_whitespace();
-            let id = parts.next()?.to_string();
-            let label = parts.next()?.trim_matches('\'').to_string();
-            Some((id, label))
-        })
-        .collect::<HashMap<_, _>>()
+/// Parse one TRANSLATE entry.
+///
+/// STRUCTURE:
+/// 1 '1959.M.CD.59.ZR59',
+/// 2 '1960.DRC60A',
+/// 3 'Homo sapiens, 2021',
+/// 4 Gorilla,
+///
+/// A single-quoted label is taken whole, including spaces and commas, without the
+/// quotes (`''` inside it is an escaped quote). An unquoted label is the next token.
+/// Returns `None` for an unterminated quote.
+fn parse_translate_line(line: &str) -> Option<(String, String)> {
+    let (id, rest) = line.trim().split_once(char::is_whitespace)?;
+    let rest = rest.trim_start();
+
+    let label = match rest.strip_prefix('\'') {
+        Some(quoted) => {
+            let mut label = String::new();
+            let mut chars = quoted.chars().peekable();
+            loop {
+                match chars.next()? {
+                    '\'' if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        label.push('\'');
+                    }
+                    '\'' => break,
+                    c => label.push(c),
+                }
+            }
+            label
+        }
+        None => rest
+            .split_whitespace()
+            .next()?
+            .trim_end_matches(',')
+            .to_string(),
+    };
+    Some((id.to_string(), label))
 }
 
+/// Replace every leaf label by its TRANSLATE entry.
+///
+/// Returns the labels (sorted) that have no entry; those leaves are left unnamed, so
+/// callers should treat a non-empty result as an error.
+#[must_use]
 pub fn rename_leaf_nodes(
     phylo_tree: &mut Tree,
     translate: &std::collections::HashMap<String, String>,
-) {
+) -> Vec<String> {
+    let mut unmapped = Vec::new();
     for leaf_id in phylo_tree.get_leaves() {
         if let Ok(node) = phylo_tree.get_mut(&leaf_id) {
-            node.name = node.name.as_ref().and_then(|n| translate.get(n).cloned());
+            let label = node.name.take();
+            node.name = label.as_ref().and_then(|n| translate.get(n).cloned());
+            if node.name.is_none() {
+                unmapped.push(label.unwrap_or_default());
+            }
         }
     }
+    unmapped.sort();
+    unmapped
 }
 
 /// Write a labeled square matrix as TSV to a file or stdout.
 /// If `path` ends with `.gz`, the output is gzip-compressed.
 /// If `path` equals `-`, the matrix is written to stdout (uncompressed).
-pub fn write_matrix_tsv<P: AsRef<Path>, T: std::fmt::Display>(
+///
+/// # Errors
+/// Returns an [`io::ErrorKind::InvalidInput`] error, before creating the file, if
+/// `mat` is not square with one row per name; otherwise any I/O error.
+pub fn write_matrix_tsv<P: AsRef<Path>, M>(path: P, names: &[String], mat: &M) -> io::Result<()>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: std::fmt::Display,
+{
+    write_matrix_delimited(path, names, mat, b'\t')
+}
+
+/// Write a labeled square matrix as TSV like [`write_matrix_tsv`], with every
+/// floating-point distance rounded to `decimals` places (`{:.N}`), so `0.9999999999999999`
+/// is written as `1.000` with `decimals = 3`. Integer distances are written unchanged.
+///
+/// # Errors
+/// As [`write_matrix_tsv`].
+pub fn write_matrix_tsv_prec<P: AsRef<Path>, M>(
+    path: P,
+    names: &[String],
+    mat: &M,
+    decimals: usize,
+) -> io::Result<()>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: std::fmt::Display,
+{
+    write_matrix_tsv(path, names, &FixedMatrix::new(mat, Some(decimals)))
+}
+
+/// A distance displayed with a fixed number of decimals, or as usual without one.
+///
+/// The precision only affects floating-point values; integers ignore it, so RF
+/// counts print the same either way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fixed<T> {
+    /// The distance to display
+    pub value: T,
+    /// Number of decimals, `None` for the default `Display` output
+    pub decimals: Option<usize>,
+}
+
+impl<T> Fixed<T> {
+    pub fn new(value: T, decimals: Option<usize>) -> Self {
+        Self { value, decimals }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Fixed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.decimals {
+            Some(decimals) => write!(f, "{:.*}", decimals, self.value),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+/// A view of a matrix whose entries are [`Fixed`], so any matrix writer can round its
+/// output without copying the matrix.
+pub struct FixedMatrix<'a, M: ?Sized> {
+    mat: &'a M,
+    decimals: Option<usize>,
+}
+
+impl<'a, M: ?Sized> FixedMatrix<'a, M> {
+    pub fn new(mat: &'a M, decimals: Option<usize>) -> Self {
+        Self { mat, decimals }
+    }
+}
+
+impl<M: MatrixRows + ?Sized> MatrixRows for FixedMatrix<'_, M> {
+    type Item = Fixed<M::Item>;
+
+    fn size(&self) -> usize {
+        self.mat.size()
+    }
+
+    fn row(&self, i: usize) -> impl Iterator<Item = Self::Item> + '_ {
+        self.mat
+            .row(i)
+            .map(|value| Fixed::new(value, self.decimals))
+    }
+
+    fn row_len(&self, i: usize) -> usize {
+        self.mat.row_len(i)
+    }
+}
+
+/// Write a labeled square matrix separated by `delim` (e.g. `b','` for CSV) to a file
+/// or stdout. `.gz` and `-` paths are handled as in [`write_matrix_tsv`].
+pub fn write_matrix_delimited<P: AsRef<Path>, M>(
+    path: P,
+    names: &[String],
+    mat: &M,
+    delim: u8,
+) -> io::Result<()>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: std::fmt::Display,
+{
+    check_square(names, mat)?;
+    write_to_path(path.as_ref(), |out| {
+        write_matrix_delimited_to(out, names, mat, delim)
+    })
+}
+
+/// Write a labeled single-column TSV (e.g. distances to a reference tree) to a file or stdout.
+///
+/// The header row is an empty cell followed by `header`; every following row is a
+/// name and its value. `.gz` and `-` paths are handled as in [`write_matrix_tsv`].
+pub fn write_column_tsv<P: AsRef<Path>, T: std::fmt::Display>(
+    path: P,
+    header: &str,
+    names: &[String],
+    values: &[T],
+) -> io::Result<()> {
+    write_to_path(path.as_ref(), |out| {
+        write_column_to(out, header, names, values)
+    })
+}
+
+/// Write the upper-triangle `(i, j, distance)` pairs as long-format TSV to a file or stdout.
+///
+/// Only `n(n-1)/2` rows are written instead of the `n²` cells of [`write_matrix_tsv`].
+/// `.gz` and `-` paths are handled as in [`write_matrix_tsv`].
+pub fn write_condensed_tsv<P: AsRef<Path>, T: std::fmt::Display>(
     path: P,
     names: &[String],
-    mat: &[Vec<T>],
+    pairs: &[(usize, usize, T)],
 ) -> io::Result<()> {
+    write_to_path(path.as_ref(), |out| write_condensed_to(out, names, pairs))
+}
+
+/// Write `(i, j, distance)` pairs as long-format TSV to a file or stdout as they are
+/// produced, e.g. from [`crate::distances::pairwise_iter`].
+///
+/// Same layout as [`write_condensed_tsv`], but no pair is held in memory after it is
+/// written. `.gz` and `-` paths are handled as in [`write_matrix_tsv`].
+pub fn write_condensed_stream<P, T, I>(path: P, names: &[String], pairs: I) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    T: std::fmt::Display,
+    I: IntoIterator<Item = (usize, usize, T)>,
+{
+    write_to_path(path.as_ref(), |out| {
+        write_condensed_iter_to(out, names, pairs)
+    })
+}
+
+/// Write a square matrix in PHYLIP distance format to a file or stdout.
+///
+/// `.gz` and `-` paths are handled as in [`write_matrix_tsv`]; see
+/// [`write_matrix_phylip_to`] for the layout.
+pub fn write_matrix_phylip<P: AsRef<Path>, M>(path: P, names: &[String], mat: &M) -> io::Result<()>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: std::fmt::Display,
+{
+    check_square(names, mat)?;
+    write_to_path(path.as_ref(), |out| write_matrix_phylip_to(out, names, mat))
+}
+
+/// Write a square matrix as a NEXUS `DISTANCES` block to a file or stdout.
+///
+/// `.gz` and `-` paths are handled as in [`write_matrix_tsv`]; see
+/// [`write_matrix_nexus_to`] for the layout.
+pub fn write_matrix_nexus<P: AsRef<Path>, M>(path: P, names: &[String], mat: &M) -> io::Result<()>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: std::fmt::Display,
+{
+    check_square(names, mat)?;
+    write_to_path(path.as_ref(), |out| write_matrix_nexus_to(out, names, mat))
+}
+
+/// Open `path` as a file, gzip stream (`.gz`) or stdout (`-`) and hand it to `write`.
+fn write_to_path<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
     use std::fs::File;
     use std::io::BufWriter;
 
-    let p = path.as_ref();
-    if p.as_os_str() == "-" {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "writing to stdout is not supported by write_matrix_tsv",
-        ));
+    if path.as_os_str() == "-" {
+        let stdout = io::stdout();
+        return write(&mut BufWriter::new(stdout.lock()));
     }
 
-    let is_gz = p.to_string_lossy().ends_with(".gz");
+    let is_gz = path.to_string_lossy().ends_with(".gz");
 
-    let mut out: Box<dyn Write> = if is_gz {
-        let f = File::create(p)?;
-        let enc = GzEncoder::new(f, Compression::default());
-        Box::new(BufWriter::new(enc))
+    if is_gz {
+        let enc = GzEncoder::new(File::create(path)?, Compression::default());
+        let mut out = BufWriter::new(enc);
+        write(&mut out)?;
+        // Finish the gzip stream explicitly so trailer errors are not lost on drop
+        out.into_inner().map_err(|e| e.into_error())?.finish()?;
+        Ok(())
     } else {
-        Box::new(BufWriter::new(File::create(p)?))
-    };
+        write(&mut BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Write a labeled square matrix as TSV to any writer.
+///
+/// The header row starts with an empty cell followed by the names; every
+/// following row is a name followed by its distances.
+pub fn write_matrix_to<W: Write, M>(out: W, names: &[String], mat: &M) -> io::Result<()>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: std::fmt::Display,
+{
+    write_matrix_delimited_to(out, names, mat, b'\t')
+}
+
+/// Write a labeled square matrix as TSV to any writer, one row at a time.
+///
+/// Same output as [`write_matrix_to`], but `rows` yields each row (itself an iterator
+/// of distances) lazily, so a row can be computed, written and dropped before the
+/// next one exists. This keeps memory at one row for matrices too large to hold.
+///
+/// # Errors
+/// Returns an [`io::ErrorKind::InvalidInput`] error if a row does not have one entry
+/// per name or the number of rows differs from the number of names. Unlike the eager
+/// writers, this is only detected while writing, so the output is incomplete by then.
+pub fn write_matrix_tsv_streaming<W, I, R>(mut w: W, names: &[String], rows: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = R>,
+    R: IntoIterator,
+    R::Item: std::fmt::Display,
+{
+    let invalid = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
 
     // Header row
-    write!(&mut out, "\t")?;
-    for (k, name) in names.iter().enumerate() {
-        if k > 0 {
-            write!(&mut out, "\t")?;
+    for name in names {
+        write!(&mut w, "\t{}", quote_field(name, '\t'))?;
+    }
+    writeln!(&mut w)?;
+
+    // Rows
+    let mut rows = rows.into_iter();
+    for (i, name) in names.iter().enumerate() {
+        let Some(row) = rows.next() else {
+            return invalid(format!(
+                "matrix has {i} rows but {} names were given",
+                names.len()
+            ));
+        };
+        write!(&mut w, "{}", quote_field(name, '\t'))?;
+        let mut len = 0;
+        for val in row {
+            write!(&mut w, "\t{}", val)?;
+            len += 1;
         }
-        write!(&mut out, "{}", name)?;
+        if len != names.len() {
+            return invalid(format!(
+                "matrix row {i} has {len} entries, expected {} for a square matrix",
+                names.len()
+            ));
+        }
+        writeln!(&mut w)?;
+    }
+    if rows.next().is_some() {
+        return invalid(format!(
+            "matrix has more than {} rows, one per name",
+            names.len()
+        ));
+    }
+
+    w.flush()?;
+    Ok(())
+}
+
+/// Write a labeled square matrix separated by `delim` to any writer.
+///
+/// Same layout as [`write_matrix_to`]. Names containing the delimiter, a newline or a
+/// carriage return are wrapped in double quotes, with inner quotes doubled (CSV style).
+pub fn write_matrix_delimited_to<W: Write, M>(
+    mut out: W,
+    names: &[String],
+    mat: &M,
+    delim: u8,
+) -> io::Result<()>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: std::fmt::Display,
+{
+    check_square(names, mat)?;
+    let delim = delim as char;
+
+    // Header row
+    for name in names {
+        write!(&mut out, "{delim}{}", quote_field(name, delim))?;
     }
     writeln!(&mut out)?;
 
     // Rows
-    for (i, row) in mat.iter().enumerate() {
-        write!(&mut out, "{}", names[i])?;
-        for val in row {
-            write!(&mut out, "\t{}", val)?;
+    for (i, name) in names.iter().enumerate().take(mat.size()) {
+        write!(&mut out, "{}", quote_field(name, delim))?;
+        for val in mat.row(i) {
+            write!(&mut out, "{delim}{}", val)?;
+        }
+        writeln!(&mut out)?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Check that `mat` is square with one row per name, so the writers never index past
+/// `names` or emit ragged rows.
+///
+/// # Errors
+/// Returns an [`io::ErrorKind::InvalidInput`] error naming the mismatch.
+fn check_square<M: MatrixRows + ?Sized>(names: &[String], mat: &M) -> io::Result<()> {
+    let invalid = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+    if names.len() != mat.size() {
+        return invalid(format!(
+            "matrix has {} rows but {} names were given",
+            mat.size(),
+            names.len()
+        ));
+    }
+    if let Some(i) = (0..mat.size()).find(|&i| mat.row_len(i) != names.len()) {
+        return invalid(format!(
+            "matrix row {i} has {} entries, expected {} for a square matrix",
+            mat.row_len(i),
+            names.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Quote `field` if it contains `delim` or a line break, doubling any inner quotes.
+fn quote_field(field: &str, delim: char) -> Cow<'_, str> {
+    if field.contains([delim, '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Write a labeled single-column TSV to any writer.
+pub fn write_column_to<W: Write, T: std::fmt::Display>(
+    mut out: W,
+    header: &str,
+    names: &[String],
+    values: &[T],
+) -> io::Result<()> {
+    writeln!(&mut out, "\t{}", header)?;
+    for (name, val) in names.iter().zip(values) {
+        writeln!(&mut out, "{}\t{}", name, val)?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Write a square matrix in PHYLIP distance format to any writer.
+///
+/// # Layout
+/// ```text
+///     3
+/// tree_1     0 2 4
+/// tree_2     2 0 6
+/// tree_3     4 6 0
+/// ```
+/// The first line is the number of taxa; each row starts with its name truncated or
+/// padded to exactly 10 characters, as `neighbor` and `fitch` expect. Truncation can
+/// make names collide (e.g. `run_tree_STATE100` and `run_tree_STATE200`); the matrix
+/// is still written, but a warning lists the collisions on stderr.
+pub fn write_matrix_phylip_to<W: Write, M>(mut out: W, names: &[String], mat: &M) -> io::Result<()>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: std::fmt::Display,
+{
+    check_square(names, mat)?;
+    let short_names = phylip_names(names);
+
+    writeln!(&mut out, "{:>5}", names.len())?;
+    for (i, name) in short_names.iter().enumerate().take(mat.size()) {
+        write!(&mut out, "{}", name)?;
+        for val in mat.row(i) {
+            write!(&mut out, " {}", val)?;
         }
         writeln!(&mut out)?;
     }
@@ -215,3 +1322,840 @@ pub fn write_matrix_tsv<P: AsRef<Path>, T: std::fmt::Display>(
     out.flush()?;
     Ok(())
 }
+
+/// Write a square matrix as a NEXUS `DISTANCES` block to any writer.
+///
+/// # Layout
+/// ```text
+/// #NEXUS
+///
+/// BEGIN DISTANCES;
+///     DIMENSIONS NEWTAXA NTAX=3;
+///     FORMAT TRIANGLE=BOTH LABELS=LEFT DIAGONAL;
+///     TAXLABELS
+///         tree_1
+///         tree_2
+///         'tree 3'
+///     ;
+///     MATRIX
+///         tree_1 0 2 4
+///         tree_2 2 0 6
+///         'tree 3' 4 6 0
+///     ;
+/// END;
+/// ```
+/// The block declares its own taxa (`NEWTAXA`), so PAUP* and SplitsTree read it
+/// without a separate `TAXA` block. Names with whitespace or NEXUS punctuation are
+/// single-quoted, with inner quotes doubled.
+pub fn write_matrix_nexus_to<W: Write, M>(mut out: W, names: &[String], mat: &M) -> io::Result<()>
+where
+    M: MatrixRows + ?Sized,
+    M::Item: std::fmt::Display,
+{
+    check_square(names, mat)?;
+    let labels: Vec<Cow<'_, str>> = names.iter().map(|name| nexus_label(name)).collect();
+
+    writeln!(&mut out, "#NEXUS\n")?;
+    writeln!(&mut out, "BEGIN DISTANCES;")?;
+    writeln!(&mut out, "    DIMENSIONS NEWTAXA NTAX={};", names.len())?;
+    writeln!(&mut out, "    FORMAT TRIANGLE=BOTH LABELS=LEFT DIAGONAL;")?;
+    writeln!(&mut out, "    TAXLABELS")?;
+    for label in &labels {
+        writeln!(&mut out, "        {label}")?;
+    }
+    writeln!(&mut out, "    ;")?;
+    writeln!(&mut out, "    MATRIX")?;
+    for (i, label) in labels.iter().enumerate().take(mat.size()) {
+        write!(&mut out, "        {label}")?;
+        for val in mat.row(i) {
+            write!(&mut out, " {}", val)?;
+        }
+        writeln!(&mut out)?;
+    }
+    writeln!(&mut out, "    ;")?;
+    writeln!(&mut out, "END;")?;
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Single-quote a NEXUS taxon label if it contains whitespace or punctuation that
+/// would end the token, doubling any inner quotes.
+fn nexus_label(name: &str) -> Cow<'_, str> {
+    let needs_quotes = name.is_empty()
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || "()[]{}/\\,;:=*'\"`+-<>".contains(c));
+    if needs_quotes {
+        Cow::Owned(format!("'{}'", name.replace('\'', "''")))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Truncate or pad every name to the 10 characters of a PHYLIP name field,
+/// warning on stderr about names that become identical.
+fn phylip_names(names: &[String]) -> Vec<String> {
+    let short_names: Vec<String> = names
+        .iter()
+        .map(|name| format!("{:<10}", name.chars().take(10).collect::<String>()))
+        .collect();
+
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for (short, name) in short_names.iter().zip(names) {
+        if let Some(first) = seen.insert(short.as_str(), name.as_str())
+            && first != name
+        {
+            eprintln!(
+                "Warning: '{first}' and '{name}' are both truncated to '{}' in PHYLIP output",
+                short.trim_end()
+            );
+        }
+    }
+
+    short_names
+}
+
+/// Write the `(i, j, distance)` pairs as long-format TSV to any writer.
+///
+/// The header is `tree_a  tree_b  distance`; rows keep the order of `pairs`, so pairs
+/// from [`crate::distances::pairwise_pairs`] read back in SciPy's condensed order.
+pub fn write_condensed_to<W: Write, T: std::fmt::Display>(
+    out: W,
+    names: &[String],
+    pairs: &[(usize, usize, T)],
+) -> io::Result<()> {
+    write_condensed_iter_to(out, names, pairs.iter().map(|(i, j, val)| (*i, *j, val)))
+}
+
+/// Write `(i, j, distance)` pairs as long-format TSV to any writer, consuming them one
+/// at a time. Same layout as [`write_condensed_to`].
+///
+/// # Errors
+/// Returns [`io::ErrorKind::InvalidInput`] for a pair with an index that is not below
+/// `names.len()`; the pairs before it have already been written.
+pub fn write_condensed_iter_to<W, T, I>(mut out: W, names: &[String], pairs: I) -> io::Result<()>
+where
+    W: Write,
+    T: std::fmt::Display,
+    I: IntoIterator<Item = (usize, usize, T)>,
+{
+    writeln!(&mut out, "tree_a\ttree_b\tdistance")?;
+    for (i, j, val) in pairs {
+        let (Some(name_i), Some(name_j)) = (names.get(i), names.get(j)) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("pair ({i}, {j}) is out of range for {} names", names.len()),
+            ));
+        };
+        writeln!(&mut out, "{name_i}\t{name_j}\t{val}")?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{SnapshotOptions, TreeSnapshot};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Scratch directory for tests that need real files; removed on drop, so also
+    /// when an assertion fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let id = NEXT.fetch_add(1, Ordering::Relaxed);
+            let dir =
+                std::env::temp_dir().join(format!("rptd_io_test_{}_{id}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        /// Path of `name` in the directory, without creating the file.
+        fn path(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+
+        /// Write `contents` to `name` in the directory and return its path.
+        fn write(&self, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+            let path = self.path(name);
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Parse in-memory NEXUS content as [`read_beast_trees_with`] parses a file.
+    fn parse_with(
+        content: &str,
+        base_name: &str,
+        burnin_trees: usize,
+        use_real_taxa: bool,
+        strict: bool,
+    ) -> Result<(HashMap<String, String>, NamedTrees, ParseSummary), ReadError> {
+        let iter = iter_beast_trees_from_reader(
+            content.as_bytes(),
+            base_name,
+            burnin_trees,
+            0,
+            use_real_taxa,
+        )?;
+        collect_trees(iter, strict, base_name)
+    }
+
+    #[test]
+    fn test_find_trees_by_state() {
+        // Names from a custom template carry no state; two files share STATE_500
+        let states = [0, 500, 1000, 500];
+        let named_trees: Vec<(String, Tree)> = (0..states.len())
+            .map(|idx| {
+                let tree = Tree::from_newick("((A:1,B:1):1,(C:1,D:1):1);").unwrap();
+                (format!("tree{idx}"), tree)
+            })
+            .collect();
+        let names = |state| -> Vec<&str> {
+            find_trees_by_state(&named_trees, &states, state)
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect()
+        };
+
+        assert_eq!(names(1000), ["tree2"]);
+        assert_eq!(names(500), ["tree1", "tree3"]);
+        // Only exact state matches count, not prefixes/suffixes of a larger number
+        assert!(names(50).is_empty());
+        assert!(names(100).is_empty());
+    }
+
+    const SMALL_NEXUS: &str = "#NEXUS
+Begin trees;
+\tTranslate
+\t\t1 Alpha,
+\t\t2 Beta,
+\t\t3 Gamma,
+\t\t4 Delta
+;
+tree STATE_0 = ((1:1,2:1):1,(3:1,4:1):1);
+tree STATE_500 = ((1:1,3:1):1,(2:1,4:1):1);
+End;
+";
+
+    #[test]
+    fn test_read_gzipped_trees() {
+        let dir = TempDir::new();
+        let plain = dir.write("small.trees", SMALL_NEXUS);
+        let gz = dir.path("small.trees.gz");
+        let mut enc = GzEncoder::new(fs::File::create(&gz).unwrap(), Compression::default());
+        enc.write_all(SMALL_NEXUS.as_bytes()).unwrap();
+        enc.finish().unwrap();
+
+        let (taxa_plain, trees_plain) = read_beast_trees(&plain, 0, 0, true).unwrap();
+        let (taxa_gz, trees_gz) = read_beast_trees(&gz, 0, 0, true).unwrap();
+
+        assert_eq!(taxa_plain, taxa_gz);
+        assert_eq!(trees_plain.len(), 2);
+        let names =
+            |trees: &[(String, Tree)]| trees.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&trees_plain), names(&trees_gz));
+        assert_eq!(trees_gz[0].0, "small_tree_STATE0");
+        for ((_, a), (_, b)) in trees_plain.iter().zip(&trees_gz) {
+            let snap_a = TreeSnapshot::from_tree(a, false).unwrap();
+            let snap_b = TreeSnapshot::from_tree(b, false).unwrap();
+            assert_eq!(snap_a.parts, snap_b.parts);
+            assert_eq!(snap_a.leaf_names, snap_b.leaf_names);
+        }
+    }
+
+    #[test]
+    fn test_iter_matches_eager_read() {
+        let dir = TempDir::new();
+        let path = dir.write("small.trees", SMALL_NEXUS);
+
+        for burnin_trees in [0, 1] {
+            let (taxons, eager) = read_beast_trees(&path, burnin_trees, 0, true).unwrap();
+
+            let iter = iter_beast_trees(&path, burnin_trees, 0, true).unwrap();
+            assert_eq!(iter.taxons(), &taxons);
+            let streamed: Vec<String> = iter.map(|item| item.unwrap().0).collect();
+
+            let eager: Vec<String> = eager.into_iter().map(|(name, _)| name).collect();
+            assert_eq!(streamed, eager);
+            assert_eq!(streamed.len(), 2 - burnin_trees);
+        }
+    }
+
+    #[test]
+    fn test_parse_from_str() {
+        let (taxons, trees) = parse_beast_trees_from_str(SMALL_NEXUS, "small", 0, 0, true).unwrap();
+        assert_eq!(taxons.len(), 4);
+        assert_eq!(taxons["3"], "Gamma");
+        let names: Vec<&str> = trees.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["small_tree_STATE0", "small_tree_STATE500"]);
+
+        let snap = TreeSnapshot::from_tree(&trees[1].1, false).unwrap();
+        assert_eq!(snap.leaf_names, ["Alpha", "Beta", "Delta", "Gamma"]);
+
+        // Burn-in is applied as for files
+        let (_, trees) = parse_beast_trees_from_str(SMALL_NEXUS, "small", 0, 100, false).unwrap();
+        assert_eq!(trees.len(), 1);
+        assert!(matches!(
+            parse_beast_trees_from_str("#NEXUS\nBegin trees;\nEnd;\n", "empty", 0, 0, false),
+            Err(ReadError::NoTrees)
+        ));
+    }
+
+    #[test]
+    fn test_read_plain_newick_file() {
+        let dir = TempDir::new();
+        let path = dir.write(
+            "plain.trees",
+            "((A:1,B:1):1,(C:1,D:1):1);\n\n[&U] ((A:1,C:1):1,(B:1,D:1):1);\n",
+        );
+
+        let (taxons, trees) = read_beast_trees(&path, 0, 0, true).unwrap();
+        let count = count_tree_blocks(&path).unwrap();
+        let (_, burned) = read_beast_trees(&path, 1, 0, false).unwrap();
+
+        assert!(taxons.is_empty());
+        let names: Vec<&str> = trees.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["plain_tree_0", "plain_tree_1"]);
+        let snap = TreeSnapshot::from_tree(&trees[1].1, false).unwrap();
+        assert_eq!(snap.leaf_names, ["A", "B", "C", "D"]);
+        assert_eq!(count, 2);
+        assert_eq!(burned[0].0, "plain_tree_1");
+
+        // NEXUS files are still read through their tree lines
+        let (_, trees) = parse_beast_trees_from_str(SMALL_NEXUS, "small", 0, 0, true).unwrap();
+        assert_eq!(trees[0].0, "small_tree_STATE0");
+        assert!(is_newick_line("[&R] ((A,B),C);"));
+        assert!(!is_newick_line("#NEXUS"));
+    }
+
+    #[test]
+    fn test_read_tree_files_merges_in_order() {
+        let dir = TempDir::new();
+        let first = dir.write("chain1.trees", SMALL_NEXUS);
+        let second = dir.write(
+            "chain2.trees",
+            SMALL_NEXUS.replace("STATE_500", "STATE_1000"),
+        );
+
+        let names = |trees: NamedTrees| trees.into_iter().map(|(n, _)| n).collect::<Vec<_>>();
+        let (_, one) = read_beast_trees(&first, 0, 0, true).unwrap();
+        let (_, two) = read_beast_trees(&second, 0, 0, true).unwrap();
+        let (taxons, both) = read_beast_tree_files(&[&first, &second], 0, 0, true).unwrap();
+
+        // Two single-file reads, concatenated with their file prefixes
+        let expected: Vec<String> = names(one)
+            .into_iter()
+            .map(|n| format!("file0_{n}"))
+            .chain(names(two).into_iter().map(|n| format!("file1_{n}")))
+            .collect();
+        assert_eq!(names(both), expected);
+        assert_eq!(expected[3], "file1_chain2_tree_STATE1000");
+        assert_eq!(taxons.len(), 4);
+
+        // The failing file is reported by position
+        let missing = dir.path("missing.trees");
+        assert!(matches!(
+            read_beast_tree_files(&[&first, &missing], 0, 0, true),
+            Err((1, ReadError::Io(_)))
+        ));
+    }
+
+    #[test]
+    fn test_name_template() {
+        let dir = TempDir::new();
+        let three =
+            SMALL_NEXUS.replace("End;", "tree STATE_1000 = ((1:1,4:1):1,(2:1,3:1):1);\nEnd;");
+        let path = dir.write("chain.trees", three);
+
+        let names = |trees: NamedTrees| trees.into_iter().map(|(n, _)| n).collect::<Vec<_>>();
+        let read = |template: &str, burnin_trees: usize| {
+            let (_, trees, _) =
+                read_beast_trees_with(&path, burnin_trees, 0, true, false, template).unwrap();
+            names(trees)
+        };
+        assert_eq!(read("{index}", 0), ["0", "1", "2"]);
+        // Indices count the burn-in trees, so names do not shift with the burn-in
+        assert_eq!(read("{base}.{index}", 1), ["chain.1", "chain.2"]);
+        assert_eq!(read("{state}-{index}", 2), ["1000-2"]);
+        assert_eq!(read(DEFAULT_NAME_TEMPLATE, 2), ["chain_tree_STATE1000"]);
+
+        // Only the default template gets the per-file prefix
+        let (_, both, _) =
+            read_beast_tree_files_with(&[&path, &path], 2, 0, true, false, "{base}.{index}")
+                .unwrap();
+        assert_eq!(names(both), ["chain.2", "chain.2"]);
+        let (_, states) = read_tree_states(&[&path], 0, 0, "{index}").unwrap();
+        assert_eq!(
+            states,
+            [("0".into(), 0), ("1".into(), 500), ("2".into(), 1000)]
+        );
+    }
+
+    #[test]
+    fn test_burnin_fraction() {
+        let dir = TempDir::new();
+        let trees: String = (0..100)
+            .map(|i| format!("tree STATE_{} = ((A:1,B:1):1,(C:1,D:1):1);\n", i * 1000))
+            .collect();
+        let path = dir.write(
+            "hundred.trees",
+            format!("#NEXUS\nBegin trees;\n{trees}End;\n"),
+        );
+        assert_eq!(count_tree_blocks(&path).unwrap(), 100);
+        assert_eq!(burnin_trees_for_fraction(&path, 0.1).unwrap(), 10);
+
+        let (_, trees, summaries) =
+            read_beast_tree_files_fraction(&[&path], 0.1, false, false, DEFAULT_NAME_TEMPLATE)
+                .unwrap();
+        assert_eq!(trees.len(), 90);
+        assert_eq!(summaries[0].parsed, 90);
+        assert_eq!(trees[0].0, "file0_hundred_tree_STATE10000");
+
+        let (_, all, _) =
+            read_beast_tree_files_fraction(&[&path], 0.0, false, false, DEFAULT_NAME_TEMPLATE)
+                .unwrap();
+        assert_eq!(all.len(), 100);
+        assert!(matches!(
+            read_beast_tree_files_fraction(&[&path], 1.0, false, false, DEFAULT_NAME_TEMPLATE),
+            Err((0, ReadError::NoTrees))
+        ));
+    }
+
+    #[test]
+    fn test_read_tree_states() {
+        let dir = TempDir::new();
+        let first = dir.write("chain1.trees", SMALL_NEXUS);
+        let second = dir.write(
+            "chain2.trees",
+            SMALL_NEXUS.replace("STATE_500", "STATE_1000"),
+        );
+
+        let (labels, states) =
+            read_tree_states(&[&first, &second], 1, 0, DEFAULT_NAME_TEMPLATE).unwrap();
+        let (_, trees) = read_beast_tree_files(&[&first, &second], 1, 0, true).unwrap();
+
+        assert_eq!(labels, vec!["Alpha", "Beta", "Delta", "Gamma"]);
+        assert_eq!(
+            states,
+            vec![
+                ("file0_chain1_tree_STATE500".to_string(), 500),
+                ("file1_chain2_tree_STATE1000".to_string(), 1000),
+            ]
+        );
+        let names: Vec<&String> = trees.iter().map(|(name, _)| name).collect();
+        assert!(names.iter().zip(&states).all(|(a, (b, _))| *a == b));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let dir = TempDir::new();
+        assert!(matches!(
+            read_beast_trees(dir.path("missing.trees"), 0, 0, true),
+            Err(ReadError::Io(_))
+        ));
+
+        // Burn-in removes both trees
+        assert!(matches!(
+            parse_beast_trees_from_str(SMALL_NEXUS, "small", 5, 0, true),
+            Err(ReadError::NoTrees)
+        ));
+
+        // One broken tree: skipped by default, fatal in strict mode
+        let broken = SMALL_NEXUS.replace("(3:1,4:1):1);", "(3:1,4:1);");
+        let (_, trees) = parse_beast_trees_from_str(&broken, "broken", 0, 0, true).unwrap();
+        assert_eq!(trees.len(), 1);
+        assert!(matches!(
+            parse_with(&broken, "broken", 0, true, true),
+            Err(ReadError::Parse { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_summary_counts_skipped_trees() {
+        let broken_line = "tree STATE_250 = ((1:1,2:1):1,(3:1,4:1);\n";
+        let mixed = SMALL_NEXUS.replace("tree STATE_500", &format!("{broken_line}tree STATE_500"));
+
+        let (_, trees, summary) = parse_with(&mixed, "mixed", 0, true, false).unwrap();
+        assert_eq!(trees.len(), 2);
+        assert_eq!(
+            summary,
+            ParseSummary {
+                parsed: 2,
+                failed: vec![1],
+                states: vec![0, 500]
+            }
+        );
+        assert_eq!(summary.skipped(), 1);
+
+        // Burn-in drops the broken tree before it is parsed
+        let (_, _, summary) = parse_with(&mixed, "mixed", 2, true, false).unwrap();
+        assert_eq!(summary.skipped(), 0);
+
+        // Files keep one summary each and report strict failures with their position
+        let dir = TempDir::new();
+        let path = dir.write("mixed.trees", &mixed);
+        let (_, _, summaries) =
+            read_beast_tree_files_with(&[&path, &path], 0, 0, true, false, DEFAULT_NAME_TEMPLATE)
+                .unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert!(matches!(
+            read_beast_tree_files_with(&[&path], 0, 0, true, true, DEFAULT_NAME_TEMPLATE),
+            Err((0, ReadError::Parse { index: 1, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_rooted_markers() {
+        let nexus = "#NEXUS\nBegin trees;\n\
+            tree STATE_0 = [&R] ((A:1,B:1):1,C:1,D:1);\n\
+            tree STATE_1 = [&U] ((A:1,B:1):1,(C:1,D:1):1);\n\
+            tree STATE_2 = ((A:1,B:1):1,(C:1,D:1):1);\nEnd;\n";
+        let mut iter = iter_beast_trees_from_reader(nexus.as_bytes(), "m", 0, 0, false).unwrap();
+        let parsed: Vec<ParsedTree> = std::iter::from_fn(|| iter.next_parsed())
+            .map(Result::unwrap)
+            .collect();
+        let hints: Vec<Option<bool>> = parsed.iter().map(|p| p.rooted).collect();
+        assert_eq!(hints, [Some(true), Some(false), None]);
+
+        let snap = |p: &ParsedTree| {
+            let options = SnapshotOptions {
+                rooted: p.rooted,
+                ..Default::default()
+            };
+            TreeSnapshot::from_tree_with(&p.tree, false, options).unwrap()
+        };
+        // The markers override the shape: a trifurcating root declared rooted, and a
+        // binary root declared unrooted
+        assert!(snap(&parsed[0]).rooted);
+        assert!(
+            !TreeSnapshot::from_tree(&parsed[0].tree, false)
+                .unwrap()
+                .rooted
+        );
+        assert!(!snap(&parsed[1]).rooted);
+        assert!(
+            TreeSnapshot::from_tree(&parsed[1].tree, false)
+                .unwrap()
+                .rooted
+        );
+        assert!(snap(&parsed[2]).rooted);
+    }
+
+    #[test]
+    fn test_parse_translate_line() {
+        let entry = |id: &str, label: &str| Some((id.to_string(), label.to_string()));
+
+        assert_eq!(
+            parse_translate_line("\t\t5 'Homo sapiens',"),
+            entry("5", "Homo sapiens")
+        );
+        assert_eq!(
+            parse_translate_line("\t\t6 Gorilla,"),
+            entry("6", "Gorilla")
+        );
+        assert_eq!(
+            parse_translate_line("7 'Pan troglodytes, 2021'"),
+            entry("7", "Pan troglodytes, 2021")
+        );
+        assert_eq!(parse_translate_line("8 'O''Brien'"), entry("8", "O'Brien"));
+        assert_eq!(
+            parse_translate_line("1 '1959.M.CD.59.ZR59',"),
+            entry("1", "1959.M.CD.59.ZR59")
+        );
+        assert_eq!(parse_translate_line("9 'unterminated"), None);
+    }
+
+    #[test]
+    fn test_split_tree_line() {
+        assert_eq!(
+            split_tree_line("tree STATE_0=(A,B);"),
+            Some(("tree STATE_0", "(A,B);"))
+        );
+        assert_eq!(
+            split_tree_line("\tTREE tree1 = (A,B);\n"),
+            Some(("TREE tree1", "(A,B);"))
+        );
+        assert_eq!(
+            split_tree_line("Tree rep_1=(A,B);"),
+            Some(("Tree rep_1", "(A,B);"))
+        );
+        // BEAST 1 writes annotations between the name and the `=`
+        assert_eq!(
+            split_tree_line("tree STATE_10 [&lnP=-3562.1] = [&R] (A:[&rate=1]1,B:1);"),
+            Some(("tree STATE_10 [&lnP=-3562.1]", "[&R] (A:[&rate=1]1,B:1);"))
+        );
+        assert_eq!(split_tree_line("Trees = (A,B);"), None);
+        assert_eq!(split_tree_line("Begin trees;"), None);
+        assert_eq!(split_tree_line("tree without_equals;"), None);
+    }
+
+    #[test]
+    fn test_read_tree_line_variants() {
+        let nexus = "#NEXUS\nBegin trees;\ntree STATE_0=(A,B);\nTREE tree1 = (A,B);\nTree rep_1=(A,B);\nEnd;\n";
+        let (_, trees) = parse_beast_trees_from_str(nexus, "variants", 0, 0, false).unwrap();
+
+        assert_eq!(trees.len(), 3);
+        assert_eq!(trees[0].0, "variants_tree_STATE0");
+    }
+
+    #[test]
+    fn test_incomplete_translate_block() {
+        let nexus = SMALL_NEXUS.replace("\t\t3 Gamma,\n", "");
+
+        let err = parse_beast_trees_from_str(&nexus, "small", 0, 0, true).unwrap_err();
+        assert!(matches!(
+            &err,
+            ReadError::Untranslated { index: 0, ids } if ids == &["3"]
+        ));
+        assert!(err.to_string().contains("TRANSLATE block: [3]"), "{err}");
+
+        // Without translation the numeric labels are kept as they are
+        let (_, trees) = parse_beast_trees_from_str(&nexus, "small", 0, 0, false).unwrap();
+        assert_eq!(trees.len(), 2);
+    }
+
+    #[test]
+    fn test_write_matrix_delimited_quotes_names() {
+        let names = vec!["weird,name".to_string(), "plain".to_string()];
+        let mat = vec![vec![0, 4], vec![4, 0]];
+
+        let mut csv = Vec::new();
+        write_matrix_delimited_to(&mut csv, &names, &mat, b',').unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            ",\"weird,name\",plain\n\"weird,name\",0,4\nplain,4,0\n"
+        );
+
+        let mut tsv = Vec::new();
+        write_matrix_to(&mut tsv, &names, &mat).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "\tweird,name\tplain\nweird,name\t0\t4\nplain\t4\t0\n"
+        );
+
+        assert_eq!(quote_field("say \"hi\",", ','), "\"say \"\"hi\"\",\"");
+    }
+
+    #[test]
+    fn test_write_matrix_to() {
+        let names = vec!["t1".to_string(), "t2".to_string()];
+        let mat = vec![vec![0, 4], vec![4, 0]];
+
+        let mut buf = Vec::new();
+        write_matrix_to(&mut buf, &names, &mat).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "\tt1\tt2\nt1\t0\t4\nt2\t4\t0\n"
+        );
+    }
+
+    #[test]
+    fn test_write_matrix_rejects_mismatched_shape() {
+        let names = vec!["t1".to_string(), "t2".to_string()];
+        let mat = vec![vec![0, 4, 1], vec![4, 0, 2], vec![1, 2, 0]];
+
+        let err = write_matrix_to(Vec::new(), &names, &mat).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "matrix has 3 rows but 2 names were given");
+
+        // The file is not created for an invalid matrix
+        let dir = TempDir::new();
+        let path = dir.path("shape.tsv");
+        assert!(write_matrix_tsv(&path, &names, &mat).is_err());
+        assert!(!path.exists());
+
+        let ragged = vec![vec![0, 4], vec![4]];
+        let err = write_matrix_phylip_to(Vec::new(), &names, &ragged).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "matrix row 1 has 1 entries, expected 2 for a square matrix"
+        );
+    }
+
+    #[test]
+    fn test_write_matrix_with_precision() {
+        let names = vec!["t1".to_string(), "t2".to_string()];
+        let mat = vec![vec![0.0, 0.9999999999999999], vec![0.9999999999999999, 0.0]];
+        let mut buf = Vec::new();
+        write_matrix_to(&mut buf, &names, &FixedMatrix::new(&mat, Some(3))).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "\tt1\tt2\nt1\t0.000\t1.000\nt2\t1.000\t0.000\n"
+        );
+
+        // Integer RF matrices are unaffected
+        let rf = vec![vec![0, 4], vec![4, 0]];
+        let mut buf = Vec::new();
+        write_matrix_to(&mut buf, &names, &FixedMatrix::new(&rf, Some(3))).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "\tt1\tt2\nt1\t0\t4\nt2\t4\t0\n"
+        );
+
+        let dir = TempDir::new();
+        let path = dir.path("prec.tsv");
+        write_matrix_tsv_prec(&path, &names, &mat, 3).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("t1\t0.000\t1.000"));
+    }
+
+    #[test]
+    fn test_condensed_round_trip() {
+        let names: Vec<String> = ["t1", "t2", "t3"].map(String::from).into();
+        let mat = vec![vec![0, 2, 4], vec![2, 0, 6], vec![4, 6, 0]];
+        let pairs: Vec<(usize, usize, usize)> = (0..3)
+            .flat_map(|i| (i + 1..3).map(move |j| (i, j)))
+            .map(|(i, j)| (i, j, mat[i][j]))
+            .collect();
+
+        let mut buf = Vec::new();
+        write_condensed_to(&mut buf, &names, &pairs).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            text,
+            "tree_a\ttree_b\tdistance\nt1\tt2\t2\nt1\tt3\t4\nt2\tt3\t6\n"
+        );
+
+        // Rebuild the square matrix from the long format
+        let index = |name: &str| names.iter().position(|n| n == name).unwrap();
+        let mut rebuilt = vec![vec![0; 3]; 3];
+        for line in text.lines().skip(1) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let (i, j) = (index(fields[0]), index(fields[1]));
+            let dist: usize = fields[2].parse().unwrap();
+            rebuilt[i][j] = dist;
+            rebuilt[j][i] = dist;
+        }
+        assert_eq!(rebuilt, mat);
+
+        // An index past the names is an error, not a panic
+        let err = write_condensed_to(Vec::new(), &names, &[(0, 3, 1)]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_write_matrix_phylip_to() {
+        let names: Vec<String> = ["t1", "a_very_long_tree_name", "t3"]
+            .map(String::from)
+            .into();
+        let mat = vec![vec![0, 2, 4], vec![2, 0, 6], vec![4, 6, 0]];
+
+        let mut buf = Vec::new();
+        write_matrix_phylip_to(&mut buf, &names, &mat).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[0].trim().parse::<usize>().unwrap(), 3);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], "t1         0 2 4");
+        assert_eq!(lines[2], "a_very_lon 2 0 6");
+        assert_eq!(lines[3], "t3         4 6 0");
+    }
+
+    #[test]
+    fn test_write_matrix_nexus_to() {
+        let names: Vec<String> = ["t1", "tree 2", "it's"].map(String::from).into();
+        let mat = vec![
+            vec![0.0, 2.5, 4.0],
+            vec![2.5, 0.0, 6.0],
+            vec![4.0, 6.0, 0.0],
+        ];
+
+        let mut buf = Vec::new();
+        write_matrix_nexus_to(&mut buf, &names, &mat).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.starts_with("#NEXUS"));
+        assert!(text.contains("BEGIN DISTANCES;") && text.trim_end().ends_with("END;"));
+
+        // Parse the block back: NTAX, the quoted labels and the first matrix row
+        let ntax: usize = text
+            .split_once("NTAX=")
+            .and_then(|(_, rest)| rest.split(';').next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(ntax, names.len());
+
+        let unquote = |label: &str| match label.strip_prefix('\'') {
+            Some(inner) => inner.strip_suffix('\'').unwrap().replace("''", "'"),
+            None => label.to_string(),
+        };
+        let section = |start: &str| -> Vec<String> {
+            text.split_once(start)
+                .unwrap()
+                .1
+                .split_once(';')
+                .unwrap()
+                .0
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        let labels: Vec<String> = section("TAXLABELS").iter().map(|l| unquote(l)).collect();
+        assert_eq!(labels, names);
+
+        let rows = section("MATRIX");
+        assert_eq!(rows.len(), 3);
+        // Values are the last NTAX tokens; the label (which may hold spaces) precedes them
+        let mut tokens: Vec<&str> = rows[1].rsplitn(ntax + 1, ' ').collect();
+        let label = unquote(tokens.pop().unwrap());
+        let values: Vec<f64> = tokens.iter().rev().map(|v| v.parse().unwrap()).collect();
+        assert_eq!(label, "tree 2");
+        assert_eq!(values, mat[1]);
+        let first: Vec<&str> = rows[0].split_whitespace().collect();
+        assert_eq!(first, ["t1", "0", "2.5", "4"]);
+    }
+
+    #[test]
+    fn test_write_matrix_tsv_streaming_matches_eager() {
+        let names: Vec<String> = ["a", "b\tc", "d"].map(String::from).into();
+        let dist = |i: usize, j: usize| i.abs_diff(j) as f64 * 1.5;
+        let mat: Vec<Vec<f64>> = (0..3)
+            .map(|i| (0..3).map(|j| dist(i, j)).collect())
+            .collect();
+
+        let mut eager = Vec::new();
+        write_matrix_to(&mut eager, &names, &mat).unwrap();
+        // Rows are generated on demand, never collected
+        let mut streamed = Vec::new();
+        let rows = (0..3).map(|i| (0..3).map(move |j| dist(i, j)));
+        write_matrix_tsv_streaming(&mut streamed, &names, rows).unwrap();
+        assert_eq!(streamed, eager);
+
+        // Ragged rows and a wrong row count are rejected
+        let short = (0..3).map(|i| (0..i).map(move |j| dist(i, j)));
+        let err = write_matrix_tsv_streaming(Vec::new(), &names, short).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = write_matrix_tsv_streaming(Vec::new(), &names, mat.iter().take(2)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let extra = mat.iter().chain(mat.iter());
+        let err = write_matrix_tsv_streaming(Vec::new(), &names, extra).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_write_column_to() {
+        let names = vec!["t1".to_string(), "t2".to_string()];
+
+        let mut buf = Vec::new();
+        write_column_to(&mut buf, "mcc", &names, &[0.0, 2.5]).unwrap();
+
+        assert_eq!(String::from_utf8(buf).unwrap(), "\tmcc\nt1\t0\nt2\t2.5\n");
+    }
+}
@@ -3,21 +3,37 @@
 //! Modules:
 //! - `distances`: generic distance trait + RF / cluster affinity implementations.
 //! - `io`: reading and parsing BEAST/NEXUS tree files.
+//! - `matrix`: flat upper-triangle storage for symmetric distance matrices.
 //! - `bitset`: compact bitset representation for tree partitions.
-//! - `snapshot`: tree snapshot for efficient distance calculations.
+//! - `snapshot`: tree snapshot for efficient distance calculations (cacheable with the
+//!   "serde" feature).
+//! - `consensus`: summary trees built from snapshot partitions.
+//! - `stats`: single-tree summary statistics (e.g. diameter).
+//! - `utils`: tree preprocessing such as pruning to a common taxon set.
+//! - `monitor`: online diagnostics for trees arriving in batches.
+//! - `sketch`: MinHash sketches for approximate RF on large tree sets.
+//! - `bhv`: Billera–Holmes–Vogtmann geodesic distance between weighted trees.
 //! - `api`: Python bindings via `pyo3` (gated behind "python" feature).
 //!
 //! Public API kept stable by re-exporting key items from the new modules.
 
+pub mod bhv;
 pub mod bitset;
+pub mod consensus;
 pub mod distances;
 pub mod io;
+pub mod matrix;
+pub mod monitor;
+pub mod sketch;
 pub mod snapshot;
+pub mod stats;
+pub mod utils;
 
 #[cfg(feature = "python")]
 pub mod api;
 
 // Re-export frequently used types & functions
 pub use bitset::Bitset;
-pub use io::{read_beast_trees, write_matrix_tsv};
+pub use io::{ReadError, read_beast_trees, write_matrix_tsv};
+pub use matrix::SymMatrix;
 pub use snapshot::TreeSnapshot;
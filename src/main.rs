@@ -1,12 +1,30 @@
-use clap::{Parser, ValueEnum};
-use rayon::prelude::*;
+use clap::{ArgGroup, Parser, ValueEnum};
+use phylotree::tree::Tree;
 use rust_python_tree_distances::distances::{
-    kf_from_snapshots, rf_from_snapshots, weighted_rf_from_snapshots,
+    JaccardRobinsonFoulds, KuhnerFelsenstein, KuhnerFelsensteinSquared, LpBranchScore,
+    NormalizedRobinsonFoulds, NormalizedWeightedRobinsonFoulds, Progress, RobinsonFoulds,
+    TreeDistance, WeightedRobinsonFoulds, cluster_order, compute_pairs, compute_sym_matrix,
+    dedup_snapshots, distances_to_reference, expand_matrix, max_rf, medoid_from_snapshots,
+    medoid_index, pairwise_iter, row_means,
 };
-use rust_python_tree_distances::io::{read_beast_trees, write_matrix_tsv};
+use rust_python_tree_distances::io::{
+    DEFAULT_NAME_TEMPLATE, Fixed, FixedMatrix, ParseSummary, ReadError, burnin_trees_for_fraction,
+    file_tree_name, find_trees_by_state, iter_beast_trees, read_beast_tree_files_fraction,
+    read_beast_tree_files_with, read_beast_trees, read_beast_trees_with, write_column_tsv,
+    write_condensed_stream, write_matrix_delimited, write_matrix_nexus, write_matrix_phylip,
+    write_matrix_tsv,
+};
+use rust_python_tree_distances::matrix::SymMatrix;
 use rust_python_tree_distances::snapshot::TreeSnapshot;
+use rust_python_tree_distances::stats::patristic_matrix;
+use rust_python_tree_distances::utils::{exclude_taxa, prune_to_common_taxa};
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two progress lines during the pairwise computation.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
 
 /// Compute pairwise Robinson–Foulds distances from a BEAST/NEXUS tree file
 /// and write a labeled distance matrix (TSV) where row/column names are tree names.
@@ -14,12 +32,13 @@ use std::time::Instant;
 #[command(
     name = "tree-dists",
     version,
-    about = "Pairwise RF distance matrix for BEAST trees"
+    about = "Pairwise RF distance matrix for BEAST trees",
+    group = ArgGroup::new("burnin").args(["burnin_trees", "burnin_states", "burnin_fraction"])
 )]
 struct Args {
-    /// Path to BEAST .trees (NEXUS) file
-    #[arg(short = 'i', long = "input")]
-    input: PathBuf,
+    /// Path(s) to BEAST .trees (NEXUS) files; trees of several files are merged with a fileN_ prefix
+    #[arg(short = 'i', long = "input", num_args = 1.., required = true)]
+    input: Vec<PathBuf>,
 
     /// Burn-in by number of trees (drop first N trees)
     #[arg(short = 't', long = "burnin-trees", default_value_t = 0)]
@@ -29,19 +48,137 @@ struct Args {
     #[arg(short = 's', long = "burnin-states", default_value_t = 0)]
     burnin_states: usize,
 
+    /// Burn-in by fraction of each file's trees (drop first floor(F * total), e.g. 0.1)
+    #[arg(long = "burnin-fraction", value_name = "F", value_parser = parse_fraction)]
+    burnin_fraction: Option<f64>,
+
     /// Output path for TSV distance matrix
-    #[arg(short = 'o', long = "output")]
-    output: PathBuf,
+    #[arg(
+        short = 'o',
+        long = "output",
+        required_unless_present_any = ["pair_states", "medoid"]
+    )]
+    output: Option<PathBuf>,
+
+    /// Only compute the distance between the trees at two STATE numbers (e.g. 500000,1000000)
+    #[arg(long = "pair-states", value_name = "STATE_A,STATE_B", value_parser = parse_state_pair)]
+    pair_states: Option<(usize, usize)>,
+
+    /// Only report the medoid tree (smallest mean distance to all others) without building the
+    /// matrix; trees are snapshotted as they are read, so only the snapshots are kept in memory
+    #[arg(
+        long = "medoid",
+        default_value_t = false,
+        conflicts_with_all = ["pair_states", "prune_to_common"]
+    )]
+    medoid: bool,
+
+    /// Reference tree file (NEXUS); write each tree's distance to its first tree instead of the matrix
+    #[arg(
+        long = "reference",
+        value_name = "PATH",
+        conflicts_with_all = ["pair_states", "medoid"]
+    )]
+    reference: Option<PathBuf>,
+
+    /// Write the leaf-to-leaf patristic distance matrix of the tree at this STATE number instead
+    #[arg(
+        long = "patristic",
+        value_name = "STATE",
+        conflicts_with_all = ["pair_states", "medoid", "reference"]
+    )]
+    patristic: Option<usize>,
 
     /// Use TRANSLATE block to map taxon IDs to labels when available
     #[arg(long = "use-real-taxa", default_value_t = false)]
     use_real_taxa: bool,
 
-    /// Distance metric to compute: rf | weighted | kf
+    /// Fail on the first malformed tree instead of skipping it
+    #[arg(long = "strict", default_value_t = false)]
+    strict: bool,
+
+    /// Tree name template with {base}, {index} and {state} placeholders, e.g. {base}.{index};
+    /// only the default is prefixed with fileN_ for several files
+    #[arg(
+        long = "name-template",
+        value_name = "TEMPLATE",
+        default_value = DEFAULT_NAME_TEMPLATE
+    )]
+    name_template: String,
+
+    /// Prune all trees to the taxa they have in common before comparing
+    #[arg(long = "prune-to-common", default_value_t = false)]
+    prune_to_common: bool,
+
+    /// Remove these tips (comma-separated names) from every tree before comparing
+    #[arg(long = "exclude-taxa", value_name = "NAMES", value_delimiter = ',')]
+    exclude_taxa: Vec<String>,
+
+    /// Distance metric to compute: rf | weighted | wrf-norm | kf | kf2 | norm-rf | jrf | lp
     #[arg(long = "metric", value_enum, default_value_t = MetricArg::Rf)]
     metric: MetricArg,
 
-    /// Quiet mode: suppresses progress messages on stdout
+    /// Exponent k of the Jaccard-RF metric (only used with --metric jrf)
+    #[arg(long = "jrf-k", default_value_t = 1.0, value_parser = parse_positive_f64)]
+    jrf_k: f64,
+
+    /// Exponent p of the Lp branch score, `inf` for the largest difference (only used with --metric lp)
+    #[arg(long = "lp-p", default_value_t = 2.0, value_parser = parse_lp_exponent)]
+    lp_p: f64,
+
+    /// Output layout: full square matrix (TSV or CSV), condensed upper-triangle pairs, or PHYLIP matrix
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Matrix)]
+    format: OutputFormat,
+
+    /// Round floating-point distances to N decimals in the output; integer distances are unaffected
+    #[arg(long = "precision")]
+    precision: Option<usize>,
+
+    /// Compare only one tree per topology and copy its distances to identical trees
+    #[arg(
+        long = "dedup",
+        default_value_t = false,
+        conflicts_with_all = ["pair_states", "medoid", "reference", "patristic"]
+    )]
+    dedup: bool,
+
+    /// Like --dedup, but trees must also have identical branch lengths (for weighted metrics)
+    #[arg(
+        long = "dedup-weighted",
+        default_value_t = false,
+        conflicts_with_all = ["dedup", "pair_states", "medoid", "reference", "patristic"]
+    )]
+    dedup_weighted: bool,
+
+    /// Write condensed pairs as they are computed, without holding the matrix in memory
+    #[arg(
+        long = "streaming",
+        default_value_t = false,
+        conflicts_with_all = ["format", "dedup", "dedup_weighted", "pair_states", "medoid", "reference", "patristic"]
+    )]
+    streaming: bool,
+
+    /// Write each tree's mean distance to all others instead of the matrix
+    #[arg(
+        long = "summary",
+        default_value_t = false,
+        conflicts_with_all = ["format", "streaming", "pair_states", "medoid", "reference", "patristic"]
+    )]
+    summary: bool,
+
+    /// Reorder the matrix rows and columns by UPGMA clustering so similar trees are adjacent
+    #[arg(
+        long = "cluster-order",
+        default_value_t = false,
+        conflicts_with_all = ["streaming", "summary", "pair_states", "medoid", "reference", "patristic"]
+    )]
+    cluster_order: bool,
+
+    /// Number of worker threads for snapshots and distances (0 = all cores)
+    #[arg(long = "threads", default_value_t = 0)]
+    threads: usize,
+
+    /// Quiet mode: suppresses progress messages on stderr
     #[arg(short = 'q', long = "quiet", default_value_t = false)]
     quiet: bool,
 }
@@ -50,26 +187,105 @@ struct Args {
 enum MetricArg {
     Rf,
     Weighted,
+    WrfNorm,
     Kf,
+    Kf2,
+    NormRf,
+    Jrf,
+    Lp,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum OutputFormat {
+    Matrix,
+    Csv,
+    Condensed,
+    Phylip,
+    Nexus,
 }
 
 fn main() {
     let args = Args::parse();
 
+    // Run all parallel work on a dedicated pool so --threads bounds CPU usage
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build()
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to build thread pool: {e}");
+            std::process::exit(1);
+        });
+    pool.install(|| match args.metric {
+        MetricArg::Rf => run(&args, &RobinsonFoulds),
+        MetricArg::Weighted => run(&args, &WeightedRobinsonFoulds),
+        MetricArg::WrfNorm => run(&args, &NormalizedWeightedRobinsonFoulds),
+        MetricArg::Kf => run(&args, &KuhnerFelsenstein),
+        MetricArg::Kf2 => run(&args, &KuhnerFelsensteinSquared),
+        MetricArg::NormRf => run(&args, &NormalizedRobinsonFoulds),
+        MetricArg::Jrf => run(&args, &JaccardRobinsonFoulds { k: args.jrf_k }),
+        MetricArg::Lp => {
+            let metric = LpBranchScore {
+                p: args.lp_p,
+                ..Default::default()
+            };
+            run(&args, &metric)
+        }
+    });
+}
+
+/// Read, filter and name the trees of all input files, reporting progress on stderr.
+/// Also returns the `STATE_` number of each tree, in the same order.
+///
+/// Exits the process if a file cannot be read or a filter fails.
+fn read_trees(args: &Args) -> (Vec<(String, Tree)>, Vec<usize>) {
     // Read trees with names
     let t0 = Instant::now();
-    let (taxons, named_trees) = read_beast_trees(
-        &args.input,
-        args.burnin_trees,
-        args.burnin_states,
-        args.use_real_taxa,
-    );
-    if named_trees.is_empty() {
-        eprintln!("No trees parsed from {:?}.", args.input);
-        std::process::exit(2);
-    }
+    // A single file keeps its plain tree names; several files are prefixed per file
+    let read = match (args.input.as_slice(), args.burnin_fraction) {
+        ([path], fraction) => fraction
+            .map_or(Ok(args.burnin_trees), |f| {
+                burnin_trees_for_fraction(path, f)
+            })
+            .and_then(|burnin_trees| {
+                read_beast_trees_with(
+                    path,
+                    burnin_trees,
+                    args.burnin_states,
+                    args.use_real_taxa,
+                    args.strict,
+                    &args.name_template,
+                )
+            })
+            .map(|(taxons, trees, summary)| (taxons, trees, vec![summary]))
+            .map_err(|e| (0, e)),
+        (paths, Some(fraction)) => read_beast_tree_files_fraction(
+            paths,
+            fraction,
+            args.use_real_taxa,
+            args.strict,
+            &args.name_template,
+        ),
+        (paths, None) => read_beast_tree_files_with(
+            paths,
+            args.burnin_trees,
+            args.burnin_states,
+            args.use_real_taxa,
+            args.strict,
+            &args.name_template,
+        ),
+    };
+    let (taxons, named_trees, summaries) =
+        read.unwrap_or_else(|(file_idx, e)| exit_read_error(&args.input[file_idx], e));
     let read_s = t0.elapsed().as_secs_f64();
     log_if(!args.quiet, format!("Reading in beast {read_s:.3}s"));
+    log_if(
+        !args.quiet,
+        format!(
+            "Parsed {} trees, skipped {} malformed",
+            summaries.iter().map(|s| s.parsed).sum::<usize>(),
+            summaries.iter().map(ParseSummary::skipped).sum::<usize>()
+        ),
+    );
     log_if(
         !args.quiet,
         format!(
@@ -78,33 +294,191 @@ fn main() {
             named_trees.len()
         ),
     );
-    let (names, trees): (Vec<String>, Vec<_>) = named_trees.into_iter().unzip();
+    let states: Vec<usize> = summaries
+        .into_iter()
+        .flat_map(|summary| summary.states)
+        .collect();
+    let named_trees = if args.exclude_taxa.is_empty() {
+        named_trees
+    } else {
+        let (names, mut trees): (Vec<String>, Vec<Tree>) = named_trees.into_iter().unzip();
+        let missing = exclude_taxa(&mut trees, &args.exclude_taxa).unwrap_or_else(|e| {
+            eprintln!("Failed to exclude taxa: {e}");
+            std::process::exit(3);
+        });
+        if !missing.is_empty() {
+            eprintln!(
+                "Warning: taxa not found in any tree: {}",
+                missing.join(", ")
+            );
+        }
+        log_if(
+            !args.quiet,
+            format!("Excluded {} taxa", args.exclude_taxa.len() - missing.len()),
+        );
+        names.into_iter().zip(trees).collect()
+    };
+    let named_trees = if args.prune_to_common {
+        let (names, mut trees): (Vec<String>, Vec<Tree>) = named_trees.into_iter().unzip();
+        let common = prune_to_common_taxa(&mut trees).unwrap_or_else(|e| {
+            eprintln!("Failed to prune trees to common taxa: {e}");
+            std::process::exit(3);
+        });
+        log_if(
+            !args.quiet,
+            format!("Pruned trees to {} common taxa", common.len()),
+        );
+        names.into_iter().zip(trees).collect()
+    } else {
+        named_trees
+    };
+    (named_trees, states)
+}
+
+/// The one tree with `STATE_<state>`, exiting if there is none or if several trees
+/// (e.g. from different input files) share it.
+fn tree_at_state<'a>(
+    args: &Args,
+    named_trees: &'a [(String, Tree)],
+    states: &[usize],
+    state: usize,
+) -> &'a Tree {
+    match find_trees_by_state(named_trees, states, state).as_slice() {
+        [] => {
+            eprintln!("No tree with STATE_{state} in {:?}.", args.input);
+            std::process::exit(2);
+        }
+        [(_, tree)] => tree,
+        found => {
+            let names: Vec<&str> = found.iter().map(|(name, _)| name.as_str()).collect();
+            eprintln!(
+                "Several trees have STATE_{state} ({}); pass a single --input file.",
+                names.join(", ")
+            );
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Report a file that could not be read, then exit.
+fn exit_read_error(path: &Path, e: ReadError) -> ! {
+    match e {
+        ReadError::NoTrees => eprintln!("No trees parsed from {:?}.", path),
+        e => eprintln!("Failed to read {:?}: {e}", path),
+    }
+    std::process::exit(2);
+}
+
+/// Run the selected mode (pair, medoid, reference or full matrix) with any distance metric.
+fn run<D: TreeDistance>(args: &Args, metric: &D) {
+    if args.medoid {
+        run_medoid(args, metric);
+        return;
+    }
+
+    let (named_trees, states) = read_trees(args);
+    if let Some(state) = args.patristic {
+        write_patristic(
+            args,
+            tree_at_state(args, &named_trees, &states, state),
+            state,
+        );
+        return;
+    }
+    let metric_label = metric.name();
+
+    if let Some((state_a, state_b)) = args.pair_states {
+        let snap = |state: usize| {
+            let tree = tree_at_state(args, &named_trees, &states, state);
+            TreeSnapshot::from_tree(tree, metric.include_trivial()).unwrap_or_else(|e| {
+                eprintln!("Failed to build snapshot for STATE_{state}: {e}");
+                std::process::exit(3);
+            })
+        };
+        let (snap_a, snap_b) = (snap(state_a), snap(state_b));
+        log_if(
+            !args.quiet,
+            format!(
+                "Determining distance using {metric_label} between STATE_{state_a} and STATE_{state_b}"
+            ),
+        );
+        println!(
+            "{}",
+            Fixed::new(metric.distance(&snap_a, &snap_b), args.precision)
+        );
+        return;
+    }
 
-    // Build bitset snapshots once
+    // Build bitset snapshots once, in parallel; each parsed tree is dropped once its
+    // snapshot exists. Trees that fail are skipped, or stop the run with --strict
     let t1 = Instant::now();
-    let snaps: Vec<TreeSnapshot> = trees
-        .iter()
-        .map(TreeSnapshot::from_tree)
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap_or_else(|e| {
-            eprintln!("Failed to build snapshots: {e}");
+    let (built, failed) = TreeSnapshot::build_snapshots(named_trees, metric.include_trivial());
+    for (name, e) in &failed {
+        if args.strict {
+            eprintln!("Failed to build snapshot for tree {name}: {e}");
             std::process::exit(3);
-        });
+        }
+        eprintln!("Skipped tree {name}: {e}");
+    }
+    if built.is_empty() {
+        eprintln!("No tree could be snapshotted.");
+        std::process::exit(3);
+    }
+    let (names, snaps): (Vec<String>, Vec<TreeSnapshot>) = built.into_iter().unzip();
     let snap_s = t1.elapsed().as_secs_f64();
     log_if(
         !args.quiet,
         format!("Creating tree bit snapshots {snap_s:.3}s"),
     );
+    if matches!(args.metric, MetricArg::Rf)
+        && let Some(first) = snaps.first()
+    {
+        log_if(!args.quiet, format!("Max possible RF: {}", max_rf(first)));
+    }
 
-    let t2 = Instant::now();
-    let (metric_label, metric_fn): (&str, fn(&TreeSnapshot, &TreeSnapshot) -> f64) =
-        match args.metric {
-            // rf is the only one that returns usize, so cast to f64
-            MetricArg::Rf => ("RF", |a, b| rf_from_snapshots(a, b) as f64),
-            MetricArg::Weighted => ("Weighted", weighted_rf_from_snapshots),
-            MetricArg::Kf => ("KF", kf_from_snapshots),
-        };
+    let output = args
+        .output
+        .as_ref()
+        .expect("clap requires --output outside of --pair-states/--medoid mode");
 
+    if let Some(reference_path) = &args.reference {
+        let (_, reference_trees) = read_beast_trees(reference_path, 0, 0, args.use_real_taxa)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to read reference {:?}: {e}", reference_path);
+                std::process::exit(2);
+            });
+        let (reference_name, reference_tree) = reference_trees
+            .into_iter()
+            .next()
+            .expect("read_beast_trees returns at least one tree");
+        let reference = TreeSnapshot::from_tree(&reference_tree, metric.include_trivial())
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to build snapshot for reference: {e}");
+                std::process::exit(3);
+            });
+
+        let t2 = Instant::now();
+        let dists: Vec<_> = distances_to_reference(&reference, &snaps, metric)
+            .into_iter()
+            .map(|dist| Fixed::new(dist, args.precision))
+            .collect();
+        let comp_s = t2.elapsed().as_secs_f64();
+        log_if(
+            !args.quiet,
+            format!("Determining distances to reference using {metric_label} {comp_s:.3}s"),
+        );
+
+        let t3 = Instant::now();
+        if let Err(e) = write_column_tsv(output, &reference_name, &names, &dists) {
+            eprintln!("Failed to write output {:?}: {e}", output);
+            std::process::exit(4);
+        }
+        let write_s = t3.elapsed().as_secs_f64();
+        log_write_done(!args.quiet, output, write_s);
+        return;
+    }
+
+    let t2 = Instant::now();
     log_if(
         !args.quiet,
         format!(
@@ -113,53 +487,345 @@ fn main() {
         ),
     );
 
-    let n = names.len();
+    // Compute distances in parallel; the condensed format writes the pairs directly
+    let printer = progress_printer();
+    let progress: Option<Progress> = (!args.quiet).then_some(&printer);
+    let t3;
+    let written = match args.format {
+        _ if args.streaming => {
+            // Pairs are written as soon as their row is done: computing and writing
+            // overlap, so both are timed together
+            t3 = t2;
+            let pairs = pairwise_iter(&snaps, metric)
+                .map(|(i, j, dist)| (i, j, Fixed::new(dist, args.precision)));
+            write_condensed_stream(output, &names, pairs)
+        }
+        OutputFormat::Condensed if !(args.dedup || args.dedup_weighted || args.cluster_order) => {
+            let pairs = compute_pairs(&snaps, metric, progress);
+            log_distances_done(!args.quiet, metric_label, t2);
+            t3 = Instant::now();
+            let pairs = pairs
+                .into_iter()
+                .map(|(i, j, dist)| (i, j, Fixed::new(dist, args.precision)));
+            write_condensed_stream(output, &names, pairs)
+        }
+        _ if args.summary => {
+            let mat = full_matrix(args, &snaps, metric, progress);
+            log_distances_done(!args.quiet, metric_label, t2);
+            t3 = Instant::now();
+            let means: Vec<_> = row_means(&mat)
+                .into_iter()
+                .map(|mean| Fixed::new(mean, args.precision))
+                .collect();
+            if !args.quiet {
+                let medoid = medoid_index(&mat);
+                eprintln!(
+                    "Medoid: {} (mean distance {})",
+                    names[medoid], means[medoid]
+                );
+            }
+            write_column_tsv(output, "mean_distance", &names, &means)
+        }
+        format => {
+            let mut mat = full_matrix(args, &snaps, metric, progress);
+            let mut names = names;
+            if args.cluster_order {
+                let order = cluster_order(&mat);
+                mat = mat.permuted(&order);
+                names = order.iter().map(|&i| names[i].clone()).collect();
+            }
+            log_distances_done(!args.quiet, metric_label, t2);
+            t3 = Instant::now();
+            write_full_matrix(format, output, &names, &mat, args.precision)
+        }
+    };
+    if let Err(e) = written {
+        eprintln!("Failed to write output {:?}: {e}", output);
+        std::process::exit(4);
+    }
+    let write_s = t3.elapsed().as_secs_f64();
+    log_write_done(!args.quiet, output, write_s);
+}
+
+/// Write a full distance matrix in the chosen output format, rounded to `precision`
+/// decimals if given.
+fn write_full_matrix<T: std::fmt::Display + Copy + Default>(
+    format: OutputFormat,
+    output: &Path,
+    names: &[String],
+    mat: &SymMatrix<T>,
+    precision: Option<usize>,
+) -> std::io::Result<()> {
+    let fixed = FixedMatrix::new(mat, precision);
+    match format {
+        OutputFormat::Matrix => write_matrix_tsv(output, names, &fixed),
+        OutputFormat::Csv => write_matrix_delimited(output, names, &fixed, b','),
+        OutputFormat::Phylip => write_matrix_phylip(output, names, &fixed),
+        OutputFormat::Nexus => write_matrix_nexus(output, names, &fixed),
+        OutputFormat::Condensed => {
+            let pairs = mat
+                .pairs()
+                .map(|(i, j, dist)| (i, j, Fixed::new(dist, precision)));
+            write_condensed_stream(output, names, pairs)
+        }
+    }
+}
 
-    // Compute distances in parallel
-    let pairs: Vec<(usize, usize, f64)> = (0..n)
-        .into_par_iter()
-        .flat_map_iter(|i| (i + 1..n).map(move |j| (i, j)))
-        .map(|(i, j)| {
-            let dist = metric_fn(&snaps[i], &snaps[j]);
-            (i, j, dist)
-        })
+/// Full distance matrix, deduplicated if `--dedup` or `--dedup-weighted` is set.
+fn full_matrix<D: TreeDistance>(
+    args: &Args,
+    snaps: &[TreeSnapshot],
+    metric: &D,
+    progress: Option<Progress>,
+) -> SymMatrix<D::Output> {
+    if args.dedup || args.dedup_weighted {
+        dedup_matrix(args, snaps, metric, progress)
+    } else {
+        compute_sym_matrix(snaps, metric, progress)
+    }
+}
+
+/// Full distance matrix computed between one representative per group of identical
+/// trees (the `--dedup` / `--dedup-weighted` modes).
+fn dedup_matrix<D: TreeDistance>(
+    args: &Args,
+    snaps: &[TreeSnapshot],
+    metric: &D,
+    progress: Option<Progress>,
+) -> SymMatrix<D::Output> {
+    if args.dedup && metric.include_trivial() {
+        eprintln!(
+            "Warning: --dedup ignores branch lengths, which {} uses; consider --dedup-weighted.",
+            metric.name()
+        );
+    }
+    let (representatives, group_of) = dedup_snapshots(snaps, args.dedup_weighted);
+    log_if(
+        !args.quiet,
+        format!(
+            "Deduplicated {} trees to {} unique trees",
+            snaps.len(),
+            representatives.len()
+        ),
+    );
+
+    let unique: Vec<TreeSnapshot> = representatives
+        .iter()
+        .map(|&idx| snaps[idx].clone())
         .collect();
+    let unique_mat = compute_sym_matrix(&unique, metric, progress);
+    expand_matrix(&unique_mat, &group_of)
+}
 
-    let mut mat = vec![vec![0.0f64; n]; n];
-    for (i, j, d) in pairs {
-        mat[i][j] = d;
-        mat[j][i] = d;
+/// Print the medoid tree and its mean distance (the `--medoid` mode).
+///
+/// Trees are streamed from the files one at a time: each is filtered, snapshotted and
+/// dropped before the next is parsed. Snapshots only hold the canonical bitsets and
+/// their lengths, far less than a parsed tree, so peak memory is the snapshots plus
+/// one tree, and [`medoid_from_snapshots`] adds just one running sum per tree.
+fn run_medoid<D: TreeDistance>(args: &Args, metric: &D) {
+    let t0 = Instant::now();
+    let mut names = Vec::new();
+    let mut snaps = Vec::new();
+    let (mut parsed, mut skipped) = (0, 0);
+    // Excluded taxa absent from every tree read so far
+    let mut missing: Option<BTreeSet<String>> = None;
+
+    for (file_idx, path) in args.input.iter().enumerate() {
+        let burnin_trees = match args.burnin_fraction {
+            Some(fraction) => burnin_trees_for_fraction(path, fraction)
+                .unwrap_or_else(|e| exit_read_error(path, e)),
+            None => args.burnin_trees,
+        };
+        let trees = iter_beast_trees(path, burnin_trees, args.burnin_states, args.use_real_taxa)
+            .unwrap_or_else(|e| exit_read_error(path, e))
+            .with_name_template(&args.name_template);
+
+        let parsed_before = parsed;
+        for item in trees {
+            let (name, mut tree) = match item {
+                Ok(named_tree) => named_tree,
+                Err(e @ ReadError::Parse { .. }) if !args.strict => {
+                    eprintln!("Skipping tree in {}: {e}", path.display());
+                    skipped += 1;
+                    continue;
+                }
+                Err(e) => exit_read_error(path, e),
+            };
+            parsed += 1;
+            // A single file keeps its plain tree names, as in the matrix modes
+            let name = if args.input.len() > 1 {
+                file_tree_name(file_idx, name, &args.name_template)
+            } else {
+                name
+            };
+
+            if !args.exclude_taxa.is_empty() {
+                let absent = exclude_taxa(std::slice::from_mut(&mut tree), &args.exclude_taxa)
+                    .unwrap_or_else(|e| {
+                        eprintln!("Failed to exclude taxa: {e}");
+                        std::process::exit(3);
+                    });
+                let absent: BTreeSet<String> = absent.into_iter().collect();
+                missing = Some(match missing {
+                    Some(missing) => missing.intersection(&absent).cloned().collect(),
+                    None => absent,
+                });
+            }
+
+            match TreeSnapshot::from_tree(&tree, metric.include_trivial()) {
+                Ok(snap) => {
+                    names.push(name);
+                    snaps.push(snap);
+                }
+                Err(e) if args.strict => {
+                    eprintln!("Failed to build snapshot for tree {name}: {e}");
+                    std::process::exit(3);
+                }
+                Err(e) => {
+                    eprintln!("Skipped tree {name}: {e}");
+                    skipped += 1;
+                }
+            }
+        }
+        if parsed == parsed_before {
+            exit_read_error(path, ReadError::NoTrees);
+        }
     }
 
-    let comp_s = t2.elapsed().as_secs_f64();
+    if let Some(missing) = missing.filter(|missing| !missing.is_empty()) {
+        let missing: Vec<String> = missing.into_iter().collect();
+        eprintln!(
+            "Warning: taxa not found in any tree: {}",
+            missing.join(", ")
+        );
+    }
+    if snaps.is_empty() {
+        eprintln!("No tree could be snapshotted.");
+        std::process::exit(3);
+    }
+    let read_s = t0.elapsed().as_secs_f64();
     log_if(
         !args.quiet,
-        format!("Determining distances using {metric_label} {comp_s:.3}s"),
+        format!(
+            "Read and snapshotted {} trees, skipped {skipped}, in {read_s:.3}s",
+            snaps.len()
+        ),
     );
 
-    let t3 = Instant::now();
-    if let Err(e) = write_matrix_tsv(&args.output, &names, &mat) {
-        eprintln!("Failed to write output {:?}: {e}", args.output);
+    let t1 = Instant::now();
+    let (index, mean) =
+        medoid_from_snapshots(&snaps, |a, b| metric.distance_f64(a, b)).expect("trees not empty");
+    let comp_s = t1.elapsed().as_secs_f64();
+    log_if(
+        !args.quiet,
+        format!("Determining medoid using {} {comp_s:.3}s", metric.name()),
+    );
+    println!("{}\t{mean}", names[index]);
+}
+
+/// Write the patristic distance matrix of `tree`, the tree at `state` (the
+/// `--patristic` mode).
+fn write_patristic(args: &Args, tree: &Tree, state: usize) {
+    let (leaves, matrix) = patristic_matrix(tree).unwrap_or_else(|e| {
+        eprintln!("Failed to compute patristic distances for STATE_{state}: {e}");
+        std::process::exit(3);
+    });
+
+    let output = args
+        .output
+        .as_ref()
+        .expect("clap requires --output outside of --pair-states/--medoid mode");
+    let t0 = Instant::now();
+    if let Err(e) = write_matrix_tsv(output, &leaves, &FixedMatrix::new(&matrix, args.precision)) {
+        eprintln!("Failed to write output {:?}: {e}", output);
         std::process::exit(4);
     }
-    let write_s = t3.elapsed().as_secs_f64();
-    log_write_done(!args.quiet, &args.output, write_s);
+    log_write_done(!args.quiet, output, t0.elapsed().as_secs_f64());
+}
+
+fn parse_state_pair(s: &str) -> Result<(usize, usize), String> {
+    let (a, b) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected two comma-separated states, got '{s}'"))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<usize>()
+            .map_err(|e| format!("invalid state '{v}': {e}"))
+    };
+    Ok((parse(a)?, parse(b)?))
+}
+
+fn parse_positive_f64(s: &str) -> Result<f64, String> {
+    let value = s
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("invalid number '{s}': {e}"))?;
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("expected a positive number, got '{s}'"))
+    }
+}
+
+/// A positive `--lp-p` exponent, where `inf` is allowed too.
+fn parse_lp_exponent(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(f64::INFINITY) => Ok(f64::INFINITY),
+        _ => parse_positive_f64(s),
+    }
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    let value = s
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("invalid number '{s}': {e}"))?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("expected a fraction between 0 and 1, got '{s}'"))
+    }
 }
 
 fn log_if(show: bool, msg: String) {
     if show {
-        println!("{}", msg);
+        eprintln!("{}", msg);
     }
 }
 
+/// Progress callback printing the share of pairs done, at most once every few seconds.
+fn progress_printer() -> impl Fn(usize, usize) + Sync {
+    let last = Mutex::new(Instant::now());
+    move |done, total| {
+        // Another thread printing right now is as good as printing ourselves
+        let Ok(mut last) = last.try_lock() else {
+            return;
+        };
+        if last.elapsed() >= PROGRESS_INTERVAL {
+            *last = Instant::now();
+            let pct = 100.0 * done as f64 / total.max(1) as f64;
+            eprintln!("  {done}/{total} pairs ({pct:.1}%)");
+        }
+    }
+}
+
+fn log_distances_done(show: bool, metric_label: &str, start: Instant) {
+    let comp_s = start.elapsed().as_secs_f64();
+    log_if(
+        show,
+        format!("Determining distances using {metric_label} {comp_s:.3}s"),
+    );
+}
+
 fn log_write_done(show: bool, output: &Path, secs: f64) {
     if !show {
         return;
     }
     let is_stdout = output.as_os_str() == "-";
     if is_stdout {
-        println!("Writing to stdout {secs:.3}s");
+        eprintln!("Writing to stdout {secs:.3}s");
     } else {
-        println!("Writing to output {secs:.3}s");
+        eprintln!("Writing to output {secs:.3}s");
     }
 }
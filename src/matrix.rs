@@ -0,0 +1,275 @@
+//! Compact storage for symmetric distance matrices.
+//!
+//! # Overview
+//! A distance matrix is symmetric with a zero diagonal, so only the `n(n-1)/2`
+//! entries above the diagonal carry information. [`SymMatrix`] stores exactly those
+//! in one flat `Vec`, which halves the memory of a nested `Vec<Vec<T>>` and avoids
+//! one pointer indirection per row.
+//!
+//! # Layout
+//! Entries are stored row by row, (0, 1), (0, 2), …, (1, 2), …, which is the
+//! condensed order of SciPy's `squareform` and of
+//! [`compute_pairs`](crate::distances::compute_pairs).
+//!
+//! The writers in [`io`](crate::io) accept any [`MatrixRows`], so both layouts can be
+//! written without converting.
+
+/// A square matrix that can be read row by row.
+pub trait MatrixRows {
+    /// Type of a single entry.
+    type Item: Copy;
+
+    /// Number of rows (and columns).
+    fn size(&self) -> usize;
+
+    /// The entries of row `i`, in column order.
+    fn row(&self, i: usize) -> impl Iterator<Item = Self::Item> + '_;
+
+    /// Number of entries in row `i`; equals [`size`](MatrixRows::size) for a square matrix.
+    fn row_len(&self, i: usize) -> usize {
+        self.row(i).count()
+    }
+}
+
+impl<T: Copy> MatrixRows for [Vec<T>] {
+    type Item = T;
+
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    fn row(&self, i: usize) -> impl Iterator<Item = T> + '_ {
+        self[i].iter().copied()
+    }
+
+    fn row_len(&self, i: usize) -> usize {
+        self[i].len()
+    }
+}
+
+impl<T: Copy> MatrixRows for Vec<Vec<T>> {
+    type Item = T;
+
+    fn size(&self) -> usize {
+        self.len()
+    }
+
+    fn row(&self, i: usize) -> impl Iterator<Item = T> + '_ {
+        self[i].iter().copied()
+    }
+
+    fn row_len(&self, i: usize) -> usize {
+        self[i].len()
+    }
+}
+
+/// Symmetric matrix with a zero diagonal, storing only the upper triangle.
+///
+/// `get(i, j)` and `get(j, i)` read the same entry; the diagonal always reads as
+/// `T::default()`.
+///
+/// # Example
+/// ```
+/// # use rust_python_tree_distances::matrix::SymMatrix;
+/// let mut m = SymMatrix::new(3);
+/// m.set(2, 0, 4);
+/// assert_eq!(m.get(0, 2), 4);
+/// assert_eq!(m.get(1, 1), 0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymMatrix<T> {
+    n: usize,
+    data: Vec<T>,
+}
+
+impl<T: Copy + Default> SymMatrix<T> {
+    /// A `n × n` matrix of `T::default()`.
+    pub fn new(n: usize) -> Self {
+        SymMatrix {
+            n,
+            data: vec![T::default(); n * n.saturating_sub(1) / 2],
+        }
+    }
+
+    /// Build a matrix from its upper triangle in condensed (row-major) order.
+    ///
+    /// # Panics
+    /// If `condensed` does not hold exactly `n(n-1)/2` values.
+    pub fn from_condensed(n: usize, condensed: Vec<T>) -> Self {
+        assert_eq!(
+            condensed.len(),
+            n * n.saturating_sub(1) / 2,
+            "condensed matrix of {n} rows needs n(n-1)/2 values"
+        );
+        SymMatrix { n, data: condensed }
+    }
+
+    /// Number of rows (and columns).
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    /// Whether the matrix has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Entry `(i, j)`, equal to `(j, i)`; `T::default()` on the diagonal.
+    pub fn get(&self, i: usize, j: usize) -> T {
+        if i == j {
+            assert!(i < self.n, "index {i} out of range for {} rows", self.n);
+            return T::default();
+        }
+        self.data[self.offset(i, j)]
+    }
+
+    /// Set entries `(i, j)` and `(j, i)`.
+    ///
+    /// # Panics
+    /// If `i == j`: the diagonal is fixed at `T::default()`.
+    pub fn set(&mut self, i: usize, j: usize, value: T) {
+        assert_ne!(i, j, "the diagonal of a SymMatrix cannot be set");
+        let offset = self.offset(i, j);
+        self.data[offset] = value;
+    }
+
+    /// Every entry above the diagonal as `(i, j, value)` with `i < j`, in condensed order.
+    pub fn pairs(&self) -> impl Iterator<Item = (usize, usize, T)> + '_ {
+        let n = self.n;
+        (0..n)
+            .flat_map(move |i| (i + 1..n).map(move |j| (i, j)))
+            .zip(self.data.iter().copied())
+            .map(|((i, j), value)| (i, j, value))
+    }
+
+    /// The upper triangle in condensed order.
+    pub fn condensed(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Expand into a nested `Vec` with both triangles and the diagonal.
+    pub fn to_dense(&self) -> Vec<Vec<T>> {
+        (0..self.n).map(|i| self.row(i).collect()).collect()
+    }
+
+    /// Grow the matrix to `n` rows, keeping every existing entry; new entries are
+    /// `T::default()`.
+    ///
+    /// The condensed layout interleaves rows, so the entries are copied into a new
+    /// buffer; no distances are recomputed.
+    ///
+    /// # Panics
+    /// If `n` is smaller than the current size.
+    pub fn grow(&mut self, n: usize) {
+        assert!(n >= self.n, "cannot shrink a {} row matrix to {n}", self.n);
+        let mut grown = SymMatrix::new(n);
+        for (i, j, value) in self.pairs() {
+            grown.set(i, j, value);
+        }
+        *self = grown;
+    }
+
+    /// The matrix with rows and columns reordered: row `k` of the result is row
+    /// `order[k]` of `self`.
+    ///
+    /// # Panics
+    /// If an index of `order` is out of range. `order` should be a permutation; an
+    /// index listed twice duplicates its row.
+    pub fn permuted(&self, order: &[usize]) -> Self {
+        let k = order.len();
+        let condensed = (0..k)
+            .flat_map(|a| (a + 1..k).map(move |b| self.get(order[a], order[b])))
+            .collect();
+        SymMatrix::from_condensed(k, condensed)
+    }
+
+    /// Position of `(i, j)` in `data`, for `i != j` in either order.
+    fn offset(&self, i: usize, j: usize) -> usize {
+        let (i, j) = if i < j { (i, j) } else { (j, i) };
+        assert!(j < self.n, "index {j} out of range for {} rows", self.n);
+        // Rows 0..i hold (n - 1) + (n - 2) + … + (n - i) entries
+        i * (2 * self.n - i - 1) / 2 + (j - i - 1)
+    }
+}
+
+impl<T: Copy + Default> MatrixRows for SymMatrix<T> {
+    type Item = T;
+
+    fn size(&self) -> usize {
+        self.n
+    }
+
+    fn row(&self, i: usize) -> impl Iterator<Item = T> + '_ {
+        (0..self.n).map(move |j| self.get(i, j))
+    }
+
+    fn row_len(&self, _i: usize) -> usize {
+        self.n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_get_round_trip() {
+        let n = 5;
+        let mut m = SymMatrix::new(n);
+        for i in 0..n {
+            for j in i + 1..n {
+                m.set(j, i, (10 * i + j) as f64);
+            }
+        }
+
+        for i in 0..n {
+            assert_eq!(m.get(i, i), 0.0);
+            for j in i + 1..n {
+                assert_eq!(m.get(i, j), (10 * i + j) as f64);
+                assert_eq!(m.get(j, i), m.get(i, j));
+            }
+        }
+        assert_eq!(m.condensed().len(), n * (n - 1) / 2);
+
+        // Pairs come back in condensed order
+        let pairs: Vec<(usize, usize)> = m.pairs().map(|(i, j, _)| (i, j)).collect();
+        assert_eq!(pairs[..5], [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2)]);
+        assert!(m.pairs().all(|(i, j, v)| v == (10 * i + j) as f64));
+    }
+
+    #[test]
+    fn test_dense_matches_nested_vec() {
+        let dense = vec![vec![0, 1, 2], vec![1, 0, 3], vec![2, 3, 0]];
+        let m = SymMatrix::from_condensed(3, vec![1, 2, 3]);
+
+        assert_eq!(m.to_dense(), dense);
+        for i in 0..3 {
+            assert!(m.row(i).eq(dense.row(i)));
+        }
+        assert_eq!(
+            SymMatrix::<usize>::new(0).to_dense(),
+            Vec::<Vec<usize>>::new()
+        );
+        assert_eq!(SymMatrix::<usize>::new(1).to_dense(), vec![vec![0]]);
+    }
+
+    #[test]
+    fn test_grow_keeps_entries() {
+        let mut m = SymMatrix::from_condensed(3, vec![1, 2, 3]);
+        m.grow(5);
+
+        assert_eq!(m.len(), 5);
+        assert_eq!((m.get(0, 1), m.get(0, 2), m.get(1, 2)), (1, 2, 3));
+        assert!(
+            m.pairs()
+                .filter(|&(_, j, _)| j >= 3)
+                .all(|(_, _, v)| v == 0)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "diagonal")]
+    fn test_set_diagonal_panics() {
+        SymMatrix::new(2).set(1, 1, 1.0);
+    }
+}
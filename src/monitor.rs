@@ -0,0 +1,220 @@
+//! Online convergence diagnostics for trees arriving in batches.
+//!
+//! # Overview
+//! During a running MCMC analysis, trees are sampled continuously. A [`BatchMonitor`]
+//! scores each new batch without keeping the full history in memory: it only keeps
+//! a count of how often every partition has been seen so far.
+//!
+//! ```text
+//! batch 1 ──► report (no history yet) ──► counts += batch 1
+//! batch 2 ──► report vs counts of 1   ──► counts += batch 2
+//! batch 3 ──► report vs counts of 1+2 ──► counts += batch 3
+//! ```
+
+use crate::bitset::Bitset;
+use crate::distances::{DistanceError, rf_from_snapshots};
+use crate::snapshot::TreeSnapshot;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// Diagnostics for one batch of trees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchReport {
+    /// Mean pairwise RF distance between the trees of this batch (0.0 for < 2 trees)
+    pub mean_within: f64,
+
+    /// Mean RF distance from each tree of this batch to the majority-rule consensus
+    /// of all previously processed trees (`None` for the first batch)
+    pub mean_to_consensus: Option<f64>,
+
+    /// Number of distinct partitions in this batch that no earlier tree contained
+    pub new_partitions: usize,
+}
+
+/// Accumulates partition statistics across batches of trees.
+///
+/// # Accumulated state
+/// - `partition_counts`: for every partition seen so far, the number of trees containing it
+/// - `trees_seen`: total number of trees processed over all batches
+/// - `leaf_names`: the taxa of the first tree processed, which every later tree must share
+///
+/// Memory grows with the number of *distinct* partitions, not with the number of trees.
+#[derive(Debug, Clone, Default)]
+pub struct BatchMonitor {
+    partition_counts: HashMap<Bitset, usize>,
+    trees_seen: usize,
+    leaf_names: Option<Vec<String>>,
+}
+
+impl BatchMonitor {
+    /// Create a monitor with no history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total number of trees processed so far.
+    pub fn trees_seen(&self) -> usize {
+        self.trees_seen
+    }
+
+    /// Leaf names of the first tree processed, `None` before any tree was seen.
+    pub fn leaf_names(&self) -> Option<&[String]> {
+        self.leaf_names.as_deref()
+    }
+
+    /// Number of distinct partitions seen so far.
+    pub fn partitions_seen(&self) -> usize {
+        self.partition_counts.len()
+    }
+
+    /// Majority-rule consensus of all processed trees: partitions present in more than
+    /// half of them.
+    pub fn consensus_parts(&self) -> HashSet<Bitset> {
+        self.partition_counts
+            .iter()
+            .filter(|&(_, &count)| 2 * count > self.trees_seen)
+            .map(|(part, _)| part.clone())
+            .collect()
+    }
+
+    /// Score a batch against the history, then add it to the history.
+    ///
+    /// # Algorithm
+    /// 1. Mean pairwise [`rf_from_snapshots`] within the batch
+    /// 2. Mean RF from each tree to the consensus of the *previous* trees:
+    ///    `|A| + |C| - 2|A ∩ C|` (no root adjustment, the consensus has no root)
+    /// 3. Count batch partitions missing from `partition_counts`
+    /// 4. Add the batch to `partition_counts` and `trees_seen`
+    ///
+    /// # Errors
+    /// Returns [`DistanceError::LeafSetMismatch`] with the position in `snaps` of the
+    /// first tree whose leaf names differ from [`BatchMonitor::leaf_names`] (or, for
+    /// the first batch, from its first tree). The batch is then not added to the history.
+    pub fn process_batch(&mut self, snaps: &[TreeSnapshot]) -> Result<BatchReport, DistanceError> {
+        let reference = self
+            .leaf_names
+            .as_deref()
+            .or(snaps.first().map(|snap| snap.leaf_names.as_slice()));
+        if let Some(reference) = reference
+            && let Some(index) = snaps.iter().position(|snap| snap.leaf_names != reference)
+        {
+            return Err(DistanceError::LeafSetMismatch { index });
+        }
+        if self.leaf_names.is_none() {
+            self.leaf_names = snaps.first().map(|snap| snap.leaf_names.clone());
+        }
+
+        let n = snaps.len();
+
+        let num_pairs = n * n.saturating_sub(1) / 2;
+        let mean_within = if num_pairs == 0 {
+            0.0
+        } else {
+            let total: usize = (0..n)
+                .into_par_iter()
+                .flat_map_iter(|i| (i + 1..n).map(move |j| (i, j)))
+                .map(|(i, j)| rf_from_snapshots(&snaps[i], &snaps[j]))
+                .sum();
+            total as f64 / num_pairs as f64
+        };
+
+        let mean_to_consensus = (self.trees_seen > 0 && n > 0).then(|| {
+            let consensus = self.consensus_parts();
+            let total: usize = snaps
+                .iter()
+                .map(|snap| {
+                    let shared = snap.parts.iter().filter(|p| consensus.contains(*p)).count();
+                    snap.parts.len() + consensus.len() - 2 * shared
+                })
+                .sum();
+            total as f64 / n as f64
+        });
+
+        let new_parts: HashSet<&Bitset> = snaps
+            .iter()
+            .flat_map(|snap| &snap.parts)
+            .filter(|part| !self.partition_counts.contains_key(*part))
+            .collect();
+        let new_partitions = new_parts.len();
+
+        for snap in snaps {
+            for part in &snap.parts {
+                *self.partition_counts.entry(part.clone()).or_insert(0) += 1;
+            }
+        }
+        self.trees_seen += n;
+
+        Ok(BatchReport {
+            mean_within,
+            mean_to_consensus,
+            new_partitions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phylotree::tree::Tree as PhyloTree;
+
+    fn snapshot(newick: &str) -> TreeSnapshot {
+        TreeSnapshot::from_tree(&PhyloTree::from_newick(newick).unwrap(), false).unwrap()
+    }
+
+    /// Batch 1 agrees on {C,D,E} and {D,E}; batch 2 keeps {C,D,E} but swaps {D,E}
+    /// for {C,D} in one tree.
+    #[test]
+    fn test_batch_monitor_two_batches() {
+        let de = "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);";
+        let cd = "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);";
+
+        let mut monitor = BatchMonitor::new();
+
+        let first = monitor
+            .process_batch(&[snapshot(de), snapshot(de)])
+            .unwrap();
+        assert_eq!(first.mean_within, 0.0);
+        assert_eq!(first.mean_to_consensus, None);
+        assert_eq!(first.new_partitions, 2);
+        assert_eq!(monitor.trees_seen(), 2);
+
+        // Consensus of batch 1 is {C,D,E}, {D,E}: the `de` tree is at 0, `cd` at 2
+        let second = monitor
+            .process_batch(&[snapshot(de), snapshot(cd)])
+            .unwrap();
+        assert_eq!(second.mean_within, 2.0);
+        assert_eq!(second.mean_to_consensus, Some(1.0));
+        assert_eq!(second.new_partitions, 1);
+        assert_eq!(monitor.trees_seen(), 4);
+        assert_eq!(monitor.partitions_seen(), 3);
+    }
+
+    #[test]
+    fn test_batch_monitor_rejects_other_leaf_sets() {
+        let de = "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);";
+        let no_e = "((A:1,B:1):1,(C:1,D:1):1);";
+        let extra_f = "((A:1,B:1):1,(C:1,(D:1,(E:1,F:1):1):1):1);";
+
+        // Within the first batch, against its first tree
+        let mut monitor = BatchMonitor::new();
+        assert_eq!(
+            monitor.process_batch(&[snapshot(de), snapshot(no_e)]),
+            Err(DistanceError::LeafSetMismatch { index: 1 })
+        );
+        assert_eq!(monitor.trees_seen(), 0);
+        assert_eq!(monitor.leaf_names(), None);
+
+        // Across batches, against the taxa of the first batch
+        monitor.process_batch(&[snapshot(de)]).unwrap();
+        assert_eq!(
+            monitor.process_batch(&[snapshot(de), snapshot(extra_f)]),
+            Err(DistanceError::LeafSetMismatch { index: 1 })
+        );
+        assert_eq!(
+            monitor.process_batch(&[snapshot(no_e)]),
+            Err(DistanceError::LeafSetMismatch { index: 0 })
+        );
+        assert_eq!(monitor.trees_seen(), 1);
+        assert_eq!(monitor.leaf_names().unwrap(), ["A", "B", "C", "D", "E"]);
+    }
+}
@@ -0,0 +1,163 @@
+//! Approximate RF distances from MinHash sketches of partition sets.
+//!
+//! # Overview
+//! Exact RF over `n` trees with `p` partitions each costs O(n²·p) set lookups. A
+//! [`MinHashSketch`] summarizes a snapshot's partition set as `k` minimum hash
+//! values, one per hash function; the fraction of positions where two sketches agree
+//! estimates the Jaccard similarity `|A ∩ B| / |A ∪ B|` of the two partition sets,
+//! from which the RF distance follows (see [`estimated_rf`]).
+//!
+//! ```text
+//! snapshot ──► k hashes per partition ──► k minima (sketch)     O(k·p), once per tree
+//! sketch a, sketch b ──► fraction of equal minima ≈ Jaccard    O(k), per pair
+//! ```
+//!
+//! # Accuracy and speed
+//! The Jaccard estimate is unbiased with a standard error of about
+//! `sqrt(J·(1 − J) / k)`, so `k = 128` is within roughly ±0.04 and `k = 1024` within
+//! roughly ±0.016. Comparing two sketches costs O(k) regardless of the tree size, which
+//! pays off once `k` is smaller than the number of partitions or the pair count is
+//! large enough that building the sketches is negligible. The estimates suit
+//! clustering and visualization; use the exact metrics in [`crate::distances`] when
+//! individual distances matter.
+//!
+//! Sketches are only comparable when built with the same `k` from snapshots sharing
+//! the same leaf order. The hash values depend on the standard library's hasher, so
+//! do not persist sketches across builds.
+
+use crate::bitset::Bitset;
+use crate::snapshot::TreeSnapshot;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// The `k` smallest-hash signature of a snapshot's partition set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinHashSketch {
+    /// `signature[i]` is the minimum of hash function `i` over all partitions
+    /// (`u64::MAX` for a tree without partitions)
+    pub signature: Vec<u64>,
+}
+
+impl TreeSnapshot {
+    /// Sketch the partition set of this snapshot with `k` hash functions.
+    ///
+    /// Larger `k` gives a more accurate [`estimated_jaccard`] at O(k) cost per
+    /// comparison, see the [module docs](crate::sketch). Trivial partitions are
+    /// sketched too if the snapshot includes them; they are shared by all trees on the
+    /// same taxa and raise the Jaccard similarity accordingly.
+    ///
+    /// # Panics
+    /// If `k` is zero.
+    pub fn minhash(&self, k: usize) -> MinHashSketch {
+        assert!(k > 0, "a MinHash sketch needs at least one hash function");
+        let mut signature = vec![u64::MAX; k];
+        for part in &self.parts {
+            let base = base_hash(part);
+            for (seed, min) in signature.iter_mut().enumerate() {
+                *min = (*min).min(seeded_hash(base, seed as u64));
+            }
+        }
+        MinHashSketch { signature }
+    }
+}
+
+/// Estimate the Jaccard similarity of the partition sets behind two sketches: the
+/// fraction of hash functions whose minima agree.
+///
+/// Two trees without partitions estimate to 1.0.
+///
+/// # Panics
+/// If the sketches were built with different `k`.
+pub fn estimated_jaccard(a: &MinHashSketch, b: &MinHashSketch) -> f64 {
+    assert_eq!(
+        a.signature.len(),
+        b.signature.len(),
+        "sketches must use the same number of hash functions"
+    );
+    let agree = a
+        .signature
+        .iter()
+        .zip(&b.signature)
+        .filter(|(x, y)| x == y)
+        .count();
+    agree as f64 / a.signature.len() as f64
+}
+
+/// Estimate the RF distance between the trees behind two sketches.
+///
+/// `total_parts` is the summed number of partitions of both trees, `|A| + |B|`, e.g.
+/// `2 * (n - 3)` for two unrooted binary trees on `n` taxa without trivial partitions.
+/// With `J = |A ∩ B| / |A ∪ B|`, the union has `total_parts / (1 + J)` partitions and
+/// the RF distance is `total_parts · (1 − J) / (1 + J)`.
+///
+/// # Panics
+/// If the sketches were built with different `k`.
+pub fn estimated_rf(a: &MinHashSketch, b: &MinHashSketch, total_parts: usize) -> f64 {
+    let jaccard = estimated_jaccard(a, b);
+    total_parts as f64 * (1.0 - jaccard) / (1.0 + jaccard)
+}
+
+/// Hash a partition once; the `k` hash functions are derived from this value.
+fn base_hash(part: &Bitset) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    part.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash function `seed`, applied to a partition's base hash (SplitMix64 finalizer).
+fn seeded_hash(base: u64, seed: u64) -> u64 {
+    let mut z = base ^ seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distances::rf_from_snapshots;
+    use phylotree::tree::Tree as PhyloTree;
+
+    fn snapshot(newick: &str) -> TreeSnapshot {
+        TreeSnapshot::from_tree(&PhyloTree::from_newick(newick).unwrap(), false).unwrap()
+    }
+
+    fn exact_jaccard(a: &TreeSnapshot, b: &TreeSnapshot) -> f64 {
+        let shared = a.parts.intersection(&b.parts).count();
+        shared as f64 / a.parts.union(&b.parts).count() as f64
+    }
+
+    #[test]
+    fn test_estimated_jaccard_close_to_exact() {
+        let base = snapshot("(A,B,(C,(D,(E,(F,(G,(H,(I,J))))))));");
+        let others = [
+            snapshot("(A,B,(C,(D,(E,(F,(G,(H,(I,J))))))));"),
+            snapshot("(A,B,(C,(D,(E,(F,(H,(G,(I,J))))))));"),
+            snapshot("(A,B,(D,(C,(E,(G,(F,(H,(I,J))))))));"),
+            snapshot("(A,J,(I,(H,(G,(F,(E,(D,(C,B))))))));"),
+        ];
+        let k = 1024;
+        let sketch = base.minhash(k);
+
+        for other in &others {
+            let exact = exact_jaccard(&base, other);
+            let estimate = estimated_jaccard(&sketch, &other.minhash(k));
+            assert!(
+                (estimate - exact).abs() < 0.1,
+                "estimate {estimate} vs exact {exact}"
+            );
+
+            let total = base.parts.len() + other.parts.len();
+            let rf = rf_from_snapshots(&base, other) as f64;
+            let rf_estimate = estimated_rf(&sketch, &other.minhash(k), total);
+            assert!(
+                (rf_estimate - rf).abs() <= 0.2 * total as f64,
+                "RF estimate {rf_estimate} vs exact {rf}"
+            );
+        }
+
+        // Identical partition sets always agree exactly
+        assert_eq!(estimated_jaccard(&sketch, &others[0].minhash(k)), 1.0);
+        assert_eq!(estimated_rf(&sketch, &others[0].minhash(k), 14), 0.0);
+    }
+}
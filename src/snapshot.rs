@@ -23,16 +23,141 @@
 
 use crate::bitset::Bitset;
 use phylotree::tree::{Tree as PhyloTree, TreeError};
-use std::collections::{HashMap, HashSet};
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+/// Errors from saving or loading a cached snapshot (`serde` feature).
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum CacheError {
+    /// The file could not be read/written or is not a valid encoding.
+    Bincode(bincode::Error),
+    /// The decoded snapshot is internally inconsistent (e.g. a stale or foreign file).
+    Inconsistent { words: usize, num_leaves: usize },
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Bincode(e) => write!(f, "snapshot cache error: {e}"),
+            CacheError::Inconsistent { words, num_leaves } => write!(
+                f,
+                "snapshot has {words} words for {num_leaves} leaves, expected {}",
+                num_leaves.div_ceil(64)
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for CacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CacheError::Bincode(e) => Some(e),
+            CacheError::Inconsistent { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<bincode::Error> for CacheError {
+    fn from(e: bincode::Error) -> Self {
+        CacheError::Bincode(e)
+    }
+}
+
+/// Errors from building a [`TreeSnapshot`].
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The tree is empty or malformed, e.g. has unnamed leaves or, under
+    /// [`MissingLengthPolicy::Error`], a branch without a length.
+    Tree(TreeError),
+    /// Two leaves carry this name, so they would share a bit.
+    DuplicateLeafName(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Tree(e) => write!(f, "{e}"),
+            SnapshotError::DuplicateLeafName(name) => {
+                write!(f, "leaf name {name:?} appears more than once")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnapshotError::Tree(e) => Some(e),
+            SnapshotError::DuplicateLeafName(_) => None,
+        }
+    }
+}
+
+impl From<TreeError> for SnapshotError {
+    fn from(e: TreeError) -> Self {
+        SnapshotError::Tree(e)
+    }
+}
+
+/// Snapshots built by [`TreeSnapshot::build_snapshots`], with their tree names.
+pub type NamedSnapshots = Vec<(String, TreeSnapshot)>;
+
+/// Trees that [`TreeSnapshot::build_snapshots`] could not snapshot, with their names.
+pub type SnapshotFailures = Vec<(String, SnapshotError)>;
+
+/// How a snapshot treats branches without a length.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingLengthPolicy {
+    /// Use 0.0, as if the branch had no length
+    #[default]
+    Zero,
+    /// Use 1.0, e.g. for cladograms compared with weighted metrics
+    Unit,
+    /// Fail with `TreeError::MissingBranchLengths`
+    Error,
+}
+
+/// Options for [`TreeSnapshot::from_tree_with`]; the default matches
+/// [`TreeSnapshot::from_tree`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SnapshotOptions {
+    /// Drop every nontrivial partition whose branch length is below this, as if the
+    /// branch were contracted into a polytomy. This matches tools that collapse
+    /// near-zero branches, e.g. the explicit 0.0 internal branches common in BEAST
+    /// burn-in. Pendant (single-leaf) branches are never collapsed, and 0.0 keeps
+    /// every partition.
+    pub collapse_epsilon: f64,
+    /// What a branch without a length counts as. Only the branches of collected
+    /// partitions are checked, so with `include_trivial = false` a pendant branch may
+    /// lack a length even under [`MissingLengthPolicy::Error`].
+    pub missing_lengths: MissingLengthPolicy,
+    /// Overrides the rootedness inferred from the tree's shape when `Some`. A tree is
+    /// otherwise rooted when its root has two children, which is wrong for an unrooted
+    /// tree written with a binary root. NEXUS files declare it with a `[&R]`/`[&U]`
+    /// marker; see [`crate::io::rooted_marker`].
+    pub rooted: Option<bool>,
+}
 
 /// An immutable snapshot of all partitions in a phylogenetic tree.
 ///
 /// # Fields
-/// - `parts`: All bipartitions, **canonicalized** (stored in a HashSet for O(1) lookup)
-/// - `lengths`: Branch lengths for each partition (HashMap keyed by Bitset)
+/// - `parts`: All bipartitions, **canonicalized** (stored in a hash set for O(1) lookup)
+/// - `lengths`: Branch lengths for each partition (hash map keyed by Bitset)
 /// - `root_children`: Bitsets for immediate children of root (for rooted RF)
+/// - `root_lengths`: Branch lengths above the root's children (for root edges in
+///   weighted metrics)
 /// - `words`: Number of u64 words needed for bitsets
 /// - `num_leaves`: Total number of leaves (needed for canonicalization)
+/// - `leaf_names`: Taxon names in bit-index order
 /// - `rooted`: Whether the tree is rooted
 ///
 /// # Canonicalization
@@ -43,23 +168,38 @@ use std::collections::{HashMap, HashSet};
 /// # Performance
 /// Using HashSet and HashMap allows O(1) average-case lookups for Robinson-Foulds
 /// and weighted distance calculations, instead of O(n log n) with sorted vectors.
+/// Both use the unseeded `FxHasher` rather than the randomly seeded default, so
+/// iterating over `parts` or `lengths` (e.g. in [`rf_detailed`]) gives the same order
+/// in every run.
+///
+/// [`rf_detailed`]: crate::distances::rf_detailed_from_snapshots
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TreeSnapshot {
-    /// All partitions in the tree, canonicalized (HashSet for fast lookup)
-    pub parts: HashSet<Bitset>,
+    /// All partitions in the tree, canonicalized (hash set for fast lookup)
+    pub parts: FxHashSet<Bitset>,
 
     /// Branch length for each partition (keyed by the canonical Bitset)
-    pub lengths: HashMap<Bitset, f64>,
+    pub lengths: FxHashMap<Bitset, f64>,
 
     /// Bitsets of root's immediate children (for rooted tree adjustment)
     pub root_children: Vec<Bitset>,
 
+    /// Branch length above each of the root's children, keyed by the child's clade as
+    /// in `root_children` (not canonicalized). With a binary root both edges map to a
+    /// single canonical partition in `lengths`, which keeps only one of the two lengths.
+    pub root_lengths: FxHashMap<Bitset, f64>,
+
     /// Number of u64 words in each bitset
     pub words: usize,
 
     /// Total number of leaves (needed for computing complements)
     pub num_leaves: usize,
 
+    /// Taxon names, `leaf_names[i]` is the taxon at bit `i`; sorted alphabetically unless
+    /// built with [`TreeSnapshot::from_tree_with_order`]
+    pub leaf_names: Vec<String>,
+
     /// Whether this tree is rooted
     pub rooted: bool,
 }
@@ -69,7 +209,7 @@ impl TreeSnapshot {
     ///
     /// # Parameters
     /// - `tree`: The phylogenetic tree to extract partitions from
-    /// - `include_trivial`: If true, includes single-leaf partitions (needed for weighted metrics like Robinson-Foulds and Kuhner-Felsenstein)
+    /// - `include_trivial`: If true, includes single-leaf partitions (needed for weighted metrics like weighted Robinson-Foulds and Kuhner-Felsenstein)
     ///
     /// # Algorithm
     /// 1. Extract leaf names and sort them alphabetically for consistency
@@ -80,31 +220,144 @@ impl TreeSnapshot {
     /// 6. Canonicalize partitions (always store side without leaf with index 0)
     ///
     /// # Errors
-    /// Returns `TreeError` if the tree is empty, malformed, or has unnamed leaves.
-    pub fn from_tree(tree: &PhyloTree) -> Result<Self, TreeError> {
-        let rooted = tree.is_rooted()?;
-        // Step 1: Extract leaf names and sort them alphabetically
-        let mut leaf_names: Vec<(usize, String)> = tree
+    /// Returns [`SnapshotError::Tree`] if the tree is empty, malformed, or has unnamed
+    /// leaves; [`SnapshotError::DuplicateLeafName`] if two leaves share a name.
+    pub fn from_tree(tree: &PhyloTree, include_trivial: bool) -> Result<Self, SnapshotError> {
+        Self::from_tree_with(tree, include_trivial, SnapshotOptions::default())
+    }
+
+    /// Extract a snapshot like [`TreeSnapshot::from_tree`] with the given
+    /// [`SnapshotOptions`], which can be combined freely.
+    ///
+    /// # Example
+    /// ```
+    /// # use phylotree::tree::Tree;
+    /// # use rust_python_tree_distances::snapshot::{MissingLengthPolicy, SnapshotOptions, TreeSnapshot};
+    /// let tree = Tree::from_newick("((A:1,B:1):1e-9,(C:1,D:1));").unwrap();
+    /// let options = SnapshotOptions {
+    ///     collapse_epsilon: 1e-6,
+    ///     missing_lengths: MissingLengthPolicy::Unit,
+    ///     rooted: Some(false),
+    /// };
+    /// let snap = TreeSnapshot::from_tree_with(&tree, false, options).unwrap();
+    /// assert!(!snap.rooted);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns `TreeError::MissingBranchLengths` (as [`SnapshotError::Tree`]) under
+    /// [`MissingLengthPolicy::Error`], or another [`SnapshotError`] as in
+    /// [`TreeSnapshot::from_tree`].
+    pub fn from_tree_with(
+        tree: &PhyloTree,
+        include_trivial: bool,
+        options: SnapshotOptions,
+    ) -> Result<Self, SnapshotError> {
+        Self::from_ordered_leaves(
+            tree,
+            include_trivial,
+            options,
+            Self::sorted_leaf_names(tree)?,
+        )
+    }
+
+    /// Step 1 of [`TreeSnapshot::from_tree`]: the leaf names, sorted alphabetically.
+    ///
+    /// A leaf without a name cannot be matched across trees, so it is rejected with
+    /// `TreeError::UnnamedLeaves` instead of giving all unnamed leaves the same empty
+    /// name.
+    fn sorted_leaf_names(tree: &PhyloTree) -> Result<Vec<String>, TreeError> {
+        let mut leaf_names: Vec<String> = tree
             .get_leaves()
             .iter()
-            .map(|leaf_id| {
-                let leaf_name = tree.get(leaf_id).unwrap().name.clone().unwrap_or_default();
-                (*leaf_id, leaf_name)
+            .map(|leaf_id| match &tree.get(leaf_id)?.name {
+                Some(name) if !name.is_empty() => Ok(name.clone()),
+                _ => Err(TreeError::UnnamedLeaves),
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, _>>()?;
 
         // Sort by taxon name (alphabetically) for consistent ordering
-        leaf_names.sort_by(|a, b| a.1.cmp(&b.1));
+        leaf_names.sort();
+        Ok(leaf_names)
+    }
 
-        let num_leaves = leaf_names.len();
-        let words = num_leaves.div_ceil(64);
+    /// Extract a snapshot whose bit `i` is the taxon `leaf_order[i]`, instead of the
+    /// alphabetical order of [`TreeSnapshot::from_tree`].
+    ///
+    /// `leaf_order` may list taxa missing from the tree; their bits are never set.
+    /// Snapshots built with the same order share bit positions even when their leaf
+    /// sets differ, so their bitsets can be compared or masked directly, e.g. across
+    /// files with overlapping taxa.
+    ///
+    /// Partitions are canonicalized against the full order (the side without
+    /// `leaf_order[0]`), so for trees lacking some listed taxa the complement side
+    /// includes those taxa too.
+    ///
+    /// # Example
+    /// ```
+    /// # use phylotree::tree::Tree;
+    /// # use rust_python_tree_distances::snapshot::TreeSnapshot;
+    /// let order: Vec<String> = ["D", "C", "B", "A"].map(String::from).to_vec();
+    /// let tree = Tree::from_newick("((A,B),(C,D));").unwrap();
+    /// let snap = TreeSnapshot::from_tree_with_order(&tree, false, &order).unwrap();
+    /// assert_eq!(snap.leaf_names, order);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns `TreeError::DifferentTipIndices` (as [`SnapshotError::Tree`]) if the tree
+    /// has a leaf missing from `leaf_order`, or another [`SnapshotError`] as in
+    /// [`TreeSnapshot::from_tree`].
+    pub fn from_tree_with_order(
+        tree: &PhyloTree,
+        include_trivial: bool,
+        leaf_order: &[String],
+    ) -> Result<Self, SnapshotError> {
+        Self::from_ordered_leaves(
+            tree,
+            include_trivial,
+            SnapshotOptions::default(),
+            leaf_order.to_vec(),
+        )
+    }
 
-        // Step 2: Create mapping: node_id → bit_index (based on sorted names)
-        let node_id_to_leaf_index: HashMap<usize, usize> = leaf_names
+    /// Steps 2-6 of [`TreeSnapshot::from_tree`] with bit `i` assigned to `leaf_names[i]`.
+    fn from_ordered_leaves(
+        tree: &PhyloTree,
+        include_trivial: bool,
+        options: SnapshotOptions,
+        leaf_names: Vec<String>,
+    ) -> Result<Self, SnapshotError> {
+        // Step 2: Create mapping: node_id → bit_index (based on the leaf order)
+        let index_of: HashMap<&str, usize> = leaf_names
             .iter()
             .enumerate()
-            .map(|(idx, &(node_id, _))| (node_id, idx))
+            .map(|(idx, name)| (name.as_str(), idx))
             .collect();
+        let node_id_to_leaf_index: HashMap<usize, usize> = tree
+            .get_leaves()
+            .iter()
+            .map(|leaf_id| match &tree.get(leaf_id)?.name {
+                Some(name) if !name.is_empty() => index_of
+                    .get(name.as_str())
+                    .map(|&idx| (*leaf_id, idx))
+                    .ok_or(TreeError::DifferentTipIndices),
+                _ => Err(TreeError::UnnamedLeaves),
+            })
+            .collect::<Result<_, _>>()?;
+        // Two leaves with one name (e.g. two TRANSLATE IDs mapped to the same label) would
+        // share a bit and silently produce wrong partitions
+        let mut named = vec![false; leaf_names.len()];
+        for &idx in node_id_to_leaf_index.values() {
+            if std::mem::replace(&mut named[idx], true) {
+                return Err(SnapshotError::DuplicateLeafName(leaf_names[idx].clone()));
+            }
+        }
+
+        let rooted = match options.rooted {
+            Some(rooted) => rooted,
+            None => tree.is_rooted()?,
+        };
+        let num_leaves = leaf_names.len();
+        let words = num_leaves.div_ceil(64);
 
         // Step 3: Perform DFS to build bitsets for each node
         let root_id = tree.get_root()?;
@@ -112,110 +365,289 @@ impl TreeSnapshot {
         // Key: node_id, Value: Bitset of leaves under this node
         // Node_id, allows us to get a branch length associated with the partition
         let mut cache: HashMap<usize, Bitset> = HashMap::new();
-        Self::compute_bitsets(root_id, tree, &node_id_to_leaf_index, words, &mut cache);
+        Self::compute_bitsets(root_id, tree, &node_id_to_leaf_index, words, &mut cache)?;
 
         // Step 4: Collect partitions (with or without trivial partitions)
-        let (parts, lengths) = Self::collect_partitions(tree, root_id, &cache)?;
+        let (parts, lengths) = Self::collect_partitions(
+            tree,
+            root_id,
+            &cache,
+            include_trivial,
+            options.collapse_epsilon,
+            options.missing_lengths,
+        )?;
 
         // Step 5: Canonicalize partitions (always store side WITHOUT leaf 0)
         let (parts_canonical, lengths_canonical) =
-            Self::canonicalize_partitions(parts, lengths, words, num_leaves);
+            Self::canonicalize_partitions(parts, lengths, num_leaves);
 
         // Step 6: Record root's children for rooted tree adjustment
-        let root_children = Self::get_root_children(tree, root_id, &cache)?;
+        let (root_children, root_lengths) =
+            Self::get_root_children(tree, root_id, &cache, options.missing_lengths)?;
 
         Ok(TreeSnapshot {
             parts: parts_canonical,
             lengths: lengths_canonical,
             root_children,
+            root_lengths,
             words,
             num_leaves,
+            leaf_names,
             rooted,
         })
     }
 
-    /// Recursively compute bitsets for all nodes via DFS.
+    /// Build snapshots for many trees in parallel, consuming them.
+    ///
+    /// `PhyloTree` is `Send` but not `Sync`, so the trees are moved into the rayon
+    /// workers instead of being shared by reference; each tree is dropped as soon as
+    /// its snapshot exists. The output keeps the input order.
+    ///
+    /// # Errors
+    /// Returns a [`SnapshotError`] if any tree fails [`TreeSnapshot::from_tree`].
+    pub fn from_trees(
+        trees: Vec<PhyloTree>,
+        include_trivial: bool,
+    ) -> Result<Vec<Self>, SnapshotError> {
+        trees
+            .into_par_iter()
+            .map(|tree| Self::from_tree(&tree, include_trivial))
+            .collect()
+    }
+
+    /// Build snapshots for many named trees in parallel, keeping going past failures.
+    ///
+    /// Unlike [`TreeSnapshot::from_trees`], a tree that fails does not abort the batch:
+    /// it lands in the second list with its name and error, so callers can report and
+    /// skip it. Both lists keep the input order. The trees are consumed for the same
+    /// reason as in [`TreeSnapshot::from_trees`].
+    pub fn build_snapshots(
+        named_trees: Vec<(String, PhyloTree)>,
+        include_trivial: bool,
+    ) -> (NamedSnapshots, SnapshotFailures) {
+        let results: Vec<_> = named_trees
+            .into_par_iter()
+            .map(
+                |(name, tree)| match Self::from_tree(&tree, include_trivial) {
+                    Ok(snap) => Ok((name, snap)),
+                    Err(e) => Err((name, e)),
+                },
+            )
+            .collect();
+
+        let mut built = Vec::with_capacity(results.len());
+        let mut failed = Vec::new();
+        for result in results {
+            match result {
+                Ok(named) => built.push(named),
+                Err(named) => failed.push(named),
+            }
+        }
+        (built, failed)
+    }
+
+    /// Taxon names of the leaves in `part`, in bit-index order.
+    ///
+    /// Bits at or above `num_leaves` are ignored.
+    ///
+    /// # Example
+    /// Leaves A=0, B=1, C=2, D=3: bitset `0b1101` → `["A", "C", "D"]`
+    pub fn taxa(&self, part: &Bitset) -> Vec<&str> {
+        part.iter_ones_below(self.num_leaves)
+            .map(|idx| self.leaf_names[idx].as_str())
+            .collect()
+    }
+
+    /// Hash of the tree's topology: its leaf names, canonical partitions, root children
+    /// and rootedness. Branch lengths are ignored.
+    ///
+    /// Partitions are hashed in sorted order, so the value does not depend on the
+    /// `HashSet` iteration order and snapshots with [`same_topology`] always share it.
+    /// Different topologies may still collide; confirm with [`same_topology`].
+    ///
+    /// [`same_topology`]: TreeSnapshot::same_topology
+    pub fn topology_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.leaf_names.hash(&mut hasher);
+        self.rooted.hash(&mut hasher);
+        let mut parts: Vec<&Bitset> = self.parts.iter().collect();
+        parts.sort_unstable();
+        parts.hash(&mut hasher);
+        let mut root_children: Vec<&Bitset> = self.root_children.iter().collect();
+        root_children.sort_unstable();
+        root_children.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether both snapshots have the same leaf names, partitions, root children and
+    /// rootedness, so every topological metric puts them at distance zero.
+    pub fn same_topology(&self, other: &TreeSnapshot) -> bool {
+        let sorted = |children: &[Bitset]| {
+            let mut children = children.to_vec();
+            children.sort_unstable();
+            children
+        };
+        self.rooted == other.rooted
+            && self.leaf_names == other.leaf_names
+            && self.parts == other.parts
+            && sorted(&self.root_children) == sorted(&other.root_children)
+    }
+
+    /// Write this snapshot to `path` in bincode format.
+    ///
+    /// Building snapshots is the slow part of a run; saving them lets later runs
+    /// skip parsing and DFS entirely.
+    #[cfg(feature = "serde")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CacheError> {
+        let file = std::fs::File::create(path).map_err(|e| CacheError::Bincode(e.into()))?;
+        bincode::serialize_into(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Read a snapshot written by [`TreeSnapshot::save`].
+    ///
+    /// # Errors
+    /// Returns [`CacheError::Inconsistent`] if `words != num_leaves.div_ceil(64)`,
+    /// which every snapshot built by [`TreeSnapshot::from_tree`] satisfies.
+    #[cfg(feature = "serde")]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CacheError> {
+        let file = std::fs::File::open(path).map_err(|e| CacheError::Bincode(e.into()))?;
+        let snap: TreeSnapshot = bincode::deserialize_from(std::io::BufReader::new(file))?;
+        if snap.words != snap.num_leaves.div_ceil(64) {
+            return Err(CacheError::Inconsistent {
+                words: snap.words,
+                num_leaves: snap.num_leaves,
+            });
+        }
+        Ok(snap)
+    }
+
+    /// Compute bitsets for all nodes below `node_id` via an iterative post-order DFS.
     ///
     /// # Algorithm
     /// - **Leaf node**: Create bitset with single bit set
-    /// - **Internal node**: OR together all child bitsets
+    /// - **Internal node**: OR together all child bitsets, once all children are done
+    ///
+    /// The pending nodes live on a heap-allocated stack instead of the call stack, so
+    /// caterpillar trees with thousands of taxa (depth ≈ n) cannot overflow it. A node
+    /// is pushed once to expand its children and once more to merge them. Results are
+    /// cached to avoid recomputation.
     ///
-    /// Results are cached to avoid recomputation.
+    /// # Errors
+    /// Returns `TreeError::NodeNotFound` if a child id does not resolve to a node, and
+    /// `TreeError::LeafIndexNotInitialized` for a leaf without a bit index.
     fn compute_bitsets(
         node_id: usize,
         tree: &PhyloTree,
         node_id_to_leaf_index: &HashMap<usize, usize>,
         words: usize,
         cache: &mut HashMap<usize, Bitset>,
-    ) -> Bitset {
-        // Return cached result if available
-        if let Some(bitset) = cache.get(&node_id) {
-            return bitset.clone();
-        }
-
-        let node = tree.get(&node_id).expect("valid node");
-
-        // Base case: leaf node
-        if node.children.is_empty() {
-            let mut bitset = Bitset::zeros(words);
-            let leaf_idx = *node_id_to_leaf_index.get(&node_id).expect("leaf mapped");
-            bitset.set(leaf_idx);
-            cache.insert(node_id, bitset.clone());
-            return bitset;
-        }
-
-        // Recursive case: internal node
-        // Merge all child bitsets with OR
-        let mut bitset = Bitset::zeros(words);
-        for &child_id in &node.children {
-            let child_bitset =
-                Self::compute_bitsets(child_id, tree, node_id_to_leaf_index, words, cache);
-            bitset.or_assign(&child_bitset);
+    ) -> Result<Bitset, TreeError> {
+        // (node, whether its children are already in the cache)
+        let mut stack = vec![(node_id, false)];
+        while let Some((id, children_done)) = stack.pop() {
+            if cache.contains_key(&id) {
+                continue;
+            }
+            let node = tree.get(&id)?;
+
+            if node.children.is_empty() {
+                let mut bitset = Bitset::zeros(words);
+                let leaf_idx = *node_id_to_leaf_index
+                    .get(&id)
+                    .ok_or(TreeError::LeafIndexNotInitialized)?;
+                bitset.set(leaf_idx);
+                cache.insert(id, bitset);
+            } else if children_done {
+                // Merge all child bitsets with OR
+                let mut bitset = Bitset::zeros(words);
+                for child_id in &node.children {
+                    bitset.or_assign(&cache[child_id]);
+                }
+                cache.insert(id, bitset);
+            } else {
+                stack.push((id, true));
+                stack.extend(node.children.iter().map(|&child_id| (child_id, false)));
+            }
         }
 
-        cache.insert(node_id, bitset.clone());
-        bitset
+        Ok(cache[&node_id].clone())
     }
 
-    /// Collect all non-trivial partitions and their branch lengths.
+    /// Collect all partitions and their branch lengths.
     ///
     /// # Parameters
     /// - `include_trivial`: If true, includes single-leaf partitions (needed for weighted metrics)
+    /// - `collapse_epsilon`: Nontrivial partitions with a shorter branch are skipped
     ///
     /// # What we skip
     /// - Root node (doesn't create a bipartition)
     /// - Trivial partitions (single leaf) - unless `include_trivial` is true
+    /// - Nodes covering every leaf (a chain of single-child nodes above the root's
+    ///   leaf set splits nothing off)
+    ///
+    /// # Multifurcations
+    /// A node with k > 2 children still has exactly one parent edge, so it yields one
+    /// partition like any other node; the polytomy simply contributes fewer partitions
+    /// than a resolved subtree would. A star tree therefore has no nontrivial partitions.
     ///
     /// # Branch lengths
-    /// Some trees may have missing branch lengths.
-    /// We treat missing lengths as 0.0.
+    /// Some trees may have missing branch lengths; `missing_lengths` decides whether
+    /// they count as 0.0, as 1.0, or are an error.
     fn collect_partitions(
         tree: &PhyloTree,
         root_id: usize,
         cache: &HashMap<usize, Bitset>,
+        include_trivial: bool,
+        collapse_epsilon: f64,
+        missing_lengths: MissingLengthPolicy,
     ) -> Result<(Vec<Bitset>, Vec<f64>), TreeError> {
         let mut parts = Vec::new();
         let mut lengths = Vec::new();
+        let num_leaves = cache[&root_id].count_ones();
+
+        // Visit nodes in id order rather than HashMap order: when two nodes canonicalize
+        // to the same bitset (e.g. both children of a binary root) the last length wins,
+        // so the order must be reproducible.
+        let mut node_ids: Vec<usize> = cache.keys().copied().collect();
+        node_ids.sort_unstable();
 
         // Unless it becomes a bottleneck, we can parallelize this loop later
-        for (&node_id, bitset) in cache.iter() {
+        for node_id in node_ids {
+            let bitset = &cache[&node_id];
             // Skip root (doesn't create a partition)
             if node_id == root_id {
                 continue;
             }
 
             // Skip trivial partitions (single leaf) unless explicitly requested
-            if bitset.count_ones() <= 1 {
+            if !include_trivial && bitset.count_ones() <= 1 {
                 continue;
             }
 
-            // Add this partition
-            parts.push(bitset.clone());
+            // A node below the root that still holds every leaf has an empty other side
+            if bitset.count_ones() == num_leaves {
+                continue;
+            }
 
             // Get branch length leading TO this node (creates the partition)
             // This is the edge from parent to this node, not the sum of child edges
             let node = tree.get(&node_id)?;
-            let length: f64 = node.parent_edge.unwrap_or(0.0);
+            let length: f64 = match (node.parent_edge, missing_lengths) {
+                (Some(length), _) => length,
+                (None, MissingLengthPolicy::Zero) => 0.0,
+                (None, MissingLengthPolicy::Unit) => 1.0,
+                (None, MissingLengthPolicy::Error) => {
+                    return Err(TreeError::MissingBranchLengths);
+                }
+            };
+
+            // Collapse short internal branches
+            if collapse_epsilon > 0.0 && bitset.count_ones() > 1 && length < collapse_epsilon {
+                continue;
+            }
+
+            parts.push(bitset.clone());
             lengths.push(length);
         }
 
@@ -268,23 +700,21 @@ impl TreeSnapshot {
     /// Partition {C,D}: bitset 0b1100 (leaf 0 NOT set) → keep as 0b1100
     ///
     /// # Returns
-    /// Returns (HashSet<Bitset>, HashMap<Bitset, f64>) for O(1) lookups
+    /// Returns (FxHashSet<Bitset>, FxHashMap<Bitset, f64>) for O(1) lookups
     fn canonicalize_partitions(
         parts: Vec<Bitset>,
         lengths: Vec<f64>,
-        words: usize,
         num_leaves: usize,
-    ) -> (HashSet<Bitset>, HashMap<Bitset, f64>) {
-        let mut canonical_parts = HashSet::with_capacity(parts.len());
-        let mut canonical_lengths = HashMap::with_capacity(lengths.len());
-
-        for (bitset, length) in parts.into_iter().zip(lengths.into_iter()) {
-            // Check if leaf 0 (bit 0 of word 0) is set
-            let leaf_0_is_set = (bitset.0[0] & 1) != 0;
-
-            let canonical_bitset = if leaf_0_is_set {
+    ) -> (FxHashSet<Bitset>, FxHashMap<Bitset, f64>) {
+        let mut canonical_parts =
+            FxHashSet::with_capacity_and_hasher(parts.len(), Default::default());
+        let mut canonical_lengths =
+            FxHashMap::with_capacity_and_hasher(lengths.len(), Default::default());
+
+        for (bitset, length) in parts.into_iter().zip(lengths) {
+            let canonical_bitset = if bitset.get(0) {
                 // Flip to complement (side without leaf 0)
-                Self::compute_complement(&bitset, words, num_leaves)
+                bitset.complement(num_leaves)
             } else {
                 // Already canonical (leaf 0 not in this side)
                 bitset
@@ -297,51 +727,38 @@ impl TreeSnapshot {
         (canonical_parts, canonical_lengths)
     }
 
-    /// Compute the bitwise complement of a partition.
-    ///
-    /// Flips all bits up to num_leaves, keeping remaining bits as 0.
-    ///
-    /// # Example
-    /// Input:  0b0011 (4 leaves) → Output: 0b1100
-    /// Input:  0b1100 (4 leaves) → Output: 0b0011
-    fn compute_complement(bitset: &Bitset, words: usize, num_leaves: usize) -> Bitset {
-        let mut complement = Bitset::zeros(words);
-
-        for i in 0..num_leaves {
-            let word = i >> 6;
-            let bit = i & 63;
-
-            // Check if bit i is set in original
-            let is_set = (bitset.0[word] & (1u64 << bit)) != 0;
-
-            // Set bit i in complement if NOT set in original
-            if !is_set {
-                complement.0[word] |= 1u64 << bit;
-            }
-        }
-
-        complement
-    }
-
-    /// Sort partitions lexicographically for edge length matching later.
     /// Get bitsets for root's immediate children (for rooted RF adjustment).
     ///
     /// In rooted trees, we need to know if two trees have the same root
     /// position to apply the correct RF distance adjustment.
+    ///
+    /// Also returns the length of the edge above each child, keyed by its bitset. A
+    /// missing length counts as 1.0 under [`MissingLengthPolicy::Unit`] and as 0.0
+    /// otherwise; it is never an error, as only collected partitions are checked.
     fn get_root_children(
         tree: &PhyloTree,
         root_id: usize,
         cache: &HashMap<usize, Bitset>,
-    ) -> Result<Vec<Bitset>, TreeError> {
+        missing_lengths: MissingLengthPolicy,
+    ) -> Result<(Vec<Bitset>, FxHashMap<Bitset, f64>), TreeError> {
         let root = tree.get(&root_id)?;
-        let mut root_children: Vec<Bitset> = root
-            .children
-            .iter()
-            .filter_map(|&child_id| cache.get(&child_id).cloned())
-            .collect();
+        let mut root_children = Vec::with_capacity(root.children.len());
+        let mut root_lengths = FxHashMap::default();
+        for child_id in &root.children {
+            let Some(bitset) = cache.get(child_id) else {
+                continue;
+            };
+            let length = match (tree.get(child_id)?.parent_edge, missing_lengths) {
+                (Some(length), _) => length,
+                (None, MissingLengthPolicy::Unit) => 1.0,
+                (None, _) => 0.0,
+            };
+            root_children.push(bitset.clone());
+            root_lengths.insert(bitset.clone(), length);
+        }
 
         root_children.sort_unstable();
-        Ok(root_children)
+        Ok((root_children, root_lengths))
     }
 }
 
@@ -482,7 +899,7 @@ mod tests {
         node3.set(1); // B
         node3.set(2); // C
         assert_eq!(node3.0[0], 0b00110);
-        assert!((node3.0[0] & 1) == 0); // No A, keep as-is
+        assert!(!node3.get(0)); // No A, keep as-is
 
         // node2: {A, B, C}
         let mut node2 = Bitset::zeros(1);
@@ -490,7 +907,7 @@ mod tests {
         node2.set(1); // B
         node2.set(2); // C
         assert_eq!(node2.0[0], 0b00111);
-        assert!((node2.0[0] & 1) != 0); // Has A, need to flip!
+        assert!(node2.get(0)); // Has A, need to flip!
         // Flip to complement {D, E} = 0b11000
 
         // node1: {A, B, C, D}
@@ -500,7 +917,7 @@ mod tests {
         node1.set(2); // C
         node1.set(3); // D
         assert_eq!(node1.0[0], 0b01111);
-        assert!((node1.0[0] & 1) != 0); // Has A, need to flip!
+        assert!(node1.get(0)); // Has A, need to flip!
         // Flip to complement {E} = 0b10000
 
         // After canonicalization and sorting:
@@ -551,8 +968,8 @@ mod tests {
         // (the side without leaf 0)
 
         // Check if leaf 0 is set
-        assert!((part_ab.0[0] & 1) != 0); // A is in {A,B}
-        assert!((part_cd.0[0] & 1) == 0); // A is NOT in {C,D}
+        assert!(part_ab.get(0)); // A is in {A,B}
+        assert!(!part_cd.get(0)); // A is NOT in {C,D}
 
         // So we'd flip {A,B} to its complement {C,D}
     }
@@ -691,4 +1108,349 @@ mod tests {
         let length = 0.5;
         assert_eq!(length, 0.5);
     }
+
+    #[test]
+    fn test_taxa_from_partition() {
+        let tree = PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:1,E:1):1):1);").unwrap();
+        let snap = TreeSnapshot::from_tree(&tree, false).unwrap();
+
+        // Bit 40 is a stray bit beyond the 5 leaves
+        let part = Bitset::from_indices([2, 3, 4, 40], 1);
+        assert_eq!(snap.taxa(&part), vec!["C", "D", "E"]);
+    }
+
+    /// ```text
+    ///      root
+    ///     /    \
+    ///   {A,B}  {C,D}
+    /// ```
+    ///
+    /// Without trivial partitions only the {C,D} split is kept (both root children
+    /// canonicalize to it); with them, every leaf adds its own pendant partition.
+    #[test]
+    fn test_include_trivial_partitions() {
+        let tree = PhyloTree::from_newick("((A:1,B:2):1,(C:3,D:4):1);").unwrap();
+
+        let without = TreeSnapshot::from_tree(&tree, false).unwrap();
+        assert_eq!(without.parts.len(), 1);
+
+        let with = TreeSnapshot::from_tree(&tree, true).unwrap();
+        assert_eq!(with.parts.len(), 5);
+
+        // Leaf D is bit 3 and keeps its pendant length
+        assert_eq!(with.lengths[&Bitset::from_indices([3], 1)], 4.0);
+    }
+
+    #[test]
+    fn test_star_tree_has_no_nontrivial_partitions() {
+        let star = PhyloTree::from_newick("(A:1,B:1,C:1,D:1,E:1);").unwrap();
+
+        assert!(
+            TreeSnapshot::from_tree(&star, false)
+                .unwrap()
+                .parts
+                .is_empty()
+        );
+        // Only the five pendant edges remain when trivial partitions are requested
+        assert_eq!(TreeSnapshot::from_tree(&star, true).unwrap().parts.len(), 5);
+    }
+
+    #[test]
+    fn test_polytomy_resolutions() {
+        use crate::distances::rf_from_snapshots;
+
+        let snap = |nwk: &str| {
+            TreeSnapshot::from_tree(&PhyloTree::from_newick(nwk).unwrap(), false).unwrap()
+        };
+        // {C,D,E,F} is a polytomy; r1 resolves {C,D}, r2 resolves {D,E,F} and {E,F}
+        let base = snap("(A,B,(C,D,E,F));");
+        let r1 = snap("(A,B,((C,D),E,F));");
+        let r2 = snap("(A,B,(C,(D,(E,F))));");
+
+        assert_eq!(base.parts.len(), 1);
+        assert_eq!(rf_from_snapshots(&base, &r1), 1);
+        assert_eq!(rf_from_snapshots(&base, &r2), 2);
+        // The resolutions share {C,D,E,F} and nothing else: 1 + 2 extra splits differ
+        assert_eq!(rf_from_snapshots(&r1, &r2), 3);
+    }
+
+    #[test]
+    fn test_collapse_short_internal_branch() {
+        // {D,E} sits on a 1e-9 branch: effectively a polytomy (C,D,E)
+        let tree = PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:1,E:1):0.000000001):1);").unwrap();
+
+        let collapse = SnapshotOptions {
+            collapse_epsilon: 1e-6,
+            ..Default::default()
+        };
+        let kept = TreeSnapshot::from_tree(&tree, false).unwrap();
+        let collapsed = TreeSnapshot::from_tree_with(&tree, false, collapse).unwrap();
+        assert_eq!(collapsed.parts.len(), kept.parts.len() - 1);
+
+        // Pendant branches are never collapsed
+        let with_trivial = TreeSnapshot::from_tree(&tree, true).unwrap();
+        let collapsed_trivial = TreeSnapshot::from_tree_with(&tree, true, collapse).unwrap();
+        assert_eq!(collapsed_trivial.parts.len(), with_trivial.parts.len() - 1);
+
+        // Collapsing combines with the other options
+        let unit_collapsed = SnapshotOptions {
+            missing_lengths: MissingLengthPolicy::Unit,
+            rooted: Some(false),
+            ..collapse
+        };
+        let cladogram = PhyloTree::from_newick("((A,B),(C,(D,E):0.000000001));").unwrap();
+        let snap = TreeSnapshot::from_tree_with(&cladogram, true, unit_collapsed).unwrap();
+        assert_eq!(snap.parts.len(), with_trivial.parts.len() - 1);
+        assert!(snap.lengths.values().all(|&length| length == 1.0));
+        assert!(!snap.rooted);
+
+        // The default keeps even exact 0.0 branches
+        let zero = PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:1,E:1):0):1);").unwrap();
+        assert_eq!(
+            TreeSnapshot::from_tree(&zero, false).unwrap().parts.len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_explicit_leaf_order() {
+        let order: Vec<String> = ["E", "D", "C", "B", "A"].map(String::from).to_vec();
+        let tree_a = PhyloTree::from_newick("((A,B),(C,(D,E)));").unwrap();
+        let tree_b = PhyloTree::from_newick("((A,B),(D,(C,E)));").unwrap();
+        let snap_a = TreeSnapshot::from_tree_with_order(&tree_a, false, &order).unwrap();
+        let snap_b = TreeSnapshot::from_tree_with_order(&tree_b, false, &order).unwrap();
+
+        // Bit i is order[i] in both snapshots, so equal bitsets are equal splits
+        assert_eq!(snap_a.leaf_names, order);
+        let shared: Vec<&Bitset> = snap_a.parts.intersection(&snap_b.parts).collect();
+        assert_eq!(shared.len(), 1);
+        let mut taxa = snap_a.taxa(shared[0]);
+        taxa.sort();
+        assert_eq!(taxa, vec!["A", "B"]);
+
+        // The same splits as the alphabetical snapshots, only at other bit positions
+        assert_eq!(
+            crate::distances::rf_from_snapshots(&snap_a, &snap_b),
+            crate::distances::robinson_foulds(&tree_a, &tree_b).unwrap()
+        );
+
+        let missing = ["A", "B", "C", "D"].map(String::from);
+        assert!(matches!(
+            TreeSnapshot::from_tree_with_order(&tree_a, false, &missing),
+            Err(SnapshotError::Tree(TreeError::DifferentTipIndices))
+        ));
+    }
+
+    #[test]
+    fn test_unnamed_leaf_is_an_error() {
+        let tree = PhyloTree::from_newick("(A,B,(C,));").unwrap();
+
+        assert!(matches!(
+            TreeSnapshot::from_tree(&tree, false),
+            Err(SnapshotError::Tree(TreeError::UnnamedLeaves))
+        ));
+    }
+
+    #[test]
+    fn test_missing_length_policies() {
+        // The {D,E} branch has no length
+        let tree = PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:1,E:1)):1);").unwrap();
+        let de = Bitset::from_indices([3, 4], 1);
+        let policy = |missing_lengths| SnapshotOptions {
+            missing_lengths,
+            ..Default::default()
+        };
+
+        let zero =
+            TreeSnapshot::from_tree_with(&tree, true, policy(MissingLengthPolicy::Zero)).unwrap();
+        assert_eq!(zero.lengths[&de], 0.0);
+        assert_eq!(
+            zero.lengths,
+            TreeSnapshot::from_tree(&tree, true).unwrap().lengths
+        );
+
+        let unit =
+            TreeSnapshot::from_tree_with(&tree, true, policy(MissingLengthPolicy::Unit)).unwrap();
+        assert_eq!(unit.lengths[&de], 1.0);
+        assert!(unit.lengths.values().all(|&length| length == 1.0));
+
+        assert!(matches!(
+            TreeSnapshot::from_tree_with(&tree, true, policy(MissingLengthPolicy::Error)),
+            Err(SnapshotError::Tree(TreeError::MissingBranchLengths))
+        ));
+        let complete = PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:1,E:1):2):1);").unwrap();
+        assert!(
+            TreeSnapshot::from_tree_with(&complete, true, policy(MissingLengthPolicy::Error))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_build_snapshots_reports_failures_by_name() {
+        let named_trees: Vec<(String, PhyloTree)> = [
+            ("good_1", "((A,B),(C,D));"),
+            ("bad", "((A,B),(A,C));"),
+            ("good_2", "((A,C),(B,D));"),
+        ]
+        .into_iter()
+        .map(|(name, nwk)| (name.to_string(), PhyloTree::from_newick(nwk).unwrap()))
+        .collect();
+
+        let (built, failed) = TreeSnapshot::build_snapshots(named_trees, false);
+        let built_names: Vec<&str> = built.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(built_names, ["good_1", "good_2"]);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "bad");
+        assert!(matches!(&failed[0].1, SnapshotError::DuplicateLeafName(name) if name == "A"));
+    }
+
+    #[test]
+    fn test_deep_caterpillar_does_not_overflow() {
+        // (t0,(t1,(t2,...(t4998,t4999)...))); nests 5000 levels deep
+        let n = 5000;
+        let mut newick = format!("t{}", n - 1);
+        for i in (0..n - 1).rev() {
+            newick = format!("(t{i},{newick})");
+        }
+        newick.push(';');
+        let tree = PhyloTree::from_newick(&newick).unwrap();
+
+        let snap = TreeSnapshot::from_tree(&tree, false).unwrap();
+        assert_eq!(snap.num_leaves, n);
+        // One partition per internal node below the root
+        assert_eq!(snap.parts.len(), n - 2);
+    }
+
+    #[test]
+    fn test_duplicate_leaf_names_are_an_error() {
+        // As after renaming, when two TRANSLATE IDs map to the same label
+        let tree = PhyloTree::from_newick("((A:1,B:1):1,(A:1,C:1):1);").unwrap();
+        assert!(matches!(
+            TreeSnapshot::from_tree(&tree, false),
+            Err(SnapshotError::DuplicateLeafName(name)) if name == "A"
+        ));
+    }
+
+    #[test]
+    fn test_dangling_child_is_an_error() {
+        let mut tree = PhyloTree::from_newick("((A,B),(C,D));").unwrap();
+        let root = tree.get_root().unwrap();
+        tree.get_mut(&root).unwrap().children.push(999);
+
+        assert!(matches!(
+            TreeSnapshot::from_tree(&tree, false),
+            Err(SnapshotError::Tree(TreeError::NodeNotFound(999)))
+        ));
+    }
+
+    #[test]
+    fn test_from_trees_matches_sequential() {
+        // 50 caterpillars over the same taxa, each with a different leaf order and lengths
+        let taxa = ["A", "B", "C", "D", "E", "F", "G"];
+        let newicks: Vec<String> = (0..50)
+            .map(|i| {
+                let mut order = taxa.to_vec();
+                order.rotate_left(i % taxa.len());
+                order.swap(i % 3, 3 + i % 4);
+                let mut newick = format!("{}:{}", order[0], 1 + i % 5);
+                for (depth, taxon) in order.iter().enumerate().skip(1) {
+                    newick = format!("({newick},{taxon}:{}):{}", depth, 1 + i % 3);
+                }
+                format!("{newick};")
+            })
+            .collect();
+        let parse = || -> Vec<PhyloTree> {
+            newicks
+                .iter()
+                .map(|nwk| PhyloTree::from_newick(nwk).unwrap())
+                .collect()
+        };
+
+        for include_trivial in [false, true] {
+            let sequential: Vec<TreeSnapshot> = parse()
+                .iter()
+                .map(|tree| TreeSnapshot::from_tree(tree, include_trivial).unwrap())
+                .collect();
+            let parallel = TreeSnapshot::from_trees(parse(), include_trivial).unwrap();
+
+            assert_eq!(parallel.len(), 50);
+            for (par, seq) in parallel.iter().zip(&sequential) {
+                assert_eq!(par.parts, seq.parts);
+                assert_eq!(par.lengths, seq.lengths);
+                assert_eq!(par.root_children, seq.root_children);
+                assert_eq!(par.leaf_names, seq.leaf_names);
+            }
+        }
+    }
+
+    /// Building the same tree repeatedly must give identical partitions and lengths,
+    /// including for the root split whose two sides canonicalize to the same bitset
+    /// but carry different branch lengths (0.3 vs 0.7).
+    #[test]
+    fn test_snapshot_lengths_are_deterministic() {
+        let newick = "((A:1,B:1):0.3,(C:1,(D:1,E:1):0.2):0.7);";
+        let tree = PhyloTree::from_newick(newick).unwrap();
+
+        let first = TreeSnapshot::from_tree(&tree, false).unwrap();
+        for _ in 0..20 {
+            let again = TreeSnapshot::from_tree(&tree, false).unwrap();
+            assert_eq!(again.parts, first.parts);
+            assert_eq!(again.lengths, first.lengths);
+        }
+    }
+
+    #[test]
+    fn test_partition_iteration_order_is_reproducible() {
+        let newick = "((A:1,B:1):0.3,((C:1,F:1):0.4,(D:1,E:1):0.2):0.7,(G:1,H:1):0.1);";
+        let order = |snap: &TreeSnapshot| -> Vec<Bitset> { snap.parts.iter().cloned().collect() };
+
+        // Independently parsed trees, snapshotted separately, iterate identically
+        let first =
+            TreeSnapshot::from_tree(&PhyloTree::from_newick(newick).unwrap(), true).unwrap();
+        for _ in 0..10 {
+            let tree = PhyloTree::from_newick(newick).unwrap();
+            let again = TreeSnapshot::from_tree(&tree, true).unwrap();
+            assert_eq!(order(&again), order(&first));
+            let lengths: Vec<_> = again.lengths.iter().collect();
+            assert_eq!(lengths, first.lengths.iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_save_load_round_trip() {
+        use crate::distances::rf_from_snapshots;
+
+        let tree_a = PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:1,E:1):1):1);").unwrap();
+        let tree_b = PhyloTree::from_newick("((A:1,C:1):1,(B:1,(D:1,E:1):1):1);").unwrap();
+        let snap_a = TreeSnapshot::from_tree(&tree_a, false).unwrap();
+        let snap_b = TreeSnapshot::from_tree(&tree_b, false).unwrap();
+
+        let dir = std::env::temp_dir();
+        let path_a = dir.join(format!("snapshot_a_{}.bin", std::process::id()));
+        let path_b = dir.join(format!("snapshot_b_{}.bin", std::process::id()));
+        snap_a.save(&path_a).unwrap();
+        snap_b.save(&path_b).unwrap();
+        let loaded_a = TreeSnapshot::load(&path_a).unwrap();
+        let loaded_b = TreeSnapshot::load(&path_b).unwrap();
+
+        assert_eq!(loaded_a.parts, snap_a.parts);
+        assert_eq!(loaded_a.leaf_names, snap_a.leaf_names);
+        assert_eq!(
+            rf_from_snapshots(&loaded_a, &loaded_b),
+            rf_from_snapshots(&snap_a, &snap_b)
+        );
+
+        // A snapshot whose word count does not match its leaf count is rejected
+        let mut broken = snap_a.clone();
+        broken.words += 1;
+        broken.save(&path_a).unwrap();
+        assert!(matches!(
+            TreeSnapshot::load(&path_a),
+            Err(CacheError::Inconsistent { .. })
+        ));
+
+        std::fs::remove_file(path_a).unwrap();
+        std::fs::remove_file(path_b).unwrap();
+    }
 }
@@ -0,0 +1,252 @@
+//! Single-tree summary statistics.
+//!
+//! These complement the pairwise distance matrix with cheap per-tree numbers,
+//! computed directly on the parsed `PhyloTree`, plus the leaf-to-leaf (patristic)
+//! distance matrix of a single tree.
+
+use phylotree::tree::{Tree as PhyloTree, TreeError};
+use std::collections::{HashMap, HashSet};
+
+/// Undirected view of a tree: node id → [(neighbor id, edge length)].
+type Adjacency = HashMap<usize, Vec<(usize, f64)>>;
+
+/// Compute the tree diameter: the longest patristic (branch-length) path between two leaves.
+///
+/// # Algorithm
+/// Standard two-pass search, valid for non-negative edge weights:
+/// 1. From any leaf, find the farthest leaf `u`
+/// 2. From `u`, the farthest leaf distance is the diameter
+///
+/// Missing branch lengths are treated as 0.0, as in snapshot construction.
+///
+/// # Example
+/// ```text
+/// ((A:1,B:2):1,(C:3,D:1):1);
+///
+/// Longest path B → C: 2 + 1 + 1 + 3 = 7
+/// ```
+///
+/// # Errors
+/// Returns `TreeError` if the tree is empty or malformed.
+pub fn tree_diameter(tree: &PhyloTree) -> Result<f64, TreeError> {
+    diameter_with(tree, |length| length)
+}
+
+/// Compute the topological diameter: the largest number of edges between two leaves.
+///
+/// Same two-pass search as [`tree_diameter`], with every edge counted as 1.
+///
+/// # Errors
+/// Returns `TreeError` if the tree is empty or malformed.
+pub fn tree_topological_diameter(tree: &PhyloTree) -> Result<usize, TreeError> {
+    diameter_with(tree, |_| 1.0).map(|edges| edges as usize)
+}
+
+/// Compute the patristic (cophenetic) distance matrix of a tree: the sum of branch
+/// lengths on the path between every pair of leaves.
+///
+/// # Algorithm
+/// DFS from the root; each node returns its leaves with their distance to the node.
+/// At an internal node, every pair of leaves coming from two different children has
+/// that node as its last common ancestor, so their distance is the sum of both
+/// distances to it. Every pair is filled exactly once.
+///
+/// Leaves are ordered alphabetically, as in snapshots. Missing branch lengths are
+/// treated as 0.0.
+///
+/// # Example
+/// ```text
+/// ((A:1,B:1):1,(C:1,D:1):1);
+///
+/// d(A, B) = 1 + 1 = 2
+/// d(A, C) = 1 + 1 + 1 + 1 = 4
+/// ```
+///
+/// # Errors
+/// Returns `TreeError::IsEmpty` for an empty tree, `TreeError::UnnamedLeaves` if a
+/// leaf has no name, or another `TreeError` if the tree is malformed.
+pub fn patristic_matrix(tree: &PhyloTree) -> Result<(Vec<String>, Vec<Vec<f64>>), TreeError> {
+    let root_id = tree.get_root()?;
+
+    let mut named_leaves = tree
+        .get_leaves()
+        .into_iter()
+        .map(|id| match tree.get(&id)?.name.as_deref() {
+            Some(name) if !name.is_empty() => Ok((name.to_string(), id)),
+            _ => Err(TreeError::UnnamedLeaves),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if named_leaves.is_empty() {
+        return Err(TreeError::IsEmpty);
+    }
+    named_leaves.sort();
+
+    let leaf_index: HashMap<usize, usize> = named_leaves
+        .iter()
+        .enumerate()
+        .map(|(idx, (_, id))| (*id, idx))
+        .collect();
+    let mut matrix = vec![vec![0.0; named_leaves.len()]; named_leaves.len()];
+    leaf_distances(tree, root_id, &leaf_index, &mut matrix)?;
+
+    let names = named_leaves.into_iter().map(|(name, _)| name).collect();
+    Ok((names, matrix))
+}
+
+/// Leaves below `node_id` as `(leaf index, distance to node_id)`, filling `matrix` for
+/// every pair whose last common ancestor is `node_id` or one of its descendants.
+fn leaf_distances(
+    tree: &PhyloTree,
+    node_id: usize,
+    leaf_index: &HashMap<usize, usize>,
+    matrix: &mut [Vec<f64>],
+) -> Result<Vec<(usize, f64)>, TreeError> {
+    let node = tree.get(&node_id)?;
+    if node.children.is_empty() {
+        return Ok(vec![(leaf_index[&node_id], 0.0)]);
+    }
+
+    let mut below: Vec<(usize, f64)> = Vec::new();
+    for &child_id in &node.children {
+        let length = tree.get(&child_id)?.parent_edge.unwrap_or(0.0);
+        let child_leaves: Vec<(usize, f64)> = leaf_distances(tree, child_id, leaf_index, matrix)?
+            .into_iter()
+            .map(|(leaf, dist)| (leaf, dist + length))
+            .collect();
+
+        // Pairs split between this child and the earlier ones meet at this node
+        for &(i, dist_i) in &child_leaves {
+            for &(j, dist_j) in &below {
+                matrix[i][j] = dist_i + dist_j;
+                matrix[j][i] = dist_i + dist_j;
+            }
+        }
+        below.extend(child_leaves);
+    }
+
+    Ok(below)
+}
+
+fn diameter_with<W: Fn(f64) -> f64>(tree: &PhyloTree, weight: W) -> Result<f64, TreeError> {
+    let adjacency = build_adjacency(tree)?;
+    let leaves: HashSet<usize> = tree.get_leaves().into_iter().collect();
+    let Some(&start) = leaves.iter().min() else {
+        return Err(TreeError::IsEmpty);
+    };
+
+    let (u, _) = farthest_leaf(&adjacency, &leaves, start, &weight);
+    let (_, diameter) = farthest_leaf(&adjacency, &leaves, u, &weight);
+    Ok(diameter)
+}
+
+/// Build the undirected adjacency list by walking down from the root.
+fn build_adjacency(tree: &PhyloTree) -> Result<Adjacency, TreeError> {
+    let root_id = tree.get_root()?;
+    let mut adjacency: Adjacency = HashMap::new();
+    adjacency.entry(root_id).or_default();
+
+    let mut stack = vec![root_id];
+    while let Some(node_id) = stack.pop() {
+        for &child_id in &tree.get(&node_id)?.children {
+            let length = tree.get(&child_id)?.parent_edge.unwrap_or(0.0);
+            adjacency
+                .entry(node_id)
+                .or_default()
+                .push((child_id, length));
+            adjacency
+                .entry(child_id)
+                .or_default()
+                .push((node_id, length));
+            stack.push(child_id);
+        }
+    }
+
+    Ok(adjacency)
+}
+
+/// Find the leaf farthest from `start` (ties resolve to the lowest node id).
+///
+/// Uses an explicit stack so deep (caterpillar) trees cannot overflow the call stack.
+fn farthest_leaf<W: Fn(f64) -> f64>(
+    adjacency: &Adjacency,
+    leaves: &HashSet<usize>,
+    start: usize,
+    weight: &W,
+) -> (usize, f64) {
+    let mut best = (start, 0.0);
+    let mut stack = vec![(start, usize::MAX, 0.0)];
+
+    while let Some((node_id, parent_id, dist)) = stack.pop() {
+        if leaves.contains(&node_id) && (dist > best.1 || (dist == best.1 && node_id < best.0)) {
+            best = (node_id, dist);
+        }
+        for &(next_id, length) in adjacency.get(&node_id).into_iter().flatten() {
+            if next_id != parent_id {
+                stack.push((next_id, node_id, dist + weight(length)));
+            }
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ```text
+    ///            root
+    ///          /      \
+    ///       1 /        \ 1
+    ///        /          \
+    ///      node1       node2
+    ///     1/  \2       3/  \1
+    ///     A    B       C    D
+    /// ```
+    ///
+    /// Longest patristic path is B → C = 2 + 1 + 1 + 3 = 7,
+    /// longest topological path is any cross-root pair = 4 edges.
+    #[test]
+    fn test_tree_diameter() {
+        let tree = PhyloTree::from_newick("((A:1,B:2):1,(C:3,D:1):1);").unwrap();
+
+        assert_eq!(tree_diameter(&tree).unwrap(), 7.0);
+        assert_eq!(tree_topological_diameter(&tree).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_tree_diameter_caterpillar() {
+        // (((A,B),C),D): A → D crosses 4 edges, A → B only 2
+        let tree = PhyloTree::from_newick("(((A:1,B:1):1,C:1):1,D:1);").unwrap();
+
+        assert_eq!(tree_diameter(&tree).unwrap(), 4.0);
+        assert_eq!(tree_topological_diameter(&tree).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_patristic_matrix() {
+        let tree = PhyloTree::from_newick("((A:1,B:1):1,(C:1,D:1):1);").unwrap();
+        let (names, matrix) = patristic_matrix(&tree).unwrap();
+
+        assert_eq!(names, vec!["A", "B", "C", "D"]);
+        assert_eq!(matrix[0][1], 2.0); // A-B
+        assert_eq!(matrix[0][2], 4.0); // A-C
+        assert_eq!(matrix[2][3], 2.0); // C-D
+        for (i, row) in matrix.iter().enumerate() {
+            assert_eq!(row[i], 0.0);
+            for (j, &d) in row.iter().enumerate() {
+                assert_eq!(d, matrix[j][i]);
+            }
+        }
+
+        // Leaves are ordered alphabetically regardless of their order in the Newick
+        let shuffled = PhyloTree::from_newick("((D:1,C:1):1,(B:1,A:1):1);").unwrap();
+        assert_eq!(patristic_matrix(&shuffled).unwrap(), (names, matrix));
+
+        // The largest entry is the diameter
+        let tree = PhyloTree::from_newick("((A:1,B:2):1,(C:3,D:1):1);").unwrap();
+        let (_, matrix) = patristic_matrix(&tree).unwrap();
+        let max = matrix.iter().flatten().cloned().fold(0.0, f64::max);
+        assert_eq!(max, tree_diameter(&tree).unwrap());
+    }
+}
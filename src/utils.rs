@@ -0,0 +1,397 @@
+//! Tree utilities that operate on parsed `PhyloTree`s before snapshotting.
+
+use phylotree::tree::{Node, Tree as PhyloTree, TreeError};
+use std::collections::HashSet;
+
+/// Prune every tree down to the taxa shared by all trees.
+///
+/// # Algorithm
+/// 1. Intersect the leaf-name sets of all trees
+/// 2. For each tree whose leaf set differs, rebuild it without the absent tips:
+///    - subtrees without any kept tip are dropped
+///    - nodes left with a single child are suppressed, their edge lengths summed
+///    - a root left with a single child is replaced by that child
+///
+/// Trees that already have exactly the common taxa are left untouched.
+/// Internal node labels are not kept on rebuilt trees.
+///
+/// # Example
+/// ```text
+/// ((A:1,B:1):1,(C:1,(D:1,E:1):1):1);
+/// ((A:1,B:1):1,(C:1,(D:1,(E:1,F:1):1):1):1);   F is not shared
+///
+/// second tree becomes ((A:1,B:1):1,(C:1,(D:1,E:2):1):1);
+/// ```
+///
+/// Returns the common taxon names.
+///
+/// # Errors
+/// Returns `TreeError::IsEmpty` if the trees share no taxa, or another `TreeError`
+/// if a tree is malformed.
+pub fn prune_to_common_taxa(trees: &mut [PhyloTree]) -> Result<HashSet<String>, TreeError> {
+    let mut common: Option<HashSet<String>> = None;
+    for tree in trees.iter() {
+        let names = leaf_names(tree);
+        common = Some(match common {
+            Some(shared) => shared.intersection(&names).cloned().collect(),
+            None => names,
+        });
+    }
+    let common = common.unwrap_or_default();
+    if common.is_empty() {
+        return Err(TreeError::IsEmpty);
+    }
+
+    for tree in trees.iter_mut() {
+        if leaf_names(tree) != common {
+            prune_tree(tree, &common)?;
+        }
+    }
+
+    Ok(common)
+}
+
+/// Remove the tips named in `exclude` from every tree, e.g. to ignore an outgroup.
+///
+/// Trees are rebuilt as in [`prune_to_common_taxa`]: nodes left with a single child
+/// are suppressed and their edge lengths summed. Trees without any excluded tip are
+/// left untouched.
+///
+/// Returns the names of `exclude` that are not a tip of any tree, sorted, so callers
+/// can warn about misspelled taxa.
+///
+/// # Errors
+/// Returns `TreeError::IsEmpty` if a tree would lose all its tips, or another
+/// `TreeError` if a tree is malformed.
+pub fn exclude_taxa(trees: &mut [PhyloTree], exclude: &[String]) -> Result<Vec<String>, TreeError> {
+    let exclude: HashSet<&str> = exclude.iter().map(String::as_str).collect();
+    let mut found: HashSet<&str> = HashSet::new();
+
+    for tree in trees.iter_mut() {
+        let names = leaf_names(tree);
+        let keep: HashSet<String> = names
+            .iter()
+            .filter(|name| !exclude.contains(name.as_str()))
+            .cloned()
+            .collect();
+        if keep.len() == names.len() {
+            continue;
+        }
+        found.extend(exclude.iter().filter(|name| names.contains(**name)));
+        prune_tree(tree, &keep)?;
+    }
+
+    let mut missing: Vec<String> = exclude
+        .difference(&found)
+        .map(|name| name.to_string())
+        .collect();
+    missing.sort_unstable();
+    Ok(missing)
+}
+
+/// Rebuild `tree` with only the tips in `keep`, see [`prune_to_common_taxa`].
+///
+/// The new tree is built node by node rather than through Newick, so tip names
+/// containing spaces, `:`, `,` or parentheses are kept as they are.
+fn prune_tree(tree: &mut PhyloTree, keep: &HashSet<String>) -> Result<(), TreeError> {
+    let root_id = tree.get_root()?;
+    let (pruned, _) = pruned_subtree(tree, root_id, keep)?.ok_or(TreeError::IsEmpty)?;
+
+    let mut rebuilt = PhyloTree::new();
+    let root = rebuilt.add(pruned.node());
+    pruned.add_children(&mut rebuilt, root)?;
+    *tree = rebuilt;
+    Ok(())
+}
+
+/// Names of all leaves with a name.
+pub(crate) fn leaf_names(tree: &PhyloTree) -> HashSet<String> {
+    tree.get_leaves()
+        .iter()
+        .filter_map(|id| tree.get(id).ok()?.name.clone())
+        .collect()
+}
+
+/// Names listed at most in [`describe_leaf_set_difference`] per side before the
+/// rest is summarised as "and N more".
+const MAX_LISTED_TAXA: usize = 10;
+
+/// Describe how the leaf set of tree `idx` differs from `reference`, e.g.
+/// `missing in tree 5: [X, Y]; extra in tree 5: [Z]`.
+///
+/// Names are sorted and each list is capped at [`MAX_LISTED_TAXA`] entries.
+/// Returns `None` if both sets are equal.
+pub fn describe_leaf_set_difference(
+    reference: &HashSet<String>,
+    other: &HashSet<String>,
+    idx: usize,
+) -> Option<String> {
+    let mut missing: Vec<&str> = reference.difference(other).map(String::as_str).collect();
+    let mut extra: Vec<&str> = other.difference(reference).map(String::as_str).collect();
+    if missing.is_empty() && extra.is_empty() {
+        return None;
+    }
+    missing.sort_unstable();
+    extra.sort_unstable();
+
+    let mut parts = Vec::new();
+    if !missing.is_empty() {
+        parts.push(format!("missing in tree {idx}: {}", capped_list(&missing)));
+    }
+    if !extra.is_empty() {
+        parts.push(format!("extra in tree {idx}: {}", capped_list(&extra)));
+    }
+    Some(parts.join("; "))
+}
+
+/// `[a, b, c]`, or `[a, b, and N more]` past [`MAX_LISTED_TAXA`] names.
+pub(crate) fn capped_list(names: &[&str]) -> String {
+    if names.len() <= MAX_LISTED_TAXA {
+        return format!("[{}]", names.join(", "));
+    }
+    format!(
+        "[{}, and {} more]",
+        names[..MAX_LISTED_TAXA].join(", "),
+        names.len() - MAX_LISTED_TAXA
+    )
+}
+
+/// Leaf names in the left-to-right order of the tree drawn ladderized, e.g. to line up
+/// the tips of two trees in a tanglegram.
+///
+/// # Algorithm
+/// A DFS from the root that visits the children of every node by increasing number
+/// of leaves below them, so the smaller clade is drawn first. Ties are broken by the
+/// alphabetically first leaf name in each clade, which makes the order independent of
+/// how the Newick string listed the children.
+///
+/// # Example
+/// ```text
+/// ((((E,D),C),B),A);  →  [A, B, C, D, E]
+/// ```
+///
+/// # Errors
+/// Returns `TreeError::UnnamedLeaves` if a leaf has no name, or another `TreeError`
+/// if the tree is empty or malformed.
+pub fn ladderized_leaf_order(tree: &PhyloTree) -> Result<Vec<String>, TreeError> {
+    let root_id = tree.get_root()?;
+    Ok(ladderized_clade(tree, root_id)?.1)
+}
+
+/// Number of leaves below `node_id` and their names in ladderized order.
+fn ladderized_clade(tree: &PhyloTree, node_id: usize) -> Result<(usize, Vec<String>), TreeError> {
+    let node = tree.get(&node_id)?;
+
+    if node.children.is_empty() {
+        return match &node.name {
+            Some(name) if !name.is_empty() => Ok((1, vec![name.clone()])),
+            _ => Err(TreeError::UnnamedLeaves),
+        };
+    }
+
+    let mut clades = node
+        .children
+        .iter()
+        .map(|&child_id| ladderized_clade(tree, child_id))
+        .collect::<Result<Vec<_>, _>>()?;
+    clades.sort_by(|(size_a, leaves_a), (size_b, leaves_b)| {
+        let first = |leaves: &[String]| leaves.iter().min().cloned();
+        size_a
+            .cmp(size_b)
+            .then_with(|| first(leaves_a).cmp(&first(leaves_b)))
+    });
+
+    let size = clades.iter().map(|(size, _)| size).sum();
+    let leaves = clades.into_iter().flat_map(|(_, leaves)| leaves).collect();
+    Ok((size, leaves))
+}
+
+/// A subtree restricted to the kept tips, see [`pruned_subtree`].
+enum Pruned {
+    Tip(String),
+    /// Two or more children, each with the length of the edge above it
+    Inner(Vec<(Pruned, Option<f64>)>),
+}
+
+impl Pruned {
+    /// The node standing for this subtree's root, without children.
+    fn node(&self) -> Node {
+        match self {
+            Pruned::Tip(name) => Node::new_named(name),
+            Pruned::Inner(_) => Node::new(),
+        }
+    }
+
+    /// Add the children of this subtree below `parent` in `tree`.
+    fn add_children(self, tree: &mut PhyloTree, parent: usize) -> Result<(), TreeError> {
+        if let Pruned::Inner(children) = self {
+            for (child, length) in children {
+                let id = tree.add_child(child.node(), parent, length)?;
+                child.add_children(tree, id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The subtree at `node_id` restricted to `keep`, with the length of the edge above
+/// it. Returns `None` if no kept leaf is below `node_id`.
+fn pruned_subtree(
+    tree: &PhyloTree,
+    node_id: usize,
+    keep: &HashSet<String>,
+) -> Result<Option<(Pruned, Option<f64>)>, TreeError> {
+    let node = tree.get(&node_id)?;
+
+    if node.children.is_empty() {
+        return Ok(node
+            .name
+            .as_ref()
+            .filter(|name| keep.contains(*name))
+            .map(|name| (Pruned::Tip(name.clone()), node.parent_edge)));
+    }
+
+    let mut children = Vec::new();
+    for &child_id in &node.children {
+        if let Some(child) = pruned_subtree(tree, child_id, keep)? {
+            children.push(child);
+        }
+    }
+
+    Ok(match children.len() {
+        0 => None,
+        // Degree-2 node: splice it out and merge the two edges
+        1 => {
+            let (subtree, length) = children.pop().expect("one child");
+            let merged = match (length, node.parent_edge) {
+                (None, None) => None,
+                (a, b) => Some(a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+            };
+            Some((subtree, merged))
+        }
+        _ => Some((Pruned::Inner(children), node.parent_edge)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distances::robinson_foulds;
+
+    #[test]
+    fn test_ladderized_leaf_order() {
+        // Caterpillar written deepest-first: ladderizing puts the single leaves first
+        let caterpillar = PhyloTree::from_newick("((((E,D),C),B),A);").unwrap();
+        assert_eq!(
+            ladderized_leaf_order(&caterpillar).unwrap(),
+            vec!["A", "B", "C", "D", "E"]
+        );
+
+        // Equal-sized clades are ordered by their first leaf name
+        let balanced = PhyloTree::from_newick("((D,C),(B,A));").unwrap();
+        assert_eq!(
+            ladderized_leaf_order(&balanced).unwrap(),
+            vec!["A", "B", "C", "D"]
+        );
+
+        let unnamed = PhyloTree::from_newick("(A,(B,));").unwrap();
+        assert!(matches!(
+            ladderized_leaf_order(&unnamed),
+            Err(TreeError::UnnamedLeaves)
+        ));
+    }
+
+    #[test]
+    fn test_prune_to_common_taxa() {
+        let mut trees = vec![
+            PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:1,E:1):1):1);").unwrap(),
+            PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:1,(E:1,F:1):1):1):1);").unwrap(),
+            PhyloTree::from_newick("((A:1,C:1):1,(B:1,(D:1,(F:1,E:1):1):1):1);").unwrap(),
+        ];
+
+        let common = prune_to_common_taxa(&mut trees).unwrap();
+        let expected: HashSet<String> = ["A", "B", "C", "D", "E"].map(String::from).into();
+        assert_eq!(common, expected);
+
+        // Only F differed: after pruning the first two trees are identical
+        assert_eq!(robinson_foulds(&trees[0], &trees[1]).unwrap(), 0);
+        assert_eq!(robinson_foulds(&trees[0], &trees[2]).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_prune_merges_suppressed_edges() {
+        let mut trees = vec![
+            PhyloTree::from_newick("((A:1,B:1):1,(C:1,D:1):1);").unwrap(),
+            PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:2,X:1):0.5):1);").unwrap(),
+        ];
+        prune_to_common_taxa(&mut trees).unwrap();
+
+        let d = trees[1]
+            .get_leaves()
+            .into_iter()
+            .find(|id| trees[1].get(id).unwrap().name.as_deref() == Some("D"))
+            .unwrap();
+        assert_eq!(trees[1].get(&d).unwrap().parent_edge, Some(2.5));
+    }
+
+    #[test]
+    fn test_prune_keeps_names_with_newick_delimiters() {
+        let mut tree = PhyloTree::from_newick("((A:1,B:1):1,(C:1,(D:1,X:1):1):1);").unwrap();
+        for id in tree.get_leaves() {
+            let node = tree.get_mut(&id).unwrap();
+            if node.name.as_deref() == Some("A") {
+                node.name = Some("Homo sapiens (2021): a, b".to_string());
+            }
+        }
+        let mut trees = vec![tree];
+
+        exclude_taxa(&mut trees, &["X".to_string()]).unwrap();
+        let expected: HashSet<String> = ["Homo sapiens (2021): a, b", "B", "C", "D"]
+            .map(String::from)
+            .into();
+        assert_eq!(leaf_names(&trees[0]), expected);
+    }
+
+    #[test]
+    fn test_exclude_taxa() {
+        // X is a rogue taxon: it alone separates the two trees
+        let mut trees = vec![
+            PhyloTree::from_newick("((A:1,B:1):1,((C:1,X:1):1,(D:1,E:1):1):1);").unwrap(),
+            PhyloTree::from_newick("((A:1,(B:1,X:1):1):1,(C:1,(D:1,E:1):1):1);").unwrap(),
+        ];
+        assert_ne!(robinson_foulds(&trees[0], &trees[1]).unwrap(), 0);
+
+        let missing = exclude_taxa(&mut trees, &["X".to_string(), "Nope".to_string()]).unwrap();
+        assert_eq!(missing, ["Nope"]);
+        assert_eq!(robinson_foulds(&trees[0], &trees[1]).unwrap(), 0);
+        let expected: HashSet<String> = ["A", "B", "C", "D", "E"].map(String::from).into();
+        assert_eq!(leaf_names(&trees[0]), expected);
+        assert_eq!(leaf_names(&trees[1]), expected);
+
+        // Excluding every tip leaves nothing to compare
+        let mut single = vec![PhyloTree::from_newick("(A:1,B:1);").unwrap()];
+        let all = ["A", "B"].map(String::from);
+        assert!(matches!(
+            exclude_taxa(&mut single, &all),
+            Err(TreeError::IsEmpty)
+        ));
+    }
+
+    #[test]
+    fn test_describe_leaf_set_difference() {
+        let reference: HashSet<String> = ["A", "B", "C", "X", "Y"].map(String::from).into();
+        let other: HashSet<String> = ["A", "B", "C", "Z"].map(String::from).into();
+        assert_eq!(
+            describe_leaf_set_difference(&reference, &other, 5).as_deref(),
+            Some("missing in tree 5: [X, Y]; extra in tree 5: [Z]")
+        );
+        assert_eq!(
+            describe_leaf_set_difference(&reference, &reference, 1),
+            None
+        );
+
+        let many: HashSet<String> = (0..15).map(|i| format!("t{i:02}")).collect();
+        let diff = describe_leaf_set_difference(&many, &HashSet::new(), 2).unwrap();
+        assert!(diff.ends_with("t09, and 5 more]"), "{diff}");
+    }
+}
@@ -0,0 +1,80 @@
+//! End-to-end checks of the command-line binary.
+
+use std::fs;
+use std::process::Command;
+
+const NEXUS: &str = "#NEXUS
+Begin trees;
+tree STATE_0 = ((A:1,B:1):1,(C:1,D:1):1);
+tree STATE_500 = ((A:1,C:1):1,(B:1,D:1):1);
+tree STATE_1000 = ((A:1,D:1):1,(B:1,C:1):1);
+End;
+";
+
+#[test]
+fn stdout_output_is_a_clean_matrix() {
+    let dir = std::env::temp_dir().join(format!("rptd_cli_stdout_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("chain.trees");
+    fs::write(&input, NEXUS).unwrap();
+
+    // Without -q, so the timing messages are printed too
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-python-tree-distances"))
+        .arg("-i")
+        .arg(&input)
+        .args(["-o", "-"])
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    assert!(!output.stderr.is_empty());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<Vec<&str>> = stdout
+        .lines()
+        .map(|line| line.split('\t').collect())
+        .collect();
+    let names = [
+        "chain_tree_STATE0",
+        "chain_tree_STATE500",
+        "chain_tree_STATE1000",
+    ];
+    assert_eq!(rows.len(), names.len() + 1);
+    assert_eq!(rows[0][0], "");
+    assert_eq!(rows[0][1..], names);
+    for (i, row) in rows[1..].iter().enumerate() {
+        assert_eq!(row[0], names[i]);
+        let values: Vec<f64> = row[1..].iter().map(|v| v.parse().unwrap()).collect();
+        assert_eq!(values.len(), names.len());
+        assert_eq!(values[i], 0.0);
+    }
+}
+
+#[test]
+fn pair_states_follow_precision() {
+    let dir = std::env::temp_dir().join(format!("rptd_cli_pair_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("chain.trees");
+    fs::write(&input, NEXUS).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rust-python-tree-distances"))
+        .arg("-i")
+        .arg(&input)
+        .args([
+            "--pair-states",
+            "0,500",
+            "--metric",
+            "weighted",
+            "--precision",
+            "3",
+            "-q",
+        ])
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let (_, decimals) = stdout.trim().split_once('.').unwrap();
+    assert_eq!(decimals.len(), 3, "{stdout}");
+}
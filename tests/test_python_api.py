@@ -244,6 +244,34 @@ class TestPairwiseWeightedRF:
         assert all(matrix1[i][j] > 0 for i in range(len(matrix1)) for j in range(len(matrix1)) if i != j)
 
 
+class TestPairwiseNormRF:
+    """Tests for pairwise_norm_rf function."""
+
+    def test_norm_rf_in_unit_interval(self):
+        """Test that normalized RF values lie in [0, 1] with a zero diagonal."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        tree_names, matrix = rtd.pairwise_norm_rf(paths, burnin_trees=1)
+
+        assert len(tree_names) == 20
+        for i in range(len(matrix)):
+            assert matrix[i][i] == 0.0
+            assert all(0.0 <= v <= 1.0 for v in matrix[i])
+
+    def test_norm_rf_proportional_to_rf(self):
+        """Test that normalized RF is RF scaled by a single constant."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        _, rf_matrix = rtd.pairwise_rf(paths, burnin_trees=1)
+        _, norm_matrix = rtd.pairwise_norm_rf(paths, burnin_trees=1)
+
+        ratios = {
+            round(rf_matrix[i][j] / norm_matrix[i][j], 6)
+            for i in range(len(rf_matrix))
+            for j in range(len(rf_matrix))
+            if rf_matrix[i][j] > 0
+        }
+        assert len(ratios) == 1
+
+
 class TestPairwiseKF:
     """Tests for pairwise_kf (Kuhner-Felsenstein) function."""
 
@@ -315,6 +343,232 @@ class TestPairwiseKF:
         total_comparisons = len(kf_matrix) * (len(kf_matrix) - 1) // 2
         assert differences / total_comparisons > 0.9
 
+    def test_kf2_is_kf_squared(self):
+        """Test that pairwise_kf2 squares the KF distances."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        names_kf, kf_matrix = rtd.pairwise_kf(paths, burnin_trees=1)
+        names_kf2, kf2_matrix = rtd.pairwise_kf2(paths, burnin_trees=1)
+
+        assert names_kf == names_kf2
+        squared = [[d * d for d in row] for row in kf_matrix]
+        assert matrices_close(kf2_matrix, squared, rtol=1e-6)
+
+
+class TestPairwiseDistances:
+    """Tests for the pairwise_distances entry point."""
+
+    def test_matches_metric_specific_functions(self):
+        """Test that each metric name dispatches to the matching function."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        _, rf = rtd.pairwise_rf(paths, burnin_trees=1)
+        _, kf = rtd.pairwise_kf(paths, burnin_trees=1)
+        _, rf_generic = rtd.pairwise_distances(paths, burnin_trees=1)
+        _, kf_generic = rtd.pairwise_distances(paths, metric="kf", burnin_trees=1)
+
+        assert rf_generic == [[float(v) for v in row] for row in rf]
+        assert matrices_close(kf_generic, kf, rtol=1e-6, atol=1e-6)
+
+    def test_unknown_metric(self):
+        """Test that an unknown metric name raises ValueError listing valid names."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        with pytest.raises(ValueError, match="norm_rf"):
+            rtd.pairwise_distances(paths, metric="euclid")
+
+    def test_subset_matches_full_matrix(self):
+        """Test that a subset matrix equals the corresponding entries of the full one."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        names, full = rtd.pairwise_distances(paths, metric="kf", burnin_trees=1)
+        subset = [3, 0, 2]
+        sub_names, sub = rtd.pairwise_distances(
+            paths, metric="kf", burnin_trees=1, subset=subset
+        )
+
+        assert sub_names == [names[i] for i in subset]
+        assert matrices_close(sub, [[full[i][j] for j in subset] for i in subset])
+
+    def test_invalid_subset(self):
+        """Test that out-of-range and repeated subset indices raise ValueError."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        with pytest.raises(ValueError, match="out of range"):
+            rtd.pairwise_rf(paths, subset=[0, 100000])
+        with pytest.raises(ValueError, match="more than once"):
+            rtd.pairwise_rf(paths, subset=[1, 0, 1])
+
+    def test_cross_distances_with_itself(self):
+        """Test that comparing a set with itself gives the square matrix."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        names, full = rtd.pairwise_distances(paths, metric="kf", burnin_trees=1)
+        names_a, names_b, cross = rtd.cross_distances(paths, paths, metric="kf", burnin_trees=1)
+
+        assert names_a == names and names_b == names
+        assert all(cross[i][i] == 0.0 for i in range(len(names)))
+        assert matrices_close(cross, full)
+
+    def test_cross_distances_is_rectangular(self, tmp_path):
+        """Test that rows follow the first set, columns the second, and taxa must match."""
+        header = "#NEXUS\nBegin trees;\n"
+        path_a = tmp_path / "a.trees"
+        path_a.write_text(header + "tree STATE_0 = ((A:1,B:1):1,(C:1,D:1):1);\nEnd;\n")
+        path_b = tmp_path / "b.trees"
+        path_b.write_text(
+            header + "tree STATE_0 = ((A:1,B:1):1,(C:1,D:1):1);\n"
+            "tree STATE_10 = ((A:1,C:1):1,(B:1,D:1):1);\nEnd;\n"
+        )
+        names_a, names_b, cross = rtd.cross_distances(
+            [str(path_a)], [str(path_b)], use_real_taxa=False
+        )
+
+        assert names_a == ["a_tree_STATE0"]
+        assert names_b == ["b_tree_STATE0", "b_tree_STATE10"]
+        assert cross == [[0.0, 4.0]]
+
+        path_c = tmp_path / "c.trees"
+        path_c.write_text(header + "tree STATE_0 = ((A:1,B:1):1,(C:1,E:1):1);\nEnd;\n")
+        with pytest.raises(ValueError, match="different leaf set"):
+            rtd.cross_distances([str(path_a)], [str(path_c)], use_real_taxa=False)
+
+
+class TestPairwiseFromNewick:
+    """Tests for pairwise_rf_from_newick."""
+
+    def test_rf_from_strings(self):
+        """Test distances computed straight from Newick strings."""
+        newicks = [
+            "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+            "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);",
+            "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+        ]
+        matrix = rtd.pairwise_rf_from_newick(newicks)
+
+        assert matrix == [[0.0, 2.0, 0.0], [2.0, 0.0, 2.0], [0.0, 2.0, 0.0]]
+
+    def test_single_thread_matches_default(self):
+        """Test that threads=1 gives the same matrix as the default pool."""
+        newicks = [
+            "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);",
+            "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);",
+            "((A:1,C:1):1,(B:1,(D:1,E:1):1):1);",
+        ]
+        for metric in ["rf", "kf"]:
+            assert rtd.pairwise_rf_from_newick(
+                newicks, metric=metric, threads=1
+            ) == rtd.pairwise_rf_from_newick(newicks, metric=metric)
+
+    def test_progress_callback(self):
+        """Test that the progress callback ends at the total number of pairs."""
+        newicks = [
+            "((A,B),(C,(D,E)));",
+            "((A,B),(E,(C,D)));",
+            "((A,C),(B,(D,E)));",
+            "((A,E),(B,(D,C)));",
+        ]
+        calls = []
+        matrix = rtd.pairwise_rf_from_newick(
+            newicks, progress_callback=lambda done, total: calls.append((done, total))
+        )
+
+        assert matrix == rtd.pairwise_rf_from_newick(newicks)
+        assert calls
+        assert all(total == 6 for _, total in calls)
+        assert max(done for done, _ in calls) == 6
+
+    def test_rf_modes(self):
+        """Test that mode controls the +2 for different root splits of rooted trees."""
+        newicks = ["((A,B),(C,(D,E)));", "((A,(B,C)),(D,E));"]
+
+        assert rtd.pairwise_rf_from_newick(newicks)[0][1] == 4.0
+        assert rtd.pairwise_rf_from_newick(newicks, mode="rooted")[0][1] == 4.0
+        assert rtd.pairwise_rf_from_newick(newicks, mode="unrooted")[0][1] == 2.0
+        with pytest.raises(ValueError, match="mode"):
+            rtd.pairwise_rf_from_newick(newicks, mode="sideways")
+        with pytest.raises(ValueError, match="rf"):
+            rtd.pairwise_rf_from_newick(newicks, metric="kf", mode="unrooted")
+
+    def test_parse_error_names_index(self):
+        """Test that a malformed string is reported with its index."""
+        with pytest.raises(ValueError, match="index 1"):
+            rtd.pairwise_rf_from_newick(["((A,B),(C,D));", "((A,B"])
+
+
+class TestTreeDiameters:
+    """Tests for tree_diameters function."""
+
+    def test_one_diameter_per_tree(self):
+        """Test that every tree gets a positive diameter."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        tree_names, diameters = rtd.tree_diameters(paths, burnin_trees=1)
+
+        assert len(tree_names) == 20
+        assert len(diameters) == len(tree_names)
+        assert all(d > 0 for d in diameters)
+
+    def test_same_names_as_pairwise(self):
+        """Test that tree names line up with the pairwise functions."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        names_rf, _ = rtd.pairwise_rf(paths, burnin_trees=1)
+        names_diam, _ = rtd.tree_diameters(paths, burnin_trees=1)
+
+        assert names_rf == names_diam
+
+
+class TestBipartitionFrequencies:
+    """Tests for bipartition_frequencies function."""
+
+    def test_split_in_two_of_three_trees(self, tmp_path):
+        """Test that a split in two of three trees has frequency 2/3."""
+        path = tmp_path / "splits.trees"
+        path.write_text(
+            "#NEXUS\nBegin trees;\n"
+            "tree STATE_0 = ((A,B),(C,(D,E)));\n"
+            "tree STATE_500 = ((A,B),(C,(D,E)));\n"
+            "tree STATE_1000 = ((A,B),(E,(C,D)));\nEnd;\n"
+        )
+        table = rtd.bipartition_frequencies([str(path)], use_real_taxa=False)
+
+        assert table[0] == (["C", "D", "E"], 1.0)
+        assert table[1][0] == ["D", "E"]
+        assert abs(table[1][1] - 2 / 3) < 1e-12
+        assert [freq for _, freq in table] == sorted((f for _, f in table), reverse=True)
+
+
+class TestBatchMonitor:
+    """Tests for the BatchMonitor class."""
+
+    def test_two_batches(self):
+        """Test that the first batch has no history and the second is scored against it."""
+        de = "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);"
+        cd = "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);"
+        monitor = rtd.BatchMonitor()
+
+        assert monitor.process_batch([de, de]) == (0.0, None, 2)
+        assert monitor.process_batch([de, cd]) == (2.0, 1.0, 1)
+        assert monitor.trees_seen == 4
+
+    def test_beast_annotations(self):
+        """Test that BEAST annotations are stripped before parsing."""
+        de = "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);"
+        annotated = "((A:[&rate=0.5]1,B:1):1,(C:1,(D:1,E:1)[&height=2.0]:1):1);"
+        assert rtd.BatchMonitor().process_batch([de, annotated]) == (0.0, None, 2)
+
+    def test_invalid_newick(self):
+        """Test that unparsable trees raise ValueError naming their index."""
+        de = "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);"
+        with pytest.raises(ValueError, match="index 1"):
+            rtd.BatchMonitor().process_batch([de, "((A,B"])
+
+    def test_leaf_set_mismatch(self):
+        """Test that batches with other taxa than the first batch are rejected."""
+        de = "((A:1,B:1):1,(C:1,(D:1,E:1):1):1);"
+        no_e = "((A:1,B:1):1,(C:1,D:1):1);"
+        monitor = rtd.BatchMonitor()
+
+        with pytest.raises(ValueError, match=r"missing in tree 1: \[E\]"):
+            monitor.process_batch([de, no_e])
+        monitor.process_batch([de])
+        with pytest.raises(ValueError, match=r"Tree 0 of the batch.*missing in tree 0: \[E\]"):
+            monitor.process_batch([no_e])
+        assert monitor.trees_seen == 1
+
 
 class TestSanityChecks:
     """Tests for input validation and sanity checks."""
@@ -327,6 +581,98 @@ class TestSanityChecks:
         tree_names, _ = rtd.pairwise_rf(paths, burnin_trees=1)
         assert len(tree_names) > 0
 
+    def test_leaf_set_error_names_offending_taxa(self):
+        """Test that the leaf-set error lists the missing and extra taxa."""
+        with pytest.raises(
+            ValueError, match=r"missing in tree 1: \[D\]; extra in tree 1: \[X\]"
+        ):
+            rtd.pairwise_rf_from_newick(["((A,B),(C,D));", "((A,B),(C,X));"])
+
+    def test_incomplete_translate_block(self, tmp_path):
+        """Test that tips missing from the TRANSLATE block are reported."""
+        path = tmp_path / "incomplete.trees"
+        path.write_text(
+            "#NEXUS\nBegin trees;\n\tTranslate\n\t\t1 A,\n\t\t2 B,\n\t\t3 C\n;\n"
+            "tree STATE_0 = ((1:1,2:1):1,(3:1,4:1):1);\n"
+            "tree STATE_500 = ((1:1,3:1):1,(2:1,4:1):1);\nEnd;\n"
+        )
+        with pytest.raises(ValueError, match=r"TRANSLATE block: \[4\]"):
+            rtd.pairwise_rf([str(path)], use_real_taxa=True)
+
+    def test_rf_detailed_names_differing_splits(self):
+        """Test that rf_detailed lists the one split unique to each 4-taxon tree."""
+        distance, only_in_a, only_in_b = rtd.rf_detailed("(A,B,(C,D));", "(A,C,(B,D));")
+
+        assert distance == 2
+        assert only_in_a == [["C", "D"]]
+        assert only_in_b == [["B", "D"]]
+
+    def test_read_trees_info_states(self, tmp_path):
+        """Test that read_trees_info returns the STATE_ number of every kept tree."""
+        path = tmp_path / "chain.trees"
+        path.write_text(
+            "#NEXUS\nBegin trees;\n\tTranslate\n\t\t1 A,\n\t\t2 B,\n\t\t3 C,\n\t\t4 D\n;\n"
+            "tree STATE_0 = ((1:1,2:1):1,(3:1,4:1):1);\n"
+            "tree STATE_500 = ((1:1,3:1):1,(2:1,4:1):1);\n"
+            "tree STATE_1000 = ((1:1,4:1):1,(2:1,3:1):1);\nEnd;\n"
+        )
+        labels, names, states = rtd.read_trees_info([str(path)], burnin_trees=1)
+
+        assert labels == ["A", "B", "C", "D"]
+        assert states == [500, 1000]
+        assert [int(name.rsplit("STATE", 1)[1]) for name in names] == states
+
+        # Names line up with the rows of a distance matrix
+        matrix_names, _ = rtd.pairwise_rf([str(path)], burnin_trees=1)
+        assert names == matrix_names
+
+    def test_name_template(self, tmp_path):
+        """Test that name_template replaces the default tree names and file prefix."""
+        path = tmp_path / "chain.trees"
+        path.write_text(
+            "#NEXUS\nBegin trees;\n"
+            "tree STATE_0 = ((A:1,B:1):1,(C:1,D:1):1);\n"
+            "tree STATE_500 = ((A:1,C:1):1,(B:1,D:1):1);\n"
+            "tree STATE_1000 = ((A:1,D:1):1,(B:1,C:1):1);\nEnd;\n"
+        )
+        names, _ = rtd.pairwise_rf([str(path)], name_template="{index}")
+        assert names == ["0", "1", "2"]
+
+        names, _ = rtd.pairwise_distances(
+            [str(path)], burnin_trees=1, name_template="{base}.{index}:{state}"
+        )
+        assert names == ["chain.1:500", "chain.2:1000"]
+
+        _, info_names, _ = rtd.read_trees_info([str(path)], name_template="{index}")
+        assert info_names == ["0", "1", "2"]
+
+    def test_prune_to_common_is_noop_for_shared_taxa(self):
+        """Test that pruning leaves trees with identical taxa unchanged."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        names, matrix = rtd.pairwise_rf(paths, burnin_trees=1)
+        names_pruned, matrix_pruned = rtd.pairwise_rf(paths, burnin_trees=1, prune_to_common=True)
+
+        assert names == names_pruned
+        assert matrix == matrix_pruned
+
+    def test_exclude_taxa_removes_rogue_tip(self, tmp_path):
+        """Test that excluding a rogue taxon makes trees differing only in its placement equal."""
+        path = tmp_path / "rogue.trees"
+        path.write_text(
+            "#NEXUS\nBegin trees;\n\tTranslate\n\t\t1 A,\n\t\t2 B,\n\t\t3 C,\n\t\t4 D,\n"
+            "\t\t5 E,\n\t\t6 X\n;\n"
+            "tree STATE_0 = ((1:1,2:1):1,((3:1,6:1):1,(4:1,5:1):1):1);\n"
+            "tree STATE_500 = ((1:1,(2:1,6:1):1):1,(3:1,(4:1,5:1):1):1);\nEnd;\n"
+        )
+        _, matrix = rtd.pairwise_rf([str(path)])
+        assert matrix[0][1] > 0
+
+        _, matrix = rtd.pairwise_rf([str(path)], exclude_taxa=["X"])
+        assert matrix[0][1] == 0
+
+        with pytest.warns(UserWarning, match="Nope"):
+            rtd.pairwise_distances([str(path)], exclude_taxa=["X", "Nope"])
+
     def test_empty_file_list(self):
         """Test error handling for empty file list."""
         with pytest.raises((ValueError, TypeError)):
@@ -369,3 +715,103 @@ class TestAPIConsistency:
 if __name__ == "__main__":
     # Allow running tests directly
     pytest.main([__file__, "-v"])
+
+
+class TestTreeSet:
+    """Tests for the TreeSet class reusing snapshots across metrics."""
+
+    def test_matches_pairwise_functions(self):
+        """Test that every metric matches its standalone function."""
+        paths = [str(TEST_DATA / "hiv1.trees")]
+        trees = rtd.TreeSet(paths, burnin_trees=1)
+        names, rf = rtd.pairwise_rf(paths, burnin_trees=1)
+        _, wrf = rtd.pairwise_weighted_rf(paths, burnin_trees=1)
+        _, kf = rtd.pairwise_kf(paths, burnin_trees=1)
+
+        assert trees.names == names
+        assert len(trees) == len(names)
+        assert trees.rf() == rf
+        assert matrices_close(trees.weighted_rf(), wrf)
+        assert matrices_close(trees.kf(), kf)
+
+    def test_max_rf_bounds_rf(self, tmp_path):
+        """Test that max_rf follows 2n - 4 for rooted trees and bounds every RF."""
+        path = tmp_path / "rooted.trees"
+        path.write_text(
+            "#NEXUS\nBegin trees;\n"
+            "tree STATE_0 = ((A:1,B:1):1,(C:1,(D:1,E:1):1):1);\n"
+            "tree STATE_500 = ((A:1,C:1):1,(B:1,(D:1,E:1):1):1);\nEnd;\n"
+        )
+        trees = rtd.TreeSet([str(path)], use_real_taxa=False)
+
+        assert trees.max_rf == 2 * 5 - 4
+        assert all(d <= trees.max_rf for row in trees.rf() for d in row)
+
+    def test_medoid_is_central_tree(self, tmp_path):
+        """Test that medoid returns the tree closest to all others, by name and index."""
+        path = tmp_path / "medoid.trees"
+        path.write_text(
+            "#NEXUS\nBegin trees;\n"
+            "tree STATE_0 = (A:1,B:1,(D:1,(C:1,(E:1,F:1):1):1):1);\n"
+            "tree STATE_500 = (A:1,B:1,(C:1,(D:1,(E:1,F:1):1):1):1);\n"
+            "tree STATE_1000 = (A:1,B:1,(C:1,(E:1,(D:1,F:1):1):1):1);\nEnd;\n"
+        )
+        trees = rtd.TreeSet([str(path)], use_real_taxa=False)
+
+        name, index = trees.medoid()
+        assert (name, index) == ("medoid_tree_STATE500", 1)
+        assert trees.medoid(metric="kf") == (name, index)
+        with pytest.raises(ValueError):
+            trees.medoid(metric="unknown")
+
+    def test_sanity_check_in_constructor(self):
+        """Test that the constructor rejects files without trees."""
+        with pytest.raises(ValueError):
+            rtd.TreeSet([str(TEST_DATA / "hiv1.trees")], burnin_trees=10_000)
+
+
+class TestTreeSnapshot:
+    """Tests for TreeSnapshot objects and the single-pair distance functions."""
+
+    def test_rf_from_newick(self):
+        """Test snapshot properties and the RF distance of two Newick trees."""
+        a = rtd.TreeSnapshot.from_newick("((A:1,B:1):1,(C:1,(D:1,E:1):1):1);")
+        b = rtd.TreeSnapshot.from_newick("((A:1,B:1):1,(E:1,(C:1,D:1):1):1);")
+
+        assert a.num_leaves == 5
+        assert a.rooted
+        assert a.num_partitions == 2
+        assert rtd.rf(a, b) == 2
+        assert rtd.rf(a, a) == 0
+        assert rtd.rf(a, b) == rtd.pairwise_rf_from_newick(
+            ["((A:1,B:1):1,(C:1,(D:1,E:1):1):1);", "((A:1,B:1):1,(E:1,(C:1,D:1):1):1);"]
+        )[0][1]
+        assert rtd.weighted_rf(a, a) == 0.0
+        assert rtd.kf(a, b) > 0.0
+
+    def test_different_taxa(self):
+        """Test that comparing snapshots on different taxa raises ValueError."""
+        a = rtd.TreeSnapshot.from_newick("((A,B),(C,D));")
+        b = rtd.TreeSnapshot.from_newick("((A,B),(C,X));")
+        with pytest.raises(ValueError):
+            rtd.rf(a, b)
+
+    def test_from_file(self):
+        """Test that a snapshot read by index matches the pairwise matrix."""
+        path = str(TEST_DATA / "hiv1.trees")
+        _, rf = rtd.pairwise_rf([path], subset=[0, 1])
+        a = rtd.TreeSnapshot.from_file(path, 0)
+        b = rtd.TreeSnapshot.from_file(path, 1)
+
+        assert rtd.rf(a, b) == rf[0][1]
+        with pytest.raises(IndexError):
+            rtd.TreeSnapshot.from_file(path, 100_000)
+
+    def test_weighted_rf_components(self):
+        """Test that the weighted RF components separate shared and unique splits."""
+        a = rtd.TreeSnapshot.from_newick("(A:1,B:1,(C:1,(D:1,E:1):2):1.5);")
+        b = rtd.TreeSnapshot.from_newick("(A:1,B:1,(E:1,(C:1,D:1):3):0.5);")
+        shared_diff, unique_a, unique_b = rtd.weighted_rf_components(a, b)
+
+        assert (shared_diff, unique_a, unique_b) == (1.0, 2.0, 3.0)
+        assert shared_diff + unique_a + unique_b == pytest.approx(rtd.weighted_rf(a, b))